@@ -0,0 +1,30 @@
+use bpf_api::disasm::disassemble;
+use bpf_api::prog::{program_ids, Program};
+
+fn main() {
+    for id in program_ids() {
+        let id = id.expect("Failed to iterate program ids");
+
+        /*
+         * The program may have been unloaded since its id was returned.
+         */
+        let program = match Program::from_id(id) {
+            Ok(program) => program,
+            Err(_) => continue,
+        };
+
+        let info = program.info().expect("Failed to get program info");
+        let tag: String = info.tag.iter().map(|b| format!("{:02x}", b)).collect();
+        println!(
+            "{}: {:?}  name {}  tag {}  map_ids {:?}",
+            info.id, info.prog_type, info.name, tag, info.map_ids
+        );
+
+        let instructions = program
+            .xlated_instructions()
+            .expect("Failed to get xlated instructions");
+        for line in disassemble(&instructions) {
+            println!("   {}", line);
+        }
+    }
+}
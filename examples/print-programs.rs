@@ -71,6 +71,7 @@ fn main() {
         prog_type: ProgramType::RawTracepoint,
         expected_attach_type: None,
         attach_btf_id: None,
+        ..Default::default()
     };
 
     let bytecode = compiler.get_bytecode();
@@ -1,6 +1,6 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use bpf_api::collections::Queue;
-use bpf_api::probes::{AttachInfo, AttachType, Probe};
+use bpf_api::probes::{AttachType, Probe, UProbe};
 use bpf_api::prog::{Program, ProgramAttr, ProgramType};
 use bpf_script::compiler::Compiler;
 use bpf_script::types::{AddToTypeDatabase, TypeDatabase};
@@ -8,70 +8,40 @@ use bpf_script_derive::AddToTypeDatabase;
 use btf::Btf;
 use clap::Parser;
 
-fn get_function_address(image_path: &str, function: Option<&String>, dynamic: bool) -> Result<u64> {
-    let file = std::fs::read(image_path).context("Could not read file.")?;
-    let file_data = file.as_slice();
-
-    let mut file = elf::File::open_stream(file_data).expect("Could not parse ELF Header");
-
-    let (symtab, strtab) = if dynamic {
-        file.dynamic_symbol_table()
-            .context("Failed to read symbol table")?
-            .context("File contained no symbol table")?
-    } else {
-        file.symbol_table()
-            .context("Failed to read symbol table")?
-            .context("File contained no symbol table")?
-    };
-
-    let function = if let Some(function) = function {
-        function
-    } else {
-        return Ok(file.ehdr.e_entry);
-    };
-
-    Ok(symtab
-        .iter()
-        .find(|s| {
-            let name = strtab
-                .get(s.st_name as usize)
-                .expect("Malformed symbol table");
-            name == function
-        })
-        .context("Failed to find function address")?
-        .st_value)
-}
-
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// Path to a program or library to trace
+    /// Path to a program or library to trace; a library name, e.g.: libc.so.6, is looked up
     image_path: String,
 
-    /// The function to trace; entry is used, if omitted
+    /// The function to trace, optionally versioned, e.g.: malloc@GLIBC_2.2.5; entry is
+    /// used, if omitted
     function: Option<String>,
 
     /// Argument types (in order); used to format output
     #[arg(short, long)]
     arg: Vec<String>,
+
+    /// Only trace the given process
+    #[arg(short, long)]
+    pid: Option<u32>,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
     /*
-     * Before anything, try to find the function address for (image_path, function)
+     * Before anything, resolve the function to the file offset the uprobe goes at.
      */
-    let address = if let Ok(address) =
-        get_function_address(&args.image_path, args.function.as_ref(), false)
-    {
-        address
-    } else if let Ok(address) = get_function_address(&args.image_path, args.function.as_ref(), true)
-    {
-        address
-    } else {
-        bail!("Failed to find function address")
-    };
+    let uprobe = match (args.pid, &args.function) {
+        (Some(pid), Some(function)) => {
+            UProbe::by_symbol_in_process(pid, &args.image_path, function, 0)
+        }
+        (Some(pid), None) => UProbe::by_entry_in_process(pid, &args.image_path),
+        (None, Some(function)) => UProbe::by_symbol(&args.image_path, function, 0),
+        (None, None) => UProbe::by_entry(&args.image_path),
+    }
+    .context("Failed to find function")?;
 
     /*
      * Create a custom type database and add the `ExecEntry` structure to it.
@@ -137,6 +107,7 @@ fn main() -> Result<()> {
         prog_type: ProgramType::KProbe,
         expected_attach_type: Some(AttachType::PerfEvent),
         attach_btf_id: None,
+        ..Default::default()
     };
 
     let bytecode = compiler.get_bytecode();
@@ -145,8 +116,7 @@ fn main() -> Result<()> {
     /*
      * Create a probe and attach the program to it.
      */
-    let attach_info = AttachInfo::UProbe((args.image_path, address));
-    let mut probe = Probe::create(attach_info);
+    let mut probe = Probe::create(uprobe.into());
     probe.attach(&program).expect("Failed to attach program");
 
     fn from_cstr(buf: &[u8]) -> String {
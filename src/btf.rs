@@ -0,0 +1,514 @@
+//! Parsing of BTF, the BPF Type Format, as found in the .BTF section of BPF objects
+//! and in the kernel, e.g.: /sys/kernel/btf/vmlinux, and loading it into the kernel.
+
+pub use crate::platform::{btf_ids, Btf, FuncInfo, LineInfo};
+
+use crate::error::Error;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::collections::HashMap;
+
+const BTF_MAGIC: u16 = 0xeb9f;
+const BTF_HEADER_LEN: usize = 24;
+
+/* how many typedefs, modifiers and array levels are followed, as libbpf does */
+const MAX_RESOLVE_DEPTH: usize = 32;
+
+pub const BTF_KIND_INT: u32 = 1;
+pub const BTF_KIND_PTR: u32 = 2;
+pub const BTF_KIND_ARRAY: u32 = 3;
+pub const BTF_KIND_STRUCT: u32 = 4;
+pub const BTF_KIND_UNION: u32 = 5;
+pub const BTF_KIND_ENUM: u32 = 6;
+pub const BTF_KIND_FWD: u32 = 7;
+pub const BTF_KIND_TYPEDEF: u32 = 8;
+pub const BTF_KIND_VOLATILE: u32 = 9;
+pub const BTF_KIND_CONST: u32 = 10;
+pub const BTF_KIND_RESTRICT: u32 = 11;
+pub const BTF_KIND_FUNC: u32 = 12;
+pub const BTF_KIND_FUNC_PROTO: u32 = 13;
+pub const BTF_KIND_VAR: u32 = 14;
+pub const BTF_KIND_DATASEC: u32 = 15;
+pub const BTF_KIND_FLOAT: u32 = 16;
+pub const BTF_KIND_DECL_TAG: u32 = 17;
+pub const BTF_KIND_TYPE_TAG: u32 = 18;
+pub const BTF_KIND_ENUM64: u32 = 19;
+
+fn malformed(what: &str) -> Error {
+    Error::MalformedObject(format!("BTF: {}", what))
+}
+
+/// A member of a struct or union, or a parameter of a function prototype.
+#[derive(Clone, Debug)]
+pub struct BtfMember {
+    pub name_off: u32,
+    pub type_id: u32,
+    pub bit_offset: u32,
+    pub bitfield_size: u32,
+}
+
+/// A variable within a data section.
+#[derive(Copy, Clone, Debug)]
+pub struct BtfVarSecinfo {
+    pub type_id: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// A single BTF type. `size_or_type` is the size for sized types and the referenced
+/// type id for the others, as in the kernel's `struct btf_type`.
+#[derive(Clone, Debug)]
+pub struct BtfType {
+    pub name_off: u32,
+    pub kind: u32,
+    pub vlen: u32,
+    pub kind_flag: bool,
+    pub size_or_type: u32,
+
+    /// The offset of the type's record within the type section.
+    pub offset: usize,
+
+    /// For ints, the encoding word; for vars, the linkage; for decl tags, the
+    /// component index; for arrays, the element count.
+    pub extra: u32,
+
+    /// For arrays, the element type id.
+    pub elem_type: u32,
+
+    pub members: Vec<BtfMember>,
+    pub enum_values: Vec<(u32, i64)>,
+    pub secinfos: Vec<BtfVarSecinfo>,
+}
+
+/// Parsed BTF type information, either from an object's .BTF section or from the
+/// kernel (e.g.: /sys/kernel/btf/vmlinux).
+#[derive(Clone, Debug)]
+pub struct BtfTypes {
+    header: Vec<u8>,
+    type_data: Vec<u8>,
+    strings: Vec<u8>,
+
+    /// `void` followed by the types from `first_id` on.
+    types: Vec<BtfType>,
+
+    /// For split BTF, e.g.: a kernel module's, the first type id and string offset
+    /// after those of the base BTF; 1 and 0 otherwise.
+    first_id: u32,
+    first_str_off: u32,
+}
+
+impl BtfTypes {
+    /// Parses raw BTF data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The BTF blob, starting with the BTF header.
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        Self::parse_with_base(data, 1, 0)
+    }
+
+    /// Parses split BTF, e.g.: a kernel module's from /sys/kernel/btf/<module>, whose
+    /// type ids and string offsets continue from those of its base BTF. Only the split
+    /// types and strings are kept, so looking up the base's fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The BTF blob, starting with the BTF header.
+    /// * `base` - The BTF it's split from, usually vmlinux's.
+    pub fn parse_split(data: &[u8], base: &BtfTypes) -> Result<Self, Error> {
+        Self::parse_with_base(
+            data,
+            base.type_count(),
+            base.first_str_off + base.strings.len() as u32,
+        )
+    }
+
+    fn parse_with_base(data: &[u8], first_id: u32, first_str_off: u32) -> Result<Self, Error> {
+        if data.len() < BTF_HEADER_LEN || LittleEndian::read_u16(data) != BTF_MAGIC {
+            return Err(malformed("bad magic"));
+        }
+
+        let hdr_len = LittleEndian::read_u32(&data[4..]) as usize;
+        if hdr_len < BTF_HEADER_LEN || hdr_len > data.len() {
+            return Err(malformed("bad header length"));
+        }
+
+        let type_off = LittleEndian::read_u32(&data[8..]) as usize;
+        let type_len = LittleEndian::read_u32(&data[12..]) as usize;
+        let str_off = LittleEndian::read_u32(&data[16..]) as usize;
+        let str_len = LittleEndian::read_u32(&data[20..]) as usize;
+
+        let section = |off: usize, len: usize| {
+            let start = hdr_len.checked_add(off);
+            let end = start.and_then(|start| start.checked_add(len));
+            start
+                .zip(end)
+                .and_then(|(start, end)| data.get(start..end))
+                .ok_or_else(|| malformed("truncated"))
+        };
+        let type_data = section(type_off, type_len)?.to_vec();
+        let strings = section(str_off, str_len)?.to_vec();
+
+        let mut btf = Self {
+            header: data[..hdr_len].to_vec(),
+            type_data,
+            strings,
+            types: vec![BtfType::void()],
+            first_id,
+            first_str_off,
+        };
+        btf.parse_types()?;
+
+        Ok(btf)
+    }
+
+    fn parse_types(&mut self) -> Result<(), Error> {
+        let data = &self.type_data;
+        let u32_at = |off: usize| -> Result<u32, Error> {
+            data.get(off..off + 4)
+                .map(LittleEndian::read_u32)
+                .ok_or_else(|| malformed("truncated type"))
+        };
+
+        let mut off = 0;
+        while off < data.len() {
+            let info = u32_at(off + 4)?;
+            let mut t = BtfType {
+                name_off: u32_at(off)?,
+                kind: (info >> 24) & 0x1f,
+                vlen: info & 0xffff,
+                kind_flag: info >> 31 == 1,
+                size_or_type: u32_at(off + 8)?,
+                offset: off,
+                extra: 0,
+                elem_type: 0,
+                members: vec![],
+                enum_values: vec![],
+                secinfos: vec![],
+            };
+
+            let mut p = off + 12;
+            match t.kind {
+                BTF_KIND_INT | BTF_KIND_VAR | BTF_KIND_DECL_TAG => {
+                    t.extra = u32_at(p)?;
+                    p += 4;
+                }
+                BTF_KIND_ARRAY => {
+                    t.elem_type = u32_at(p)?;
+                    t.extra = u32_at(p + 8)?;
+                    p += 12;
+                }
+                BTF_KIND_STRUCT | BTF_KIND_UNION => {
+                    for _ in 0..t.vlen {
+                        let offset = u32_at(p + 8)?;
+                        let (bit_offset, bitfield_size) = if t.kind_flag {
+                            (offset & 0xffffff, offset >> 24)
+                        } else {
+                            (offset, 0)
+                        };
+                        t.members.push(BtfMember {
+                            name_off: u32_at(p)?,
+                            type_id: u32_at(p + 4)?,
+                            bit_offset,
+                            bitfield_size,
+                        });
+                        p += 12;
+                    }
+                }
+                BTF_KIND_ENUM => {
+                    for _ in 0..t.vlen {
+                        let value = if t.kind_flag {
+                            u32_at(p + 4)? as i32 as i64
+                        } else {
+                            u32_at(p + 4)? as i64
+                        };
+                        t.enum_values.push((u32_at(p)?, value));
+                        p += 8;
+                    }
+                }
+                BTF_KIND_ENUM64 => {
+                    for _ in 0..t.vlen {
+                        let value = (u32_at(p + 8)? as u64) << 32 | u32_at(p + 4)? as u64;
+                        t.enum_values.push((u32_at(p)?, value as i64));
+                        p += 12;
+                    }
+                }
+                BTF_KIND_FUNC_PROTO => {
+                    for _ in 0..t.vlen {
+                        t.members.push(BtfMember {
+                            name_off: u32_at(p)?,
+                            type_id: u32_at(p + 4)?,
+                            bit_offset: 0,
+                            bitfield_size: 0,
+                        });
+                        p += 8;
+                    }
+                }
+                BTF_KIND_DATASEC => {
+                    for _ in 0..t.vlen {
+                        t.secinfos.push(BtfVarSecinfo {
+                            type_id: u32_at(p)?,
+                            offset: u32_at(p + 4)?,
+                            size: u32_at(p + 8)?,
+                        });
+                        p += 12;
+                    }
+                }
+                BTF_KIND_PTR | BTF_KIND_FWD | BTF_KIND_TYPEDEF | BTF_KIND_VOLATILE
+                | BTF_KIND_CONST | BTF_KIND_RESTRICT | BTF_KIND_FUNC | BTF_KIND_FLOAT
+                | BTF_KIND_TYPE_TAG => {}
+                kind => return Err(malformed(&format!("unknown kind {}", kind))),
+            }
+
+            self.types.push(t);
+            off = p;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the raw BTF blob, including any modifications made since parsing.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = self.header.clone();
+        LittleEndian::write_u32(&mut header[8..], 0);
+        LittleEndian::write_u32(&mut header[12..], self.type_data.len() as u32);
+        LittleEndian::write_u32(&mut header[16..], self.type_data.len() as u32);
+        LittleEndian::write_u32(&mut header[20..], self.strings.len() as u32);
+
+        let mut data = header;
+        data.extend_from_slice(&self.type_data);
+        data.extend_from_slice(&self.strings);
+        data
+    }
+
+    /// Returns the string at the given offset of the string section.
+    pub fn string(&self, offset: u32) -> &str {
+        let bytes = offset
+            .checked_sub(self.first_str_off)
+            .and_then(|offset| self.strings.get(offset as usize..))
+            .unwrap_or(&[]);
+        let len = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+        std::str::from_utf8(&bytes[..len]).unwrap_or("")
+    }
+
+    /// Returns the number of type ids, including `void`.
+    pub fn type_count(&self) -> u32 {
+        self.first_id + self.types.len() as u32 - 1
+    }
+
+    /// Maps a type id to its position in `types`.
+    fn index(&self, id: u32) -> Option<usize> {
+        match id {
+            0 => Some(0),
+            id if id >= self.first_id => Some((id - self.first_id) as usize + 1),
+            _ => None,
+        }
+    }
+
+    /// Maps a position in `types` to its type id.
+    fn id_at(&self, index: usize) -> u32 {
+        match index {
+            0 => 0,
+            index => self.first_id + index as u32 - 1,
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Result<&BtfType, Error> {
+        self.index(id)
+            .and_then(|index| self.types.get(index))
+            .ok_or_else(|| malformed(&format!("type id {} out of range", id)))
+    }
+
+    pub fn name(&self, id: u32) -> Result<&str, Error> {
+        Ok(self.string(self.get(id)?.name_off))
+    }
+
+    /// Finds the id of the first type with the given name and kind.
+    pub fn find_by_name_kind(&self, name: &str, kind: u32) -> Option<u32> {
+        self.types
+            .iter()
+            .position(|t| t.kind == kind && t.name_off != 0 && self.string(t.name_off) == name)
+            .map(|index| self.id_at(index))
+    }
+
+    /// Returns an index of type ids by name, for repeated lookups in large BTF
+    /// like vmlinux's.
+    pub fn name_index(&self) -> HashMap<&str, Vec<u32>> {
+        let mut index: HashMap<&str, Vec<u32>> = HashMap::new();
+        for (i, t) in self.types.iter().enumerate() {
+            if t.name_off != 0 {
+                index
+                    .entry(self.string(t.name_off))
+                    .or_default()
+                    .push(self.id_at(i));
+            }
+        }
+        index
+    }
+
+    /// Follows typedefs and type modifiers (const, volatile, ...) to the underlying type.
+    pub fn skip_mods_and_typedefs(&self, mut id: u32) -> Result<u32, Error> {
+        for _ in 0..MAX_RESOLVE_DEPTH {
+            let t = self.get(id)?;
+            match t.kind {
+                BTF_KIND_TYPEDEF | BTF_KIND_VOLATILE | BTF_KIND_CONST | BTF_KIND_RESTRICT
+                | BTF_KIND_TYPE_TAG => id = t.size_or_type,
+                _ => return Ok(id),
+            }
+        }
+
+        Err(malformed(&format!("type id {} is a chain too deep", id)))
+    }
+
+    /// Sets the size of a data section and the offsets of its variables. Compilers leave
+    /// these to be filled in from the object's section and symbol tables, but the kernel
+    /// requires them when loading.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The type id of the `BTF_KIND_DATASEC`.
+    /// * `size` - The size of the section.
+    /// * `offsets` - The offset of each variable, in the order they're listed.
+    pub fn set_datasec_layout(&mut self, id: u32, size: u32, offsets: &[u32]) -> Result<(), Error> {
+        let secinfos: Vec<BtfVarSecinfo> = self
+            .get(id)?
+            .secinfos
+            .iter()
+            .zip(offsets)
+            .map(|(secinfo, offset)| BtfVarSecinfo {
+                offset: *offset,
+                ..*secinfo
+            })
+            .collect();
+        if offsets.len() != secinfos.len() {
+            return Err(malformed("wrong number of data section offsets"));
+        }
+
+        self.set_datasec_entries(id, size, &secinfos)
+    }
+
+    /// Sets the size of a data section and replaces its variables, keeping their number.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The type id of the `BTF_KIND_DATASEC`.
+    /// * `size` - The size of the section.
+    /// * `secinfos` - The new variables.
+    pub fn set_datasec_entries(
+        &mut self,
+        id: u32,
+        size: u32,
+        secinfos: &[BtfVarSecinfo],
+    ) -> Result<(), Error> {
+        let t = self.get_mut_kind(id, BTF_KIND_DATASEC)?;
+        if secinfos.len() != t.secinfos.len() {
+            return Err(malformed("wrong number of data section variables"));
+        }
+
+        t.size_or_type = size;
+        t.secinfos = secinfos.to_vec();
+
+        let offset = t.offset;
+        LittleEndian::write_u32(&mut self.type_data[offset + 8..], size);
+        for (i, secinfo) in secinfos.iter().enumerate() {
+            let p = offset + 12 + i * 12;
+            LittleEndian::write_u32(&mut self.type_data[p..], secinfo.type_id);
+            LittleEndian::write_u32(&mut self.type_data[p + 4..], secinfo.offset);
+            LittleEndian::write_u32(&mut self.type_data[p + 8..], secinfo.size);
+        }
+
+        Ok(())
+    }
+
+    /// Changes the type and linkage of a `BTF_KIND_VAR`.
+    pub fn set_var(&mut self, id: u32, type_id: u32, linkage: u32) -> Result<(), Error> {
+        let t = self.get_mut_kind(id, BTF_KIND_VAR)?;
+        t.size_or_type = type_id;
+        t.extra = linkage;
+
+        let offset = t.offset;
+        LittleEndian::write_u32(&mut self.type_data[offset + 8..], type_id);
+        LittleEndian::write_u32(&mut self.type_data[offset + 12..], linkage);
+        Ok(())
+    }
+
+    /// Changes the linkage of a `BTF_KIND_FUNC`, which is kept in its vlen.
+    pub fn set_func_linkage(&mut self, id: u32, linkage: u32) -> Result<(), Error> {
+        let t = self.get_mut_kind(id, BTF_KIND_FUNC)?;
+        t.vlen = linkage;
+
+        let offset = t.offset;
+        let info = LittleEndian::read_u32(&self.type_data[offset + 4..]);
+        LittleEndian::write_u32(&mut self.type_data[offset + 4..], info & !0xffff | linkage);
+        Ok(())
+    }
+
+    /// Appends a `BTF_KIND_VAR`, returning its type id.
+    pub fn add_var(&mut self, name_off: u32, type_id: u32, linkage: u32) -> u32 {
+        let offset = self.type_data.len();
+        for value in [name_off, BTF_KIND_VAR << 24, type_id, linkage] {
+            self.type_data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        self.types.push(BtfType {
+            name_off,
+            kind: BTF_KIND_VAR,
+            size_or_type: type_id,
+            offset,
+            extra: linkage,
+            ..BtfType::void()
+        });
+        self.type_count() - 1
+    }
+
+    fn get_mut_kind(&mut self, id: u32, kind: u32) -> Result<&mut BtfType, Error> {
+        self.index(id)
+            .and_then(|index| self.types.get_mut(index))
+            .filter(|t| t.kind == kind)
+            .ok_or_else(|| malformed(&format!("type id {} isn't of kind {}", id, kind)))
+    }
+
+    /// Computes the size of a type in bytes.
+    pub fn resolve_size(&self, id: u32) -> Result<u32, Error> {
+        self.resolve_size_at_depth(id, 0)
+    }
+
+    fn resolve_size_at_depth(&self, id: u32, depth: usize) -> Result<u32, Error> {
+        if depth >= MAX_RESOLVE_DEPTH {
+            return Err(malformed(&format!("type id {} is a chain too deep", id)));
+        }
+
+        let id = self.skip_mods_and_typedefs(id)?;
+        let t = self.get(id)?;
+        match t.kind {
+            BTF_KIND_INT | BTF_KIND_STRUCT | BTF_KIND_UNION | BTF_KIND_ENUM | BTF_KIND_ENUM64
+            | BTF_KIND_DATASEC | BTF_KIND_FLOAT => Ok(t.size_or_type),
+            BTF_KIND_PTR => Ok(8),
+            BTF_KIND_ARRAY => self
+                .resolve_size_at_depth(t.elem_type, depth + 1)?
+                .checked_mul(t.extra)
+                .ok_or_else(|| malformed(&format!("type id {} is too large", id))),
+            BTF_KIND_VAR => self.resolve_size_at_depth(t.size_or_type, depth + 1),
+            _ => Err(malformed(&format!("type id {} has no size", id))),
+        }
+    }
+}
+
+impl BtfType {
+    fn void() -> Self {
+        Self {
+            name_off: 0,
+            kind: 0,
+            vlen: 0,
+            kind_flag: false,
+            size_or_type: 0,
+            offset: 0,
+            extra: 0,
+            elem_type: 0,
+            members: vec![],
+            enum_values: vec![],
+            secinfos: vec![],
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::Error;
-use crate::platform::{Map, MapType};
+use crate::platform::{Map, MapFd, MapType};
 
 /// An array that exposes an idiomatic Rust interface to an underlying BPF array.
 pub struct Array<V: Copy + Default> {
@@ -77,3 +77,9 @@ impl<V: Copy + Default> Array<V> {
         self.map.get_identifier()
     }
 }
+
+impl<V: Copy + Default> MapFd for Array<V> {
+    fn map_fd(&self) -> u32 {
+        self.map.map_fd()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::Error;
-use crate::platform::{Map, MapType};
+use crate::platform::{Map, MapFd, MapType};
 
 /// A hashmap that exposes an idiomatic Rust interface to an underlying BPF hashmap.
 pub struct HashMap<K: Copy + Default, V: Copy + Default> {
@@ -97,3 +97,9 @@ impl<K: Copy + Default, V: Copy + Default> HashMap<K, V> {
         self.map.get_identifier()
     }
 }
+
+impl<K: Copy + Default, V: Copy + Default> MapFd for HashMap<K, V> {
+    fn map_fd(&self) -> u32 {
+        self.map.map_fd()
+    }
+}
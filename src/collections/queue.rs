@@ -1,5 +1,5 @@
 use crate::error::Error;
-use crate::platform::{Map, MapType};
+use crate::platform::{Map, MapFd, MapType};
 
 #[derive(Copy, Clone, Default)]
 struct Void {}
@@ -37,7 +37,7 @@ impl<V: Copy + Default> Queue<V> {
     /// use bpf_api::collections::Queue;
     ///
     /// let queue = Queue::<u32>::with_capacity(10).expect("Failed to create queue");
-    /// assert!(matches!(queue.pop(), Err(_)));
+    /// assert!(queue.pop().unwrap_err().is_not_found());
     /// ```
     pub fn pop(&self) -> Result<V, Error> {
         self.map.get_and_del(&Void::default())
@@ -87,3 +87,9 @@ impl<V: Copy + Default> Queue<V> {
         self.map.get_identifier()
     }
 }
+
+impl<V: Copy + Default> MapFd for Queue<V> {
+    fn map_fd(&self) -> u32 {
+        self.map.map_fd()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::error::{Error, Result};
-use crate::platform::{Map, MapType};
+use crate::platform::{Map, MapFd, MapType};
 
 use byteorder::{ByteOrder, NativeEndian};
 
@@ -84,7 +84,7 @@ impl RingBuffer {
         }
 
         // round up to the next power of 2 pages.
-        let pages = (min_capacity + (Self::PAGE_SIZE - 1)) / Self::PAGE_SIZE;
+        let pages = min_capacity.div_ceil(Self::PAGE_SIZE);
         let mut capacity = pages - 1;
         capacity |= capacity >> 1;
         capacity |= capacity >> 2;
@@ -225,3 +225,9 @@ impl RingBuffer {
         }
     }
 }
+
+impl MapFd for RingBuffer {
+    fn map_fd(&self) -> u32 {
+        self.map.map_fd()
+    }
+}
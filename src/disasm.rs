@@ -0,0 +1,550 @@
+//! A disassembler for raw eBPF instructions, producing text in the same syntax
+//! `bpftool prog dump xlated` uses.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::disasm::disassemble;
+//!
+//! /* r0 = 0; exit */
+//! let lines = disassemble(&[0x00000000000000b7, 0x0000000000000095]);
+//! assert_eq!(lines, vec!["0: (b7) r0 = 0", "1: (95) exit"]);
+//! ```
+
+/* instruction classes */
+const BPF_LD: u8 = 0x00;
+const BPF_LDX: u8 = 0x01;
+const BPF_ST: u8 = 0x02;
+const BPF_STX: u8 = 0x03;
+const BPF_ALU: u8 = 0x04;
+const BPF_JMP: u8 = 0x05;
+const BPF_JMP32: u8 = 0x06;
+const BPF_ALU64: u8 = 0x07;
+
+/* load/store modes */
+const BPF_IMM: u8 = 0x00;
+const BPF_ABS: u8 = 0x20;
+const BPF_IND: u8 = 0x40;
+const BPF_MEMSX: u8 = 0x80;
+const BPF_ATOMIC: u8 = 0xc0;
+
+/* operand source */
+const BPF_X: u8 = 0x08;
+
+/* alu/jmp operations that need special handling */
+const BPF_NEG: u8 = 0x80;
+const BPF_MOV: u8 = 0xb0;
+const BPF_END: u8 = 0xd0;
+const BPF_JA: u8 = 0x00;
+const BPF_CALL: u8 = 0x80;
+const BPF_EXIT: u8 = 0x90;
+
+/* atomic operations */
+const BPF_FETCH: i32 = 0x01;
+const BPF_XCHG: i32 = 0xe0 | BPF_FETCH;
+const BPF_CMPXCHG: i32 = 0xf0 | BPF_FETCH;
+
+/* pseudo source registers */
+const BPF_PSEUDO_MAP_FD: u8 = 1;
+const BPF_PSEUDO_MAP_VALUE: u8 = 2;
+const BPF_PSEUDO_BTF_ID: u8 = 3;
+const BPF_PSEUDO_FUNC: u8 = 4;
+const BPF_PSEUDO_MAP_IDX: u8 = 5;
+const BPF_PSEUDO_MAP_IDX_VALUE: u8 = 6;
+const BPF_PSEUDO_CALL: u8 = 1;
+const BPF_PSEUDO_KFUNC_CALL: u8 = 2;
+
+const HELPER_NAMES: [&str; 212] = [
+    "unspec",
+    "map_lookup_elem",
+    "map_update_elem",
+    "map_delete_elem",
+    "probe_read",
+    "ktime_get_ns",
+    "trace_printk",
+    "get_prandom_u32",
+    "get_smp_processor_id",
+    "skb_store_bytes",
+    "l3_csum_replace",
+    "l4_csum_replace",
+    "tail_call",
+    "clone_redirect",
+    "get_current_pid_tgid",
+    "get_current_uid_gid",
+    "get_current_comm",
+    "get_cgroup_classid",
+    "skb_vlan_push",
+    "skb_vlan_pop",
+    "skb_get_tunnel_key",
+    "skb_set_tunnel_key",
+    "perf_event_read",
+    "redirect",
+    "get_route_realm",
+    "perf_event_output",
+    "skb_load_bytes",
+    "get_stackid",
+    "csum_diff",
+    "skb_get_tunnel_opt",
+    "skb_set_tunnel_opt",
+    "skb_change_proto",
+    "skb_change_type",
+    "skb_under_cgroup",
+    "get_hash_recalc",
+    "get_current_task",
+    "probe_write_user",
+    "current_task_under_cgroup",
+    "skb_change_tail",
+    "skb_pull_data",
+    "csum_update",
+    "set_hash_invalid",
+    "get_numa_node_id",
+    "skb_change_head",
+    "xdp_adjust_head",
+    "probe_read_str",
+    "get_socket_cookie",
+    "get_socket_uid",
+    "set_hash",
+    "setsockopt",
+    "skb_adjust_room",
+    "redirect_map",
+    "sk_redirect_map",
+    "sock_map_update",
+    "xdp_adjust_meta",
+    "perf_event_read_value",
+    "perf_prog_read_value",
+    "getsockopt",
+    "override_return",
+    "sock_ops_cb_flags_set",
+    "msg_redirect_map",
+    "msg_apply_bytes",
+    "msg_cork_bytes",
+    "msg_pull_data",
+    "bind",
+    "xdp_adjust_tail",
+    "skb_get_xfrm_state",
+    "get_stack",
+    "skb_load_bytes_relative",
+    "fib_lookup",
+    "sock_hash_update",
+    "msg_redirect_hash",
+    "sk_redirect_hash",
+    "lwt_push_encap",
+    "lwt_seg6_store_bytes",
+    "lwt_seg6_adjust_srh",
+    "lwt_seg6_action",
+    "rc_repeat",
+    "rc_keydown",
+    "skb_cgroup_id",
+    "get_current_cgroup_id",
+    "get_local_storage",
+    "sk_select_reuseport",
+    "skb_ancestor_cgroup_id",
+    "sk_lookup_tcp",
+    "sk_lookup_udp",
+    "sk_release",
+    "map_push_elem",
+    "map_pop_elem",
+    "map_peek_elem",
+    "msg_push_data",
+    "msg_pop_data",
+    "rc_pointer_rel",
+    "spin_lock",
+    "spin_unlock",
+    "sk_fullsock",
+    "tcp_sock",
+    "skb_ecn_set_ce",
+    "get_listener_sock",
+    "skc_lookup_tcp",
+    "tcp_check_syncookie",
+    "sysctl_get_name",
+    "sysctl_get_current_value",
+    "sysctl_get_new_value",
+    "sysctl_set_new_value",
+    "strtol",
+    "strtoul",
+    "sk_storage_get",
+    "sk_storage_delete",
+    "send_signal",
+    "tcp_gen_syncookie",
+    "skb_output",
+    "probe_read_user",
+    "probe_read_kernel",
+    "probe_read_user_str",
+    "probe_read_kernel_str",
+    "tcp_send_ack",
+    "send_signal_thread",
+    "jiffies64",
+    "read_branch_records",
+    "get_ns_current_pid_tgid",
+    "xdp_output",
+    "get_netns_cookie",
+    "get_current_ancestor_cgroup_id",
+    "sk_assign",
+    "ktime_get_boot_ns",
+    "seq_printf",
+    "seq_write",
+    "sk_cgroup_id",
+    "sk_ancestor_cgroup_id",
+    "ringbuf_output",
+    "ringbuf_reserve",
+    "ringbuf_submit",
+    "ringbuf_discard",
+    "ringbuf_query",
+    "csum_level",
+    "skc_to_tcp6_sock",
+    "skc_to_tcp_sock",
+    "skc_to_tcp_timewait_sock",
+    "skc_to_tcp_request_sock",
+    "skc_to_udp6_sock",
+    "get_task_stack",
+    "load_hdr_opt",
+    "store_hdr_opt",
+    "reserve_hdr_opt",
+    "inode_storage_get",
+    "inode_storage_delete",
+    "d_path",
+    "copy_from_user",
+    "snprintf_btf",
+    "seq_printf_btf",
+    "skb_cgroup_classid",
+    "redirect_neigh",
+    "per_cpu_ptr",
+    "this_cpu_ptr",
+    "redirect_peer",
+    "task_storage_get",
+    "task_storage_delete",
+    "get_current_task_btf",
+    "bprm_opts_set",
+    "ktime_get_coarse_ns",
+    "ima_inode_hash",
+    "sock_from_file",
+    "check_mtu",
+    "for_each_map_elem",
+    "snprintf",
+    "sys_bpf",
+    "btf_find_by_name_kind",
+    "sys_close",
+    "timer_init",
+    "timer_set_callback",
+    "timer_start",
+    "timer_cancel",
+    "get_func_ip",
+    "get_attach_cookie",
+    "task_pt_regs",
+    "get_branch_snapshot",
+    "trace_vprintk",
+    "skc_to_unix_sock",
+    "kallsyms_lookup_name",
+    "find_vma",
+    "loop",
+    "strncmp",
+    "get_func_arg",
+    "get_func_ret",
+    "get_func_arg_cnt",
+    "get_retval",
+    "set_retval",
+    "xdp_get_buff_len",
+    "xdp_load_bytes",
+    "xdp_store_bytes",
+    "copy_from_user_task",
+    "skb_set_tstamp",
+    "ima_file_hash",
+    "kptr_xchg",
+    "map_lookup_percpu_elem",
+    "skc_to_mptcp_sock",
+    "dynptr_from_mem",
+    "ringbuf_reserve_dynptr",
+    "ringbuf_submit_dynptr",
+    "ringbuf_discard_dynptr",
+    "dynptr_read",
+    "dynptr_write",
+    "dynptr_data",
+    "tcp_raw_gen_syncookie_ipv4",
+    "tcp_raw_gen_syncookie_ipv6",
+    "tcp_raw_check_syncookie_ipv4",
+    "tcp_raw_check_syncookie_ipv6",
+    "ktime_get_tai_ns",
+    "user_ringbuf_drain",
+    "cgrp_storage_get",
+    "cgrp_storage_delete",
+];
+
+/// Returns the name of a BPF helper function, without the `bpf_` prefix, given its id.
+///
+/// # Arguments
+///
+/// * `id` - The helper function id, as encoded in the `imm` field of a call instruction.
+pub fn helper_name(id: u32) -> Option<&'static str> {
+    HELPER_NAMES.get(id as usize).copied()
+}
+
+/// A single decoded eBPF instruction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Instruction {
+    pub opcode: u8,
+    pub dst: u8,
+    pub src: u8,
+    pub offset: i16,
+    pub imm: i32,
+}
+
+impl From<u64> for Instruction {
+    fn from(raw: u64) -> Self {
+        Self {
+            opcode: raw as u8,
+            dst: (raw >> 8) as u8 & 0xf,
+            src: (raw >> 12) as u8 & 0xf,
+            offset: (raw >> 16) as i16,
+            imm: (raw >> 32) as i32,
+        }
+    }
+}
+
+impl Instruction {
+    /// The instruction class, e.g.: `BPF_ALU64`.
+    fn class(&self) -> u8 {
+        self.opcode & 0x07
+    }
+
+    /// The size field of load/store instructions.
+    fn size(&self) -> &'static str {
+        match self.opcode & 0x18 {
+            0x00 => "u32",
+            0x08 => "u16",
+            0x10 => "u8",
+            _ => "u64",
+        }
+    }
+
+    /// The load/store mode.
+    fn mode(&self) -> u8 {
+        self.opcode & 0xe0
+    }
+
+    /// The alu/jmp operation.
+    fn op(&self) -> u8 {
+        self.opcode & 0xf0
+    }
+
+    /// Whether an alu/jmp instruction uses a register as its source operand.
+    fn is_reg_src(&self) -> bool {
+        self.opcode & BPF_X != 0
+    }
+
+    /// Whether this is the first half of a 16-byte `ld_imm64` instruction.
+    pub fn is_wide(&self) -> bool {
+        self.opcode == (BPF_LD | BPF_IMM | 0x18)
+    }
+}
+
+/// Formats the memory operand of a load/store, e.g.: `*(u32 *)(r10 -4)`.
+fn format_mem(size: &str, reg: u8, offset: i16) -> String {
+    format!("*({} *)(r{} {:+})", size, reg, offset)
+}
+
+fn format_alu(insn: &Instruction) -> String {
+    let is64 = insn.class() == BPF_ALU64;
+    let r = if is64 { 'r' } else { 'w' };
+    let dst = format!("{}{}", r, insn.dst);
+    let src = if insn.is_reg_src() {
+        format!("{}{}", r, insn.src)
+    } else {
+        insn.imm.to_string()
+    };
+
+    match insn.op() {
+        BPF_NEG => format!("{} = -{}", dst, dst),
+        BPF_END => {
+            let kind = if is64 {
+                "bswap"
+            } else if insn.is_reg_src() {
+                "be"
+            } else {
+                "le"
+            };
+            format!("r{} = {}{} r{}", insn.dst, kind, insn.imm, insn.dst)
+        }
+        BPF_MOV if insn.offset != 0 => {
+            format!("{} = (s{}){}{}", dst, insn.offset, r, insn.src)
+        }
+        op => {
+            let signed = insn.offset == 1;
+            let op = match op {
+                0x00 => "+=",
+                0x10 => "-=",
+                0x20 => "*=",
+                0x30 if signed => "s/=",
+                0x30 => "/=",
+                0x40 => "|=",
+                0x50 => "&=",
+                0x60 => "<<=",
+                0x70 => ">>=",
+                0x90 if signed => "s%=",
+                0x90 => "%=",
+                0xa0 => "^=",
+                0xb0 => "=",
+                0xc0 => "s>>=",
+                _ => return format!("invalid alu op {:#04x}", insn.opcode),
+            };
+            format!("{} {} {}", dst, op, src)
+        }
+    }
+}
+
+fn format_jmp(insn: &Instruction) -> String {
+    let is32 = insn.class() == BPF_JMP32;
+
+    match insn.op() {
+        BPF_JA if is32 => format!("gotol pc{:+}", insn.imm),
+        BPF_JA => format!("goto pc{:+}", insn.offset),
+        BPF_EXIT => "exit".to_string(),
+        BPF_CALL => match insn.src {
+            BPF_PSEUDO_CALL => format!("call pc{:+}", insn.imm),
+            BPF_PSEUDO_KFUNC_CALL => "call kernel-function".to_string(),
+            _ => match helper_name(insn.imm as u32) {
+                Some(name) => format!("call bpf_{}#{}", name, insn.imm),
+                None => format!("call #{}", insn.imm),
+            },
+        },
+        op => {
+            let r = if is32 { 'w' } else { 'r' };
+            let op = match op {
+                0x10 => "==",
+                0x20 => ">",
+                0x30 => ">=",
+                0x40 => "&",
+                0x50 => "!=",
+                0x60 => "s>",
+                0x70 => "s>=",
+                0xa0 => "<",
+                0xb0 => "<=",
+                0xc0 => "s<",
+                0xd0 => "s<=",
+                _ => return format!("invalid jmp op {:#04x}", insn.opcode),
+            };
+            let src = if insn.is_reg_src() {
+                format!("{}{}", r, insn.src)
+            } else {
+                format!("{:#x}", insn.imm)
+            };
+            format!(
+                "if {}{} {} {} goto pc{:+}",
+                r, insn.dst, op, src, insn.offset
+            )
+        }
+    }
+}
+
+fn format_atomic(insn: &Instruction) -> String {
+    /* operands are printed as 64-bit registers whatever the size, as the kernel does */
+    let prefix = if insn.size() == "u64" {
+        "atomic64"
+    } else {
+        "atomic"
+    };
+    let mem = format!("({} *)(r{} {:+})", insn.size(), insn.dst, insn.offset);
+
+    match insn.imm {
+        BPF_XCHG => format!("r{} = {}_xchg({}, r{})", insn.src, prefix, mem, insn.src),
+        BPF_CMPXCHG => format!("r0 = {}_cmpxchg({}, r0, r{})", prefix, mem, insn.src),
+        imm => {
+            let (op, name) = match imm & !BPF_FETCH {
+                0x00 => ("+=", "add"),
+                0x40 => ("|=", "or"),
+                0x50 => ("&=", "and"),
+                0xa0 => ("^=", "xor"),
+                _ => return format!("invalid atomic op {:#x}", imm),
+            };
+
+            if imm & BPF_FETCH != 0 {
+                format!(
+                    "r{} = {}_fetch_{}({}, r{})",
+                    insn.src, prefix, name, mem, insn.src
+                )
+            } else {
+                format!("lock *{} {} r{}", mem, op, insn.src)
+            }
+        }
+    }
+}
+
+fn format_ld_imm64(insn: &Instruction, next: Option<&Instruction>) -> String {
+    let lo = insn.imm as u32 as u64;
+    let hi = next.map(|n| n.imm as u32 as u64).unwrap_or(0);
+    let value = match insn.src {
+        BPF_PSEUDO_MAP_FD | BPF_PSEUDO_MAP_IDX => format!("map[id:{}]", lo),
+        BPF_PSEUDO_MAP_VALUE | BPF_PSEUDO_MAP_IDX_VALUE => {
+            format!("map[id:{}][0]+{}", lo, hi)
+        }
+        BPF_PSEUDO_BTF_ID => format!("btf_id:{}", lo),
+        BPF_PSEUDO_FUNC => format!("subprog[{:+}]", insn.imm),
+        _ => format!("{:#x}", hi << 32 | lo),
+    };
+
+    format!("r{} = {}", insn.dst, value)
+}
+
+fn format_instruction(insns: &[Instruction], index: usize) -> String {
+    let insn = &insns[index];
+
+    match insn.class() {
+        BPF_ALU | BPF_ALU64 => format_alu(insn),
+        BPF_JMP | BPF_JMP32 => format_jmp(insn),
+        BPF_LDX => {
+            let size = if insn.mode() == BPF_MEMSX {
+                insn.size().replace('u', "s")
+            } else {
+                insn.size().to_string()
+            };
+            format!(
+                "r{} = {}",
+                insn.dst,
+                format_mem(&size, insn.src, insn.offset)
+            )
+        }
+        BPF_ST => format!(
+            "{} = {}",
+            format_mem(insn.size(), insn.dst, insn.offset),
+            insn.imm
+        ),
+        BPF_STX if insn.mode() == BPF_ATOMIC => format_atomic(insn),
+        BPF_STX => format!(
+            "{} = r{}",
+            format_mem(insn.size(), insn.dst, insn.offset),
+            insn.src
+        ),
+        BPF_LD if insn.is_wide() => format_ld_imm64(insn, insns.get(index + 1)),
+        BPF_LD if insn.mode() == BPF_ABS => {
+            format!("r0 = *({} *)skb[{}]", insn.size(), insn.imm)
+        }
+        BPF_LD if insn.mode() == BPF_IND => {
+            format!("r0 = *({} *)skb[r{} + {}]", insn.size(), insn.src, insn.imm)
+        }
+        _ => format!("invalid opcode {:#04x}", insn.opcode),
+    }
+}
+
+/// Disassembles raw eBPF instructions, e.g.: as returned by `Program::xlated_instructions()`,
+/// into one line of text per instruction. Each line is prefixed with the instruction index
+/// and opcode, like `bpftool prog dump xlated`. The second half of a 16-byte `ld_imm64`
+/// instruction doesn't get a line of its own.
+///
+/// # Arguments
+///
+/// * `instructions` - The raw instructions.
+pub fn disassemble(instructions: &[u64]) -> Vec<String> {
+    let insns: Vec<Instruction> = instructions.iter().map(|i| Instruction::from(*i)).collect();
+
+    let mut lines = vec![];
+    let mut index = 0;
+    while index < insns.len() {
+        lines.push(format!(
+            "{}: ({:02x}) {}",
+            index,
+            insns[index].opcode,
+            format_instruction(&insns, index)
+        ));
+        index += if insns[index].is_wide() { 2 } else { 1 };
+    }
+
+    lines
+}
@@ -1,12 +1,63 @@
 use thiserror::Error;
 
-use std::io::Error as IoError;
+use std::fmt::{Display, Formatter};
+use std::io::{Error as IoError, ErrorKind};
 use std::num::{ParseIntError, TryFromIntError};
 
+pub use crate::platform::Command;
+
+const EPERM: i32 = 1;
+const ENOENT: i32 = 2;
+const EACCES: i32 = 13;
+const ENOSPC: i32 = 28;
+
+/// The system call a `SystemError` came from, along with the object it acted on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Operation {
+    /// A `bpf()` command and the fd of the map or program it targeted, if any.
+    Bpf { cmd: Command, fd: Option<u32> },
+
+    /// Opening a perf event for the named probe on a CPU, or for a process.
+    PerfEventOpen {
+        name: String,
+        cpu: u32,
+        pid: Option<u32>,
+    },
+
+    /// An ioctl on a perf event fd, e.g.: `PERF_EVENT_IOC_SET_BPF`.
+    Ioctl { request: &'static str, fd: u32 },
+
+    /// Mapping the memory of a map.
+    Mmap { fd: u32 },
+
+    /// Getting or setting a resource limit, e.g.: `RLIMIT_MEMLOCK`.
+    Prlimit { resource: &'static str },
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bpf { cmd, fd: Some(fd) } => write!(f, "bpf({}) on fd {}", cmd, fd),
+            Self::Bpf { cmd, fd: None } => write!(f, "bpf({})", cmd),
+            Self::PerfEventOpen {
+                name,
+                pid: Some(pid),
+                ..
+            } => write!(f, "perf_event_open({}) for pid {}", name, pid),
+            Self::PerfEventOpen { name, cpu, .. } => {
+                write!(f, "perf_event_open({}) on cpu {}", name, cpu)
+            }
+            Self::Ioctl { request, fd } => write!(f, "ioctl({}) on fd {}", request, fd),
+            Self::Mmap { fd } => write!(f, "mmap on fd {}", fd),
+            Self::Prlimit { resource } => write!(f, "prlimit({})", resource),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("a system error occurred")]
-    SystemError(isize),
+    #[error("{op} failed: {}", strerror(*.errno))]
+    SystemError { op: Operation, errno: i32 },
 
     #[error("an IO error occurred")]
     IoError(#[from] IoError),
@@ -28,6 +79,87 @@ pub enum Error {
 
     #[error("value was out of range")]
     OutOfRange,
+
+    #[error("malformed object: {0}")]
+    MalformedObject(String),
+
+    #[error("malformed tracepoint format: {0}")]
+    MalformedFormat(String),
+
+    #[error("symbol not found: {0}")]
+    SymbolNotFound(String),
+
+    #[error("the program failed to load: {}", strerror(*.0))]
+    VerifierError(i32, String),
+}
+
+impl Error {
+    /// Builds a `SystemError` from the negative return value of a raw system call.
+    pub(crate) fn system(op: Operation, ret: isize) -> Self {
+        Self::SystemError {
+            op,
+            errno: -ret as i32,
+        }
+    }
+
+    /// The verifier log of a program that failed to load.
+    pub fn verifier_log(&self) -> Option<&str> {
+        match self {
+            Self::VerifierError(_, log) => Some(log),
+            _ => None,
+        }
+    }
+
+    /// The errno behind the error, if it came from the kernel.
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            Self::SystemError { errno, .. } | Self::VerifierError(errno, _) => Some(*errno),
+            Self::IoError(e) => e.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// The `std::io::ErrorKind` that best describes the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IoError(e) => e.kind(),
+            Self::SystemError { errno, .. } | Self::VerifierError(errno, _) => {
+                IoError::from_raw_os_error(*errno).kind()
+            }
+            Self::ParseIntError(_)
+            | Self::TryFromIntError(_)
+            | Self::InvalidArgument
+            | Self::OutOfRange => ErrorKind::InvalidInput,
+            Self::MalformedObject(_) | Self::MalformedFormat(_) => ErrorKind::InvalidData,
+            Self::SymbolNotFound(_) => ErrorKind::NotFound,
+            Self::NotImplemented => ErrorKind::Unsupported,
+            Self::MutexPoisoned => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the kernel reported `ENOENT`, e.g.: a missing map key or an empty queue.
+    pub fn is_not_found(&self) -> bool {
+        self.errno() == Some(ENOENT)
+    }
+
+    /// Whether the kernel reported `EPERM` or `EACCES`, e.g.: missing capabilities.
+    pub fn is_permission_denied(&self) -> bool {
+        matches!(self.errno(), Some(EPERM) | Some(EACCES))
+    }
+
+    /// Whether the kernel reported `ENOSPC`, e.g.: a full map or a short verifier log.
+    pub fn is_no_space(&self) -> bool {
+        self.errno() == Some(ENOSPC)
+    }
+}
+
+/// The description of an errno, e.g.: "No such file or directory".
+pub fn strerror(errno: i32) -> String {
+    let message = IoError::from_raw_os_error(errno).to_string();
+    match message.rfind(" (os error ") {
+        Some(i) => message[..i].to_string(),
+        None => message,
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,29 @@
+//! Probes for what the running kernel supports, for deciding what to load on kernels
+//! of different versions and configurations. Program types, map types, attach types
+//! and helpers are probed by loading minimal programs and creating minimal maps, as
+//! libbpf does. Results are cached, so each probe runs once per process.
+//!
+//! A probe returns `Ok(false)` when the kernel rejects the feature as unknown or
+//! invalid, and an error when it can't tell, e.g.: without the privileges to load
+//! programs.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::features::{helper_supported, map_type_supported, program_type_supported};
+//! use bpf_api::map::MapType;
+//! use bpf_api::prog::ProgramType;
+//!
+//! if map_type_supported(MapType::RingBuf).unwrap() {
+//!     // use a ring buffer rather than a perf event array
+//! }
+//!
+//! assert!(program_type_supported(ProgramType::SocketFilter).unwrap());
+//!
+//! /* bpf_map_lookup_elem */
+//! assert!(helper_supported(ProgramType::SocketFilter, 1).unwrap());
+//! ```
+
+pub use crate::platform::{
+    attach_type_supported, btf_supported, helper_supported, kprobe_multi_supported, link_supported,
+    map_type_supported, program_type_supported, ring_buffer_supported,
+};
@@ -30,16 +30,50 @@
 //!
 //! * [MIT license](http://opensource.org/licenses/MIT)
 
+pub mod btf;
 pub mod collections;
+pub mod disasm;
 pub mod error;
+pub mod features;
+pub mod map;
+pub mod object;
+pub mod preflight;
 pub mod probes;
 pub mod prog;
+pub mod testing;
+pub mod tracepoint;
+pub mod verifier;
 
 mod platform;
 
 #[cfg(test)]
 mod tests {
+    use crate::btf::{Btf, BtfTypes, FuncInfo, BTF_KIND_STRUCT};
     use crate::collections::{Array, HashMap, Queue};
+    use crate::disasm::disassemble;
+    use crate::error::{Command, Error, Operation};
+    use crate::features::{
+        attach_type_supported, btf_supported, helper_supported, kprobe_multi_supported,
+        link_supported, map_type_supported, program_type_supported, ring_buffer_supported,
+    };
+    use crate::map::{map_ids, Map, MapType};
+    use crate::object::{CoreRelo, CoreRelocator, Object};
+    use crate::preflight::{raise_memlock_rlimit, Preflight};
+    use crate::probes::{resolve_library, AttachInfo, AttachType, Probe, UProbe};
+    use crate::prog::{
+        prog_flags, program_ids, LogLevel, Program, ProgramAttr, ProgramBtf, ProgramStats,
+        ProgramType, StatsGuard, StatsSampler,
+    };
+    use crate::testing::{
+        internet_checksum, tcp_flags, Context, NetworkHeader, Packet, PacketBuilder, SkBuff,
+        TransportHeader, XdpAction, XdpMd,
+    };
+    use crate::tracepoint::{FieldLocation, FieldValue, TracepointFormat};
+    use crate::verifier::VerifierReport;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    /* r0 = 0; exit */
+    const RETURN_ZERO: [u64; 2] = [0x00000000000000b7, 0x0000000000000095];
 
     #[test]
     fn hashmap_insert_get() {
@@ -79,6 +113,31 @@ mod tests {
         assert!(queue.pop().is_err());
     }
 
+    #[test]
+    fn system_error_context() {
+        let queue = Queue::<u32>::with_capacity(1).unwrap();
+        let err = queue.pop().err().unwrap();
+        assert!(err.is_not_found());
+        assert!(!err.is_permission_denied());
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(matches!(
+            err,
+            Error::SystemError {
+                op: Operation::Bpf {
+                    cmd: Command::MapLookupAndDeleteElem,
+                    fd: Some(_),
+                },
+                errno: 2,
+            }
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("bpf(BPF_MAP_LOOKUP_AND_DELETE_ELEM) on fd "));
+        assert!(err
+            .to_string()
+            .ends_with("failed: No such file or directory"));
+    }
+
     #[test]
     fn array_set_get() {
         const ARRAY_SIZE: u32 = 10;
@@ -94,4 +153,1006 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn map_from_id() {
+        let _array = Array::<[u8; 24]>::with_capacity(1).unwrap();
+
+        /* only maps with a matching key/value size can be opened as this type */
+        let opened = map_ids()
+            .map(|id| id.unwrap())
+            .filter_map(|id| Map::<u32, [u8; 24]>::from_id(id).ok())
+            .count();
+        assert!(opened > 0);
+    }
+
+    #[test]
+    fn program_from_id() {
+        let attr = ProgramAttr {
+            prog_name: Some("from_id_test".into()),
+            prog_type: ProgramType::SocketFilter,
+            expected_attach_type: None,
+            attach_btf_id: None,
+            ..Default::default()
+        };
+        let _program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let found = program_ids()
+            .map(|id| id.unwrap())
+            .filter_map(|id| Program::from_id(id).ok())
+            .any(|p| {
+                p.get_attr().prog_name.as_deref() == Some("from_id_test")
+                    && p.get_attr().prog_type == ProgramType::SocketFilter
+            });
+        assert!(found);
+    }
+
+    #[test]
+    fn map_info() {
+        let map = Map::<u32, u64>::with_capacity(MapType::Array, 7).unwrap();
+        let info = map.info().unwrap();
+        assert_eq!(info.map_type, MapType::Array);
+        assert_eq!(info.key_size, 4);
+        assert_eq!(info.value_size, 8);
+        assert_eq!(info.max_entries, 7);
+        assert!(info.memlock > 0);
+
+        let opened = Map::<u32, u64>::from_id(info.id).unwrap();
+        assert_eq!(opened.info().unwrap().id, info.id);
+        assert!(Map::<u32, u32>::from_id(info.id).is_err());
+    }
+
+    #[test]
+    fn program_info() {
+        let attr = ProgramAttr {
+            prog_name: Some("info_test".into()),
+            prog_type: ProgramType::SocketFilter,
+            expected_attach_type: None,
+            attach_btf_id: None,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let info = program.info().unwrap();
+        assert_eq!(info.prog_type, ProgramType::SocketFilter);
+        assert_eq!(info.name, "info_test");
+        assert!(info.map_ids.is_empty());
+        assert_eq!(info.xlated_prog_len as usize, RETURN_ZERO.len() * 8);
+    }
+
+    #[test]
+    fn program_bind_map() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let map = Map::<u32, u64>::with_capacity(MapType::Array, 1).unwrap();
+        let map_id = map.info().unwrap().id;
+        program.bind_map(&map).unwrap();
+        assert_eq!(program.info().unwrap().map_ids, vec![map_id]);
+
+        /* the program keeps the map alive */
+        drop(map);
+        assert!(Map::<u32, u64>::from_id(map_id).is_ok());
+    }
+
+    #[test]
+    fn feature_probes() {
+        assert!(program_type_supported(ProgramType::Xdp).unwrap());
+        assert!(!program_type_supported(ProgramType::Unspec).unwrap());
+        assert!(map_type_supported(MapType::Hash).unwrap());
+        assert!(map_type_supported(MapType::ArrayOfMaps).unwrap());
+        assert!(!map_type_supported(MapType::Unspec).unwrap());
+        assert!(attach_type_supported(AttachType::CgroupInetIngress).unwrap());
+        assert!(attach_type_supported(AttachType::XdpDevmap).unwrap());
+
+        assert!(helper_supported(ProgramType::SocketFilter, 1).unwrap());
+        assert!(!helper_supported(ProgramType::SocketFilter, 100_000).unwrap());
+        assert!(matches!(
+            helper_supported(ProgramType::Tracing, 1),
+            Err(Error::InvalidArgument)
+        ));
+
+        assert!(btf_supported().unwrap());
+        assert!(ring_buffer_supported().unwrap());
+        assert!(link_supported().unwrap());
+        assert!(kprobe_multi_supported().is_ok());
+
+        /* cached results come back the same */
+        assert!(program_type_supported(ProgramType::Xdp).unwrap());
+        assert!(!helper_supported(ProgramType::SocketFilter, 100_000).unwrap());
+    }
+
+    #[test]
+    fn preflight_report() {
+        /* the tests run as root */
+        let preflight = Preflight::check().unwrap();
+        assert!(preflight.capabilities.bpf || preflight.capabilities.sys_admin);
+        assert!(preflight.memlock.cur > 0);
+        assert!(preflight.memlock.cur <= preflight.memlock.max);
+        assert!(!preflight
+            .problems()
+            .iter()
+            .any(|p| p.starts_with("missing CAP_BPF")));
+
+        assert!(raise_memlock_rlimit().is_ok());
+        let preflight = Preflight::check().unwrap();
+        assert!(preflight.memlock_charged.is_some());
+        assert!(preflight.memlock_charged == Some(false) || preflight.memlock.cur == u64::MAX);
+    }
+
+    #[test]
+    fn program_verifier_log() {
+        /* exit without setting r0 */
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let err = Program::create(&attr, &RETURN_ZERO[1..], None)
+            .err()
+            .unwrap();
+        assert!(err.verifier_log().unwrap().contains("R0 !read_ok"));
+
+        /* a log that doesn't fit in the initial buffer is retried with a larger one */
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            log_level: LogLevel::Verbose,
+            log_size: Some(128),
+            ..Default::default()
+        };
+        let mut log = vec![];
+        Program::create(&attr, &RETURN_ZERO, Some(&mut log)).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.len() > 128);
+        assert!(log.contains("processed 2 insns"));
+
+        /* failures before the verifier runs keep their context */
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let too_long = vec![RETURN_ZERO[0]; 1_000_001];
+        let err = Program::create(&attr, &too_long, None).err().unwrap();
+        assert!(matches!(
+            err,
+            Error::SystemError {
+                op: Operation::Bpf {
+                    cmd: Command::ProgLoad,
+                    ..
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn program_attr_validation() {
+        let invalid = [
+            (ProgramType::SocketFilter, prog_flags::SLEEPABLE, None),
+            (ProgramType::SocketFilter, prog_flags::XDP_HAS_FRAGS, None),
+            (ProgramType::SocketFilter, 0, Some(1)),
+            (ProgramType::Xdp, prog_flags::XDP_DEV_BOUND_ONLY, None),
+        ];
+        for (prog_type, prog_flags, prog_ifindex) in invalid {
+            let attr = ProgramAttr {
+                prog_type,
+                prog_flags,
+                prog_ifindex,
+                ..Default::default()
+            };
+            assert!(matches!(
+                Program::create(&attr, &RETURN_ZERO, None),
+                Err(Error::InvalidArgument)
+            ));
+        }
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::Xdp,
+            prog_flags: prog_flags::XDP_HAS_FRAGS | prog_flags::TEST_RND_HI32,
+            license: Some("Dual MIT/GPL".into()),
+            kern_version: Some(0x060000),
+            ..Default::default()
+        };
+        assert!(Program::create(&attr, &RETURN_ZERO, None).is_ok());
+
+        /* unknown flags are left to the kernel to reject */
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            prog_flags: prog_flags::TEST_REG_INVARIANTS,
+            ..Default::default()
+        };
+        assert!(Program::create(&attr, &RETURN_ZERO, None).is_ok());
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            prog_flags: 1 << 31,
+            ..Default::default()
+        };
+        assert_eq!(
+            Program::create(&attr, &RETURN_ZERO, None)
+                .err()
+                .unwrap()
+                .errno(),
+            Some(22)
+        );
+    }
+
+    /// Builds a BTF blob from raw type records and a string section.
+    fn raw_btf(types: &[u32], strings: &[u8]) -> Vec<u8> {
+        let type_len = (types.len() * 4) as u32;
+        let mut data = vec![0x9f, 0xeb, 1, 0];
+        for field in [24, 0, type_len, type_len, strings.len() as u32] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        for word in types {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data.extend_from_slice(strings);
+        data
+    }
+
+    #[test]
+    fn btf_malformed() {
+        /* a header length too short to hold the header */
+        let mut data = raw_btf(&[], b"\0");
+        data[4] = 8;
+        assert!(matches!(
+            BtfTypes::parse(&data),
+            Err(Error::MalformedObject(_))
+        ));
+
+        /* typedef a b; typedef b a; */
+        let typedefs = BtfTypes::parse(&raw_btf(&[0, 8 << 24, 2, 0, 8 << 24, 1], b"\0")).unwrap();
+        assert!(typedefs.skip_mods_and_typedefs(1).is_err());
+        assert!(typedefs.resolve_size(1).is_err());
+
+        /* int x[0x80000000]; */
+        let types = [0, 1 << 24, 4, 32, 0, 3 << 24, 0, 1, 1, 0x8000_0000];
+        let array = BtfTypes::parse(&raw_btf(&types, b"\0")).unwrap();
+        assert!(array.resolve_size(2).is_err());
+    }
+
+    #[test]
+    fn program_extension() {
+        /* int policy(int x) { return x; } int xdp_main() { return policy(XDP_DROP) & 3; } */
+        const TARGET: [u64; 6] = [
+            0x0000_0001_0000_01b7,
+            0x0000_0002_0000_1085,
+            0x0000_0003_0000_0057,
+            0x0000_0000_0000_0095,
+            0x0000_0000_0000_10bf,
+            0x0000_0000_0000_0095,
+        ];
+        /* int new_policy(int x) { return XDP_PASS; } */
+        const EXT: [u64; 2] = [0x0000_0002_0000_00b7, 0x0000_0000_0000_0095];
+
+        #[rustfmt::skip]
+        let btf = raw_btf(
+            &[
+                1, 0x0100_0000, 4, 0x0100_0020, /* [1] int */
+                0, 0x0d00_0001, 1, 5, 1,        /* [2] int (int x) */
+                7, 0x0c00_0001, 2,              /* [3] global policy */
+                0, 0x0d00_0000, 1,              /* [4] int (void) */
+                14, 0x0c00_0001, 4,             /* [5] global xdp_main */
+            ],
+            b"\0int\0x\0policy\0xdp_main\0",
+        );
+        let btf = Btf::load(&btf, None).unwrap();
+        let program_btf = |func_info: Vec<FuncInfo>| ProgramBtf {
+            btf_fd: btf.get_identifier(),
+            func_info,
+            ..Default::default()
+        };
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::Xdp,
+            btf: Some(program_btf(vec![
+                FuncInfo {
+                    insn_off: 0,
+                    type_id: 5,
+                },
+                FuncInfo {
+                    insn_off: 4,
+                    type_id: 3,
+                },
+            ])),
+            ..Default::default()
+        };
+        let target = Program::create(&attr, &TARGET, None).unwrap();
+
+        let packet = [0u8; 64];
+        let run = || target.test_run().data_in(&packet).run().unwrap().retval;
+        assert_eq!(run(), 1);
+
+        /* an extension needs its target */
+        let mut attr = ProgramAttr {
+            prog_type: ProgramType::Ext,
+            btf: Some(program_btf(vec![FuncInfo {
+                insn_off: 0,
+                type_id: 3,
+            }])),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Program::create(&attr, &EXT, None),
+            Err(Error::InvalidArgument)
+        ));
+
+        attr.attach_prog_fd = Some(target.get_fd());
+        attr.attach_btf_id = Some(target.func_btf_id("policy").unwrap());
+        assert_eq!(target.func_btf_id("xdp_main").unwrap(), 5);
+
+        /* some kernels refuse tracing and extension programs altogether */
+        let ext = match Program::create(&attr, &EXT, None) {
+            Err(e) if e.is_permission_denied() => return,
+            r => r.unwrap(),
+        };
+
+        let mut probe = Probe::create(AttachInfo::Extension);
+        probe.attach(&ext).unwrap();
+        assert_eq!(run(), 2);
+
+        probe.detach(&ext).unwrap();
+        assert_eq!(run(), 1);
+    }
+
+    #[test]
+    fn verifier_report() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let err = Program::create(&attr, &RETURN_ZERO[1..], None)
+            .err()
+            .unwrap();
+        let report = VerifierReport::parse(err.verifier_log().unwrap());
+        assert_eq!(report.error.as_deref(), Some("R0 !read_ok"));
+        assert_eq!(report.error_insn, Some(0));
+        assert_eq!(report.instructions[0].text, "(95) exit");
+        assert_eq!(report.stats.unwrap().processed_insns, 1);
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            log_level: LogLevel::Verbose,
+            ..Default::default()
+        };
+        let mut log = vec![];
+        Program::create(&attr, &RETURN_ZERO, Some(&mut log)).unwrap();
+        let report = VerifierReport::parse(std::str::from_utf8(&log).unwrap());
+        assert_eq!(report.error, None);
+        assert_eq!(report.instructions.len(), 2);
+        assert_eq!(report.instructions[0].register_before("R1"), Some("ctx()"));
+        assert_eq!(report.instructions[0].register_after("R0"), Some("0"));
+        assert_eq!(report.stats.unwrap().processed_insns, 2);
+    }
+
+    #[test]
+    fn program_xlated_and_jited() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        assert_eq!(program.xlated_instructions().unwrap(), RETURN_ZERO);
+
+        let info = program.info().unwrap();
+        let jited = program.jited_image().unwrap();
+        assert_eq!(jited.image.len(), info.jited_prog_len as usize);
+        assert_eq!(jited.func_lens.iter().sum::<u32>(), info.jited_prog_len);
+    }
+
+    #[test]
+    fn disassemble_instructions() {
+        let instructions = [
+            0x00000000000016bf,
+            0xfffffff800000a07,
+            0x00000000fffc1a63,
+            0x0000000000030115,
+            0x0000000100000085,
+            0x0000000500001118,
+            0x0000000000000000,
+            0x0000000200000218,
+            0x0000000100000000,
+            0x0000000000081061,
+            0x00000000fff81adb,
+            0x00000001fffc1ac3,
+            0x00000010000000dc,
+            0x0000000000000095,
+        ];
+
+        assert_eq!(
+            disassemble(&instructions),
+            vec![
+                "0: (bf) r6 = r1",
+                "1: (07) r10 += -8",
+                "2: (63) *(u32 *)(r10 -4) = r1",
+                "3: (15) if r1 == 0x0 goto pc+3",
+                "4: (85) call bpf_map_lookup_elem#1",
+                "5: (18) r1 = map[id:5]",
+                "7: (18) r2 = 0x100000002",
+                "9: (61) r0 = *(u32 *)(r1 +8)",
+                "10: (db) lock *(u64 *)(r10 -8) += r1",
+                "11: (c3) r1 = atomic_fetch_add((u32 *)(r10 -4), r1)",
+                "12: (dc) r0 = be16 r0",
+                "13: (95) exit",
+            ]
+        );
+    }
+
+    #[test]
+    fn program_stats() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let _guard = StatsGuard::enable().unwrap();
+        assert_eq!(program.stats().unwrap(), ProgramStats::default());
+
+        let mut sampler = StatsSampler::new(&[&program]).unwrap();
+        let rates = sampler.sample().unwrap();
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].id, program.info().unwrap().id);
+        assert_eq!(rates[0].runs_per_sec, 0.0);
+        assert_eq!(rates[0].avg_run_time_ns, 0.0);
+    }
+
+    #[test]
+    fn program_test_run() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::SocketFilter,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let _guard = StatsGuard::enable().unwrap();
+        let packet = [0xffu8; 64];
+        let result = program.test_run().data_in(&packet).repeat(5).run().unwrap();
+        assert_eq!(result.retval, 0);
+        assert!(result.data_out.is_empty());
+        assert_eq!(program.stats().unwrap().run_cnt, 5);
+    }
+
+    #[test]
+    fn tracepoint_attach() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::Tracepoint,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        let mut probe = Probe::create(AttachInfo::Tracepoint {
+            category: "syscalls/..".into(),
+            name: "sys_enter_getpid".into(),
+        });
+        assert!(matches!(
+            probe.attach(&program),
+            Err(Error::InvalidArgument)
+        ));
+
+        let _guard = StatsGuard::enable().unwrap();
+        let mut probe = Probe::create(AttachInfo::Tracepoint {
+            category: "syscalls".into(),
+            name: "sys_enter_getpid".into(),
+        });
+        match probe.attach(&program) {
+            Ok(()) => {}
+            /* neither tracefs nor debugfs is mounted */
+            Err(e) if e.is_not_found() => return,
+            Err(e) => panic!("attaching to the tracepoint failed: {}", e),
+        }
+
+        /* getpid() */
+        let _ = std::process::id();
+        assert!(program.stats().unwrap().run_cnt > 0);
+
+        probe.detach(&program).unwrap();
+    }
+
+    #[test]
+    fn return_probes() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::KProbe,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+
+        /* a file nothing maps, so no breakpoint actually gets installed */
+        let path = std::env::temp_dir().join(format!("bpf-api-uretprobe-{}", std::process::id()));
+        std::fs::write(&path, [0u8; 4096]).unwrap();
+        let mut probe = Probe::create(AttachInfo::URetProbe((
+            path.to_str().unwrap().to_string(),
+            0,
+        )));
+        probe.attach(&program).unwrap();
+        probe.attach(&program).unwrap();
+        probe.detach(&program).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        /* kernels without kprobes have neither the PMU nor kprobe_events */
+        for maxactive in [None, Some(16)] {
+            let mut probe = Probe::create(AttachInfo::KRetProbe {
+                name: "__x64_sys_getpid".into(),
+                maxactive,
+            });
+            match probe.attach(&program) {
+                Ok(()) => probe.detach(&program).unwrap(),
+                Err(e) => assert!(e.is_not_found(), "{}", e),
+            }
+        }
+    }
+
+    #[no_mangle]
+    #[inline(never)]
+    extern "C" fn bpf_api_uprobe_target(x: u64) -> u64 {
+        std::hint::black_box(x + 1)
+    }
+
+    #[test]
+    fn uprobe_by_symbol() {
+        let libc = "/lib/x86_64-linux-gnu/libc.so.6";
+        if std::path::Path::new(libc).exists() {
+            let malloc = UProbe::by_symbol(libc, "malloc", 0).unwrap();
+            let default = UProbe::by_symbol(libc, "malloc@@GLIBC_2.2.5", 4).unwrap();
+            assert_eq!(malloc.offset + 4, default.offset);
+            assert_ne!(
+                UProbe::by_symbol(libc, "memcpy", 0).unwrap(),
+                UProbe::by_symbol(libc, "memcpy@GLIBC_2.2.5", 0).unwrap()
+            );
+            assert!(matches!(
+                UProbe::by_symbol(libc, "malloc@GLIBC_0.0", 0),
+                Err(Error::SymbolNotFound(_))
+            ));
+        }
+
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        assert!(matches!(
+            UProbe::by_symbol(exe, "bpf_api_no_such_function", 0),
+            Err(Error::SymbolNotFound(_))
+        ));
+        assert_eq!(
+            UProbe::by_entry(exe).unwrap(),
+            UProbe::by_symbol(exe, "_start", 0).unwrap()
+        );
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::KProbe,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+        let _guard = StatsGuard::enable().unwrap();
+
+        let uprobe = UProbe::by_symbol(exe, "bpf_api_uprobe_target", 0).unwrap();
+        let mut probe = Probe::create(uprobe.clone().into());
+        probe.attach(&program).unwrap();
+        let mut retprobe = Probe::create(uprobe.retprobe());
+        retprobe.attach(&program).unwrap();
+
+        assert_eq!(bpf_api_uprobe_target(1), 2);
+        assert_eq!(program.stats().unwrap().run_cnt, 2);
+    }
+
+    #[no_mangle]
+    #[inline(never)]
+    extern "C" fn bpf_api_uprobe_pid_target(x: u64) -> u64 {
+        std::hint::black_box(x + 1)
+    }
+
+    #[test]
+    fn uprobe_per_process() {
+        let pid = std::process::id();
+        let libc = resolve_library("libc.so.6", Some(pid)).unwrap();
+        assert!(libc.starts_with(format!("/proc/{}/root/", pid)));
+        assert_eq!(
+            libc.canonicalize().unwrap(),
+            resolve_library("libc", None)
+                .unwrap()
+                .canonicalize()
+                .unwrap()
+        );
+        assert!(resolve_library("libbpf-api-no-such-library.so", None).is_err());
+        assert!(matches!(
+            resolve_library("../libc.so.6", None),
+            Err(Error::InvalidArgument)
+        ));
+
+        let malloc = UProbe::by_symbol_in_process(pid, "libc.so.6", "malloc", 0).unwrap();
+        assert_eq!(malloc.path, libc.to_str().unwrap());
+        assert_eq!(malloc.pid, Some(pid));
+
+        let attr = ProgramAttr {
+            prog_type: ProgramType::KProbe,
+            ..Default::default()
+        };
+        let program = Program::create(&attr, &RETURN_ZERO, None).unwrap();
+        let _guard = StatsGuard::enable().unwrap();
+
+        let exe = std::env::current_exe().unwrap();
+        let exe = exe.to_str().unwrap();
+        let uprobe = UProbe::by_symbol(exe, "bpf_api_uprobe_pid_target", 0).unwrap();
+
+        /* a probe for another process doesn't fire here */
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let mut probe = Probe::create(
+            UProbe {
+                pid: Some(child.id()),
+                ..uprobe.clone()
+            }
+            .inherit(true)
+            .into(),
+        );
+        probe.attach(&program).unwrap();
+        assert_eq!(bpf_api_uprobe_pid_target(1), 2);
+        assert_eq!(program.stats().unwrap().run_cnt, 0);
+        probe.detach(&program).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let mut probe = Probe::create(
+            UProbe {
+                pid: Some(pid),
+                ..uprobe
+            }
+            .into(),
+        );
+        probe.attach(&program).unwrap();
+        assert_eq!(bpf_api_uprobe_pid_target(1), 2);
+        assert_eq!(program.stats().unwrap().run_cnt, 1);
+    }
+
+    #[test]
+    fn tracepoint_format() {
+        let format = "\
+name: example
+ID: 7
+format:
+	field:unsigned short common_type;	offset:0;	size:2;	signed:0;
+	field:int common_pid;	offset:4;	size:4;	signed:1;
+
+	field:char comm[8];	offset:8;	size:8;	signed:1;
+	field:const char * ptr;	offset:16;	size:8;	signed:0;
+	field:s16 deltas[2];	offset:24;	size:4;	signed:1;
+	field:__rel_loc u8[] blob;	offset:28;	size:4;	signed:0;
+
+print fmt: \"comm=%s\", REC->comm
+";
+        let format = TracepointFormat::parse(format).unwrap();
+        assert_eq!(format.name, "example");
+        assert_eq!(format.record_size(), 32);
+        assert!(format.fields[1].is_common());
+        assert!(!format.field("comm").unwrap().is_common());
+        assert_eq!(format.field("ptr").unwrap().type_name, "const char *");
+        assert_eq!(format.field("comm").unwrap().array_len, Some(8));
+        assert_eq!(
+            format.field("blob").unwrap().location,
+            FieldLocation::RelLoc
+        );
+
+        let mut record = vec![0u8; 32];
+        record[0..2].copy_from_slice(&7u16.to_ne_bytes());
+        record[4..8].copy_from_slice(&(-1i32).to_ne_bytes());
+        record[8..12].copy_from_slice(b"sh\0x");
+        record[16..24].copy_from_slice(&0xffff8000u64.to_ne_bytes());
+        record[24..26].copy_from_slice(&(-2i16).to_ne_bytes());
+        record[26..28].copy_from_slice(&3i16.to_ne_bytes());
+        /* 2 bytes right after the field */
+        record[28..32].copy_from_slice(&(2u32 << 16).to_ne_bytes());
+        record.extend_from_slice(&[0xab, 0xcd]);
+
+        let values = format.decode(&record).unwrap();
+        let values: Vec<&FieldValue> = values.iter().map(|(_, v)| v).collect();
+        assert_eq!(
+            values,
+            [
+                &FieldValue::Unsigned(7),
+                &FieldValue::Signed(-1),
+                &FieldValue::String("sh".into()),
+                &FieldValue::Unsigned(0xffff8000),
+                &FieldValue::Array(vec![FieldValue::Signed(-2), FieldValue::Signed(3)]),
+                &FieldValue::Bytes(vec![0xab, 0xcd]),
+            ]
+        );
+        assert_eq!(values[4].to_string(), "[-2, 3]");
+        assert!(matches!(
+            format.decode(&record[..20]),
+            Err(Error::OutOfRange)
+        ));
+        assert!(matches!(
+            TracepointFormat::parse("name: x\nformat:\n\tfield:int;\toffset:0;\n"),
+            Err(Error::MalformedFormat(_))
+        ));
+
+        match TracepointFormat::from_tracefs("sched", "sched_process_exec") {
+            Ok(format) => {
+                let field = format.field("filename").unwrap();
+                assert_eq!(field.location, FieldLocation::DataLoc);
+                assert_eq!(field.type_name, "char");
+            }
+            Err(e) => assert!(e.is_not_found()),
+        }
+    }
+
+    #[test]
+    fn packet_build_parse() {
+        let frame = PacketBuilder::new()
+            .vlan(100)
+            .ipv4(Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2))
+            .tcp(4000, 80)
+            .tcp_flags(tcp_flags::SYN)
+            .payload(b"abc")
+            .build()
+            .unwrap();
+
+        /* ethernet + vlan, ipv4, tcp, payload */
+        assert_eq!(frame.len(), 18 + 20 + 20 + 3);
+        assert_eq!(internet_checksum(&frame[18..38]), 0);
+
+        let packet = Packet::parse(&frame).unwrap();
+        assert_eq!(packet.vlans, vec![100]);
+        assert_eq!(packet.ether_type, 0x0800);
+        assert!(matches!(
+            packet.network,
+            Some(NetworkHeader::Ipv4 { protocol: 6, .. })
+        ));
+        assert!(matches!(
+            packet.transport,
+            Some(TransportHeader::Tcp {
+                src_port: 4000,
+                dst_port: 80,
+                flags: tcp_flags::SYN,
+                ..
+            })
+        ));
+        assert_eq!(packet.payload, b"abc");
+
+        let frame = PacketBuilder::new()
+            .ipv6(Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST)
+            .icmp(128, 0, 1)
+            .build()
+            .unwrap();
+        let packet = Packet::parse(&frame).unwrap();
+        assert!(matches!(
+            packet.transport,
+            Some(TransportHeader::Icmp { icmp_type: 128, .. })
+        ));
+
+        let frame = PacketBuilder::new()
+            .ipv4(Ipv4Addr::LOCALHOST, Ipv4Addr::LOCALHOST)
+            .icmp(8, 0, 1)
+            .build()
+            .unwrap();
+        assert_eq!(internet_checksum(&frame[34..]), 0);
+
+        /* padding past the IP length isn't payload */
+        let mut frame = PacketBuilder::new()
+            .ipv4(Ipv4Addr::LOCALHOST, Ipv4Addr::LOCALHOST)
+            .udp(1, 2)
+            .payload(b"abc")
+            .build()
+            .unwrap();
+        frame.resize(60, 0);
+        assert_eq!(Packet::parse(&frame).unwrap().payload, b"abc");
+        frame.truncate(44);
+        assert!(Packet::parse(&frame).is_err());
+
+        let payload = vec![0; 0x10000];
+        assert!(matches!(
+            PacketBuilder::new()
+                .ipv6(Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST)
+                .udp(1, 2)
+                .payload(&payload)
+                .build(),
+            Err(Error::OutOfRange)
+        ));
+        assert_eq!(internet_checksum(&vec![0xff; 0x40000]), 0);
+    }
+
+    #[test]
+    fn xdp_test_run_with_context() {
+        let attr = ProgramAttr {
+            prog_type: ProgramType::Xdp,
+            ..Default::default()
+        };
+
+        /* r0 = XDP_DROP; exit */
+        let program = Program::create(&attr, &[0x1000000b7, 0x95], None).unwrap();
+        let frame = PacketBuilder::new()
+            .ipv4(Ipv4Addr::LOCALHOST, Ipv4Addr::LOCALHOST)
+            .udp(1, 2)
+            .build()
+            .unwrap();
+        let ctx = XdpMd {
+            data_end: frame.len() as u32,
+            ..Default::default()
+        };
+
+        let result = program
+            .test_run()
+            .data_in(&frame)
+            .ctx_in(ctx.as_bytes())
+            .ctx_out(std::mem::size_of::<XdpMd>())
+            .run()
+            .unwrap();
+        assert_eq!(XdpAction::try_from(result.retval).unwrap(), XdpAction::Drop);
+        assert!(XdpMd::from_bytes(&result.ctx_out).is_ok());
+        assert_eq!(std::mem::size_of::<SkBuff>(), 192);
+    }
+
+    #[test]
+    fn object_load() {
+        let object = Object::from_bytes(include_bytes!("../testdata/object.o")).unwrap();
+        assert_eq!(object.maps().len(), 5);
+        assert_eq!(object.programs().len(), 1);
+        assert_eq!(object.programs()[0].prog_type, ProgramType::Xdp);
+
+        let loaded = object.load().unwrap();
+        let program = loaded.program("xdp_count").unwrap();
+        let packet = PacketBuilder::new().build().unwrap();
+        let result = program.test_run().data_in(&packet).repeat(3).run().unwrap();
+        assert_eq!(result.retval, XdpAction::Pass as u32);
+
+        let btf_id = program.info().unwrap().btf_id;
+        assert_ne!(btf_id, 0);
+        assert_eq!(Btf::from_id(btf_id).unwrap().id().unwrap(), btf_id);
+
+        let counts = loaded.map::<u32, u64>("counts").unwrap();
+        let hits = loaded.map::<i32, u64>("hits").unwrap();
+        assert_eq!(counts.get(&0).unwrap(), 3);
+        assert_eq!(hits.get(&0).unwrap(), 3);
+        assert!(loaded.map::<u32, u32>("counts").is_err());
+    }
+
+    #[test]
+    fn object_globals() {
+        let mut object = Object::from_bytes(include_bytes!("../testdata/object.o")).unwrap();
+        assert_eq!(object.global::<u64>("increment").unwrap(), 1);
+        assert_eq!(object.global::<u64>("packets").unwrap(), 0);
+        assert_eq!(object.global::<[u64; 2]>(".data").unwrap(), [7, 100]);
+        assert!(object.global::<u32>("version").is_err());
+        assert!(matches!(
+            object.global::<[u64; 3]>(".data"),
+            Err(Error::OutOfRange)
+        ));
+        object.set_global("increment", &2u64).unwrap();
+
+        let mut loaded = object.load().unwrap();
+        let program = loaded.program("xdp_count").unwrap();
+        let packet = PacketBuilder::new().build().unwrap();
+        program.test_run().data_in(&packet).repeat(3).run().unwrap();
+
+        assert_eq!(loaded.global::<u64>("increment").unwrap(), 2);
+        assert_eq!(loaded.global::<u64>("packets").unwrap(), 6);
+        assert_eq!(loaded.global::<[u64; 2]>(".data").unwrap(), [7, 106]);
+        assert!(loaded.set_global("increment", &3u64).is_err());
+        assert!(matches!(
+            loaded.global::<[u64; 3]>(".data"),
+            Err(Error::OutOfRange)
+        ));
+
+        loaded.set_global("packets", &0u64).unwrap();
+        let program = loaded.program("xdp_count").unwrap();
+        program.test_run().data_in(&packet).run().unwrap();
+        assert_eq!(loaded.global::<u64>("packets").unwrap(), 2);
+    }
+
+    #[test]
+    fn core_relocation_negative_enumerator() {
+        /* enum e { A = -1 } locally, enum e { A = -5 } in the target */
+        let local =
+            BtfTypes::parse(&raw_btf(&[1, 0x8600_0001, 4, 3, -1i32 as u32], b"\0e\0A\0")).unwrap();
+        let target =
+            BtfTypes::parse(&raw_btf(&[1, 0x8600_0001, 4, 3, -5i32 as u32], b"\0e\0A\0")).unwrap();
+
+        /* r0 = A */
+        let mut instructions = [0xffff_ffff_0000_00b7];
+        let relo = CoreRelo {
+            insn_off: 0,
+            type_id: 1,
+            access: "0".into(),
+            kind: 11,
+        };
+        CoreRelocator::new(&local, &target)
+            .apply(&mut instructions, 0, &relo)
+            .unwrap();
+        assert_eq!(instructions[0], 0xffff_fffb_0000_00b7);
+    }
+
+    #[test]
+    fn object_core_relocations() {
+        let vmlinux = std::fs::read("/sys/kernel/btf/vmlinux").unwrap();
+        let vmlinux = BtfTypes::parse(&vmlinux).unwrap();
+        let task_struct = vmlinux
+            .find_by_name_kind("task_struct", BTF_KIND_STRUCT)
+            .unwrap();
+        let pid = vmlinux
+            .get(task_struct)
+            .unwrap()
+            .members
+            .iter()
+            .find(|m| vmlinux.string(m.name_off) == "pid")
+            .unwrap()
+            .bit_offset;
+
+        let object = Object::from_bytes(include_bytes!("../testdata/core.o")).unwrap();
+        let loaded = object.load().unwrap();
+        let program = loaded.program("xdp_core").unwrap();
+        program.test_run().data_in(&[0u8; 64]).run().unwrap();
+
+        let results = loaded.global::<[u64; 4]>("results").unwrap();
+        assert_eq!(results[0], pid as u64 / 8);
+        assert_eq!(results[1], 0);
+        assert_eq!(
+            results[2],
+            vmlinux.resolve_size(task_struct).unwrap() as u64
+        );
+        assert_eq!(results[3], MapType::RingBuf as u64);
+    }
+
+    #[test]
+    fn object_subprograms() {
+        let object = Object::from_bytes(include_bytes!("../testdata/subprog.o")).unwrap();
+        let programs = object.programs();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(object.license(), Some("GPL"));
+
+        /* add_one is appended to both programs, double_plus_one only to the second */
+        assert_eq!(programs[0].instructions.len(), 3 + 8);
+        assert_eq!(programs[1].instructions.len(), 3 + 3 + 8);
+
+        let loaded = object.load().unwrap();
+        let packet = [0u8; 64];
+
+        let first = loaded.program("xdp_first").unwrap();
+        let result = first.test_run().data_in(&packet).run().unwrap();
+        assert_eq!(result.retval, XdpAction::Pass as u32);
+
+        let second = loaded.program("xdp_second").unwrap();
+        let result = second.test_run().data_in(&packet).repeat(2).run().unwrap();
+        assert_eq!(result.retval, XdpAction::Tx as u32);
+
+        assert_eq!(loaded.global::<u64>("calls").unwrap(), 3);
+    }
+
+    #[test]
+    fn object_externs() {
+        let object = Object::from_bytes(include_bytes!("../testdata/externs.o")).unwrap();
+        let mut loaded = object.load().unwrap();
+        let program = loaded.program("xdp_externs").unwrap();
+        let result = program.test_run().data_in(&[0u8; 64]).run().unwrap();
+        assert_eq!(result.retval, XdpAction::Pass as u32);
+
+        let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").unwrap();
+        let mut version = release
+            .split(['.', '-'])
+            .map(|p| p.parse::<u64>().unwrap_or(0));
+        let (major, minor) = (version.next().unwrap(), version.next().unwrap());
+
+        let kallsyms = std::fs::read_to_string("/proc/kallsyms").unwrap();
+        let prog_put = kallsyms
+            .lines()
+            .find(|l| l.ends_with(" bpf_prog_put"))
+            .and_then(|l| u64::from_str_radix(l.split(' ').next().unwrap(), 16).ok())
+            .unwrap();
+
+        let results = loaded.global::<[u64; 5]>("results").unwrap();
+        assert_eq!(results[0] >> 8, major << 8 | minor);
+        assert_eq!(results[1], 1);
+        assert_ne!(results[2], 0);
+        assert_eq!(results[3], 0);
+        assert_eq!(results[4], prog_put);
+
+        assert_eq!(
+            loaded.global::<u32>("LINUX_KERNEL_VERSION").unwrap() as u64,
+            results[0]
+        );
+        assert!(loaded.set_global("CONFIG_HZ", &0u32).is_err());
+    }
 }
@@ -0,0 +1,3 @@
+pub use crate::platform::{
+    map_flags, map_ids, IdIter, Map, MapDefinition, MapFd, MapInfo, MapType,
+};
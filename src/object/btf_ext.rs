@@ -0,0 +1,143 @@
+use crate::btf::{BtfTypes, FuncInfo, LineInfo};
+use crate::error::Error;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::collections::HashMap;
+
+const BTF_MAGIC: u16 = 0xeb9f;
+
+fn malformed(what: &str) -> Error {
+    Error::MalformedObject(format!("BTF.ext: {}", what))
+}
+
+/// A CO-RE relocation, as in the kernel's `struct bpf_core_relo`, with the access
+/// string resolved.
+#[derive(Clone, Debug)]
+pub struct CoreRelo {
+    pub insn_off: u32,
+    pub type_id: u32,
+    pub access: String,
+    pub kind: u32,
+}
+
+/// The contents of a .BTF.ext section: per-section records that refer to instructions
+/// by byte offset within their section.
+#[derive(Clone, Debug, Default)]
+pub struct BtfExt {
+    pub func_info: HashMap<String, Vec<FuncInfo>>,
+    pub line_info: HashMap<String, Vec<LineInfo>>,
+    pub core_relos: HashMap<String, Vec<CoreRelo>>,
+}
+
+impl BtfExt {
+    pub fn parse(data: &[u8], btf: &BtfTypes) -> Result<Self, Error> {
+        if data.len() < 24 || LittleEndian::read_u16(data) != BTF_MAGIC {
+            return Err(malformed("bad magic"));
+        }
+
+        let hdr_len = LittleEndian::read_u32(&data[4..]) as usize;
+        let u32_at = |off: usize| -> Result<u32, Error> {
+            data.get(off..off + 4)
+                .map(LittleEndian::read_u32)
+                .ok_or_else(|| malformed("truncated"))
+        };
+
+        let func_info = Self::parse_info(
+            data,
+            hdr_len + u32_at(8)? as usize,
+            u32_at(12)? as usize,
+            btf,
+            8,
+            |rec| FuncInfo {
+                insn_off: LittleEndian::read_u32(&rec[0..]),
+                type_id: LittleEndian::read_u32(&rec[4..]),
+            },
+        )?;
+
+        let line_info = Self::parse_info(
+            data,
+            hdr_len + u32_at(16)? as usize,
+            u32_at(20)? as usize,
+            btf,
+            16,
+            |rec| LineInfo {
+                insn_off: LittleEndian::read_u32(&rec[0..]),
+                file_name_off: LittleEndian::read_u32(&rec[4..]),
+                line_off: LittleEndian::read_u32(&rec[8..]),
+                line_col: LittleEndian::read_u32(&rec[12..]),
+            },
+        )?;
+
+        /* CO-RE relocations were added to the header later */
+        let core_relos = if hdr_len >= 32 {
+            Self::parse_info(
+                data,
+                hdr_len + u32_at(24)? as usize,
+                u32_at(28)? as usize,
+                btf,
+                16,
+                |rec| CoreRelo {
+                    insn_off: LittleEndian::read_u32(&rec[0..]),
+                    type_id: LittleEndian::read_u32(&rec[4..]),
+                    access: btf.string(LittleEndian::read_u32(&rec[8..])).to_string(),
+                    kind: LittleEndian::read_u32(&rec[12..]),
+                },
+            )?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            func_info,
+            line_info,
+            core_relos,
+        })
+    }
+
+    /// Parses an info block: a record size followed by, for each section, the section's
+    /// name, the number of records and the records themselves. Records may be larger
+    /// than what's known here, in which case the extra bytes are ignored.
+    fn parse_info<T>(
+        data: &[u8],
+        offset: usize,
+        len: usize,
+        btf: &BtfTypes,
+        min_rec_size: usize,
+        parse_record: impl Fn(&[u8]) -> T,
+    ) -> Result<HashMap<String, Vec<T>>, Error> {
+        let mut info = HashMap::new();
+        if len == 0 {
+            return Ok(info);
+        }
+
+        let block = data
+            .get(offset..offset + len)
+            .ok_or_else(|| malformed("info out of bounds"))?;
+        let rec_size = LittleEndian::read_u32(block) as usize;
+        if rec_size < min_rec_size {
+            return Err(malformed("record size too small"));
+        }
+
+        let mut p = 4;
+        while p < block.len() {
+            let header = block
+                .get(p..p + 8)
+                .ok_or_else(|| malformed("truncated info"))?;
+            let name = btf.string(LittleEndian::read_u32(header)).to_string();
+            let count = LittleEndian::read_u32(&header[4..]) as usize;
+            p += 8;
+
+            let records = block
+                .get(p..p + count * rec_size)
+                .ok_or_else(|| malformed("truncated records"))?;
+            info.insert(
+                name,
+                records.chunks_exact(rec_size).map(&parse_record).collect(),
+            );
+            p += count * rec_size;
+        }
+
+        Ok(info)
+    }
+}
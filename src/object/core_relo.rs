@@ -0,0 +1,591 @@
+//! BPF CO-RE (Compile Once - Run Everywhere) relocations: instructions that encode
+//! facts about kernel types, e.g.: a field's offset, are rewritten to match the
+//! running kernel's BTF before loading.
+
+use super::btf_ext::CoreRelo;
+use crate::btf::{
+    BtfTypes, BTF_KIND_ARRAY, BTF_KIND_ENUM, BTF_KIND_ENUM64, BTF_KIND_FLOAT, BTF_KIND_FWD,
+    BTF_KIND_INT, BTF_KIND_PTR, BTF_KIND_STRUCT, BTF_KIND_UNION,
+};
+use crate::error::Error;
+
+use std::collections::HashMap;
+
+const FIELD_BYTE_OFFSET: u32 = 0;
+const FIELD_BYTE_SIZE: u32 = 1;
+const FIELD_EXISTS: u32 = 2;
+const FIELD_SIGNED: u32 = 3;
+const FIELD_LSHIFT_U64: u32 = 4;
+const FIELD_RSHIFT_U64: u32 = 5;
+const TYPE_ID_LOCAL: u32 = 6;
+const TYPE_ID_TARGET: u32 = 7;
+const TYPE_EXISTS: u32 = 8;
+const TYPE_SIZE: u32 = 9;
+const ENUMVAL_EXISTS: u32 = 10;
+const ENUMVAL_VALUE: u32 = 11;
+
+const BTF_INT_SIGNED: u32 = 1;
+
+/* the same marker libbpf uses, so the verifier's complaint is recognizable */
+const POISON_HELPER: u64 = 0xbad2310;
+
+const BPF_CLASS_LD: u8 = 0x00;
+const BPF_CLASS_LDX: u8 = 0x01;
+const BPF_CLASS_ST: u8 = 0x02;
+const BPF_CLASS_STX: u8 = 0x03;
+const BPF_CLASS_ALU: u8 = 0x04;
+const BPF_CLASS_ALU64: u8 = 0x07;
+const BPF_LD_IMM64: u8 = 0x18;
+const BPF_CALL: u64 = 0x85;
+const BPF_SRC_X: u8 = 0x08;
+const BPF_SIZE_MASK: u8 = 0x18;
+const BPF_SIZE_W: u8 = 0x00;
+const BPF_SIZE_H: u8 = 0x08;
+const BPF_SIZE_B: u8 = 0x10;
+const BPF_SIZE_DW: u8 = 0x18;
+
+fn malformed(what: String) -> Error {
+    Error::MalformedObject(format!("CO-RE: {}", what))
+}
+
+/// The name of a type or field without its "flavor", e.g.: `task_struct___old` is
+/// matched against `task_struct`.
+fn essential_name(name: &str) -> &str {
+    match name.find("___") {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
+/// The bit offset of the `index`th element of an array of `size` byte elements.
+fn element_bit_offset(index: u32, size: u32) -> Result<u32, Error> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| offset.checked_mul(8))
+        .ok_or_else(|| malformed(format!("offset of element {} overflows", index)))
+}
+
+fn add_bit_offset(a: u32, b: u32) -> Result<u32, Error> {
+    a.checked_add(b)
+        .ok_or_else(|| malformed("field offset overflows".into()))
+}
+
+fn is_composite(kind: u32) -> bool {
+    kind == BTF_KIND_STRUCT || kind == BTF_KIND_UNION
+}
+
+fn is_enum(kind: u32) -> bool {
+    kind == BTF_KIND_ENUM || kind == BTF_KIND_ENUM64
+}
+
+/// One step of an access string after the first: a named struct/union member or an
+/// array element.
+#[derive(Clone, Debug)]
+enum Access {
+    Member { name: String, type_id: u32 },
+    Array { index: u32 },
+}
+
+/// The location of a field, resolved in either the local or the target BTF.
+#[derive(Copy, Clone, Debug)]
+struct Field {
+    type_id: u32,
+    bit_offset: u32,
+    bitfield_size: u32,
+}
+
+/// Relocates instructions against the kernel's BTF.
+pub struct CoreRelocator<'a> {
+    local: &'a BtfTypes,
+    target: &'a BtfTypes,
+
+    /// Target type ids by essential name.
+    target_index: HashMap<&'a str, Vec<u32>>,
+}
+
+impl<'a> CoreRelocator<'a> {
+    pub fn new(local: &'a BtfTypes, target: &'a BtfTypes) -> Self {
+        let mut target_index: HashMap<&str, Vec<u32>> = HashMap::new();
+        for (name, ids) in target.name_index() {
+            target_index
+                .entry(essential_name(name))
+                .or_default()
+                .extend(ids);
+        }
+
+        Self {
+            local,
+            target,
+            target_index,
+        }
+    }
+
+    /// Applies a relocation to a program's instructions.
+    ///
+    /// # Arguments
+    ///
+    /// * `instructions` - The program's instructions.
+    /// * `insn_index` - The index of the relocated instruction within the program.
+    /// * `relo` - The relocation.
+    pub fn apply(
+        &self,
+        instructions: &mut [u64],
+        insn_index: usize,
+        relo: &CoreRelo,
+    ) -> Result<(), Error> {
+        if insn_index >= instructions.len() {
+            return Err(malformed(format!(
+                "instruction {} out of bounds",
+                insn_index
+            )));
+        }
+
+        match self.compute(relo)? {
+            Some(value) => {
+                Self::patch(instructions, insn_index, value, relo.kind)?;
+                if relo.kind == FIELD_BYTE_OFFSET {
+                    self.resize_access(instructions, insn_index, relo)?;
+                }
+                Ok(())
+            }
+            None => {
+                /*
+                 * the program can't work on this kernel, but it may never execute the
+                 * instruction, e.g.: when guarded by a field existence check, so it's
+                 * left to the verifier to reject if it's reachable.
+                 */
+                let poison = BPF_CALL | POISON_HELPER << 32;
+                if instructions[insn_index] as u8 == BPF_LD_IMM64 {
+                    if let Some(next) = instructions.get_mut(insn_index + 1) {
+                        *next = poison;
+                    }
+                }
+                instructions[insn_index] = poison;
+                Ok(())
+            }
+        }
+    }
+
+    /// Adjusts the width of a memory access to a field whose size differs in the
+    /// target, e.g.: a field that grew from u32 to u64. Bitfields are read through
+    /// their own relocations and are left alone.
+    fn resize_access(
+        &self,
+        instructions: &mut [u64],
+        insn_index: usize,
+        relo: &CoreRelo,
+    ) -> Result<(), Error> {
+        let opcode = instructions[insn_index] as u8;
+        if !matches!(opcode & 0x07, BPF_CLASS_LDX | BPF_CLASS_ST | BPF_CLASS_STX) {
+            return Ok(());
+        }
+
+        let (_, _, local_field) = self.parse_access(relo)?;
+        if local_field.bitfield_size != 0 {
+            return Ok(());
+        }
+
+        let local_size = self.local.resolve_size(local_field.type_id)?;
+        let target_size = match self.compute(&CoreRelo {
+            kind: FIELD_BYTE_SIZE,
+            ..relo.clone()
+        })? {
+            Some(size) => size as u32,
+            None => return Err(malformed(format!("no size for {}", relo.access))),
+        };
+        if local_size == target_size {
+            return Ok(());
+        }
+
+        let insn_size = match opcode & BPF_SIZE_MASK {
+            BPF_SIZE_B => 1,
+            BPF_SIZE_H => 2,
+            BPF_SIZE_W => 4,
+            _ => 8,
+        };
+        let size_bits = match target_size {
+            1 => BPF_SIZE_B,
+            2 => BPF_SIZE_H,
+            4 => BPF_SIZE_W,
+            8 => BPF_SIZE_DW,
+            _ => 0xff,
+        };
+        if insn_size != local_size || size_bits == 0xff {
+            return Err(malformed(format!(
+                "can't resize access at {} from {} to {} bytes",
+                insn_index, insn_size, target_size
+            )));
+        }
+
+        let opcode = (opcode & !BPF_SIZE_MASK) | size_bits;
+        instructions[insn_index] = (instructions[insn_index] & !0xff) | opcode as u64;
+        Ok(())
+    }
+
+    /// Computes the new value for a relocation, or `None` if it can't be satisfied by
+    /// the target kernel.
+    fn compute(&self, relo: &CoreRelo) -> Result<Option<u64>, Error> {
+        if relo.kind == TYPE_ID_LOCAL {
+            return Ok(Some(relo.type_id as u64));
+        }
+
+        let local_type = self.local.get(relo.type_id)?;
+        let local_name = essential_name(self.local.string(local_type.name_off));
+        if local_name.is_empty() {
+            return Err(malformed(format!(
+                "relocation against anonymous type {}",
+                relo.type_id
+            )));
+        }
+
+        let candidates = self
+            .target_index
+            .get(local_name)
+            .map(|ids| ids.as_slice())
+            .unwrap_or(&[]);
+
+        /* every matching candidate has to agree on the value */
+        let mut result = None;
+        for &target_id in candidates {
+            let target_type = self.target.get(target_id)?;
+            if target_type.kind != local_type.kind {
+                continue;
+            }
+
+            let value = match relo.kind {
+                FIELD_BYTE_OFFSET | FIELD_BYTE_SIZE | FIELD_EXISTS | FIELD_SIGNED
+                | FIELD_LSHIFT_U64 | FIELD_RSHIFT_U64 => self.compute_field(relo, target_id)?,
+                TYPE_ID_TARGET => Some(target_id as u64),
+                TYPE_EXISTS => Some(1),
+                TYPE_SIZE => Some(self.target.resolve_size(target_id)? as u64),
+                ENUMVAL_EXISTS | ENUMVAL_VALUE => self.compute_enum(relo, target_id)?,
+                kind => return Err(malformed(format!("unsupported relocation kind {}", kind))),
+            };
+
+            match (value, result) {
+                (None, _) => continue,
+                (Some(v), Some(r)) if v != r => {
+                    return Err(malformed(format!(
+                        "ambiguous relocation against {}",
+                        local_name
+                    )))
+                }
+                (v, _) => result = v,
+            }
+        }
+
+        Ok(match (result, relo.kind) {
+            (None, FIELD_EXISTS | TYPE_EXISTS | ENUMVAL_EXISTS) => Some(0),
+            (result, _) => result,
+        })
+    }
+
+    /// Parses an access string like `0:1:2`: the first index is into an array of the
+    /// root type, the rest are member or element indexes. Returns the first index, the
+    /// remaining accesses and the accessed field in the local BTF.
+    fn parse_access(&self, relo: &CoreRelo) -> Result<(u32, Vec<Access>, Field), Error> {
+        let mut indexes = relo.access.split(':').map(|i| {
+            i.parse::<u32>()
+                .map_err(|_| malformed(format!("bad access string {}", relo.access)))
+        });
+
+        let root_index = indexes
+            .next()
+            .ok_or_else(|| malformed("empty access string".into()))??;
+        let mut field = Field {
+            type_id: relo.type_id,
+            bit_offset: element_bit_offset(root_index, self.local.resolve_size(relo.type_id)?)?,
+            bitfield_size: 0,
+        };
+        let mut accesses = vec![];
+        for index in indexes {
+            let index = index?;
+            let t = self
+                .local
+                .get(self.local.skip_mods_and_typedefs(field.type_id)?)?;
+            if is_composite(t.kind) {
+                let member = t
+                    .members
+                    .get(index as usize)
+                    .ok_or_else(|| malformed(format!("member {} out of bounds", index)))?;
+                let type_id = member.type_id;
+                field = Field {
+                    type_id,
+                    bit_offset: add_bit_offset(field.bit_offset, member.bit_offset)?,
+                    bitfield_size: member.bitfield_size,
+                };
+
+                /* anonymous members are found by searching the target's members */
+                let name = self.local.string(member.name_off);
+                if !name.is_empty() {
+                    accesses.push(Access::Member {
+                        name: name.to_string(),
+                        type_id,
+                    });
+                }
+            } else if t.kind == BTF_KIND_ARRAY {
+                let offset = element_bit_offset(index, self.local.resolve_size(t.elem_type)?)?;
+                field = Field {
+                    type_id: t.elem_type,
+                    bit_offset: add_bit_offset(field.bit_offset, offset)?,
+                    bitfield_size: 0,
+                };
+                accesses.push(Access::Array { index });
+            } else {
+                return Err(malformed(format!("bad access string {}", relo.access)));
+            }
+        }
+
+        Ok((root_index, accesses, field))
+    }
+
+    /// Finds a member by name in a target struct or union, looking inside anonymous
+    /// members too. Returns the member's type, bit offset and bitfield size.
+    fn find_member(&self, id: u32, name: &str) -> Result<Option<Field>, Error> {
+        let t = self.target.get(id)?;
+        for member in t.members.iter() {
+            let member_name = self.target.string(member.name_off);
+            if member_name.is_empty() {
+                let inner = self.target.skip_mods_and_typedefs(member.type_id)?;
+                if !is_composite(self.target.get(inner)?.kind) {
+                    continue;
+                }
+
+                if let Some(field) = self.find_member(inner, name)? {
+                    return Ok(Some(Field {
+                        bit_offset: add_bit_offset(member.bit_offset, field.bit_offset)?,
+                        ..field
+                    }));
+                }
+            } else if essential_name(member_name) == essential_name(name) {
+                return Ok(Some(Field {
+                    type_id: member.type_id,
+                    bit_offset: member.bit_offset,
+                    bitfield_size: member.bitfield_size,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether a local field's type is compatible with a target field's type: the same
+    /// kind, with composites, pointers, ints and floats matching any of their kind.
+    fn fields_compatible(&self, local_id: u32, target_id: u32) -> Result<bool, Error> {
+        let local = self
+            .local
+            .get(self.local.skip_mods_and_typedefs(local_id)?)?;
+        let target = self
+            .target
+            .get(self.target.skip_mods_and_typedefs(target_id)?)?;
+
+        if is_composite(local.kind) && is_composite(target.kind) {
+            return Ok(true);
+        }
+
+        if is_enum(local.kind) && is_enum(target.kind) {
+            return Ok(true);
+        }
+
+        if local.kind != target.kind {
+            return Ok(false);
+        }
+
+        Ok(match local.kind {
+            BTF_KIND_FWD => {
+                essential_name(self.local.string(local.name_off))
+                    == essential_name(self.target.string(target.name_off))
+            }
+            BTF_KIND_INT | BTF_KIND_PTR | BTF_KIND_FLOAT => true,
+            BTF_KIND_ARRAY => self.fields_compatible(local.elem_type, target.elem_type)?,
+            _ => false,
+        })
+    }
+
+    /// Resolves the local access path in a target type, returning `None` if a member
+    /// doesn't exist or doesn't have a compatible type.
+    fn match_field(
+        &self,
+        target_id: u32,
+        root_index: u32,
+        accesses: &[Access],
+    ) -> Result<Option<Field>, Error> {
+        let mut field = Field {
+            type_id: target_id,
+            bit_offset: element_bit_offset(root_index, self.target.resolve_size(target_id)?)?,
+            bitfield_size: 0,
+        };
+
+        for access in accesses {
+            let id = self.target.skip_mods_and_typedefs(field.type_id)?;
+            let t = self.target.get(id)?;
+            match access {
+                Access::Member { name, type_id } => {
+                    if !is_composite(t.kind) {
+                        return Ok(None);
+                    }
+
+                    let member = match self.find_member(id, name)? {
+                        Some(member) => member,
+                        None => return Ok(None),
+                    };
+
+                    if !self.fields_compatible(*type_id, member.type_id)? {
+                        return Ok(None);
+                    }
+
+                    field = Field {
+                        bit_offset: add_bit_offset(field.bit_offset, member.bit_offset)?,
+                        ..member
+                    };
+                }
+                Access::Array { index } => {
+                    /* a zero-length array is a flexible array member */
+                    if t.kind != BTF_KIND_ARRAY || (t.extra != 0 && *index >= t.extra) {
+                        return Ok(None);
+                    }
+
+                    let elem_size = self.target.resolve_size(t.elem_type)?;
+                    field = Field {
+                        type_id: t.elem_type,
+                        bit_offset: add_bit_offset(
+                            field.bit_offset,
+                            element_bit_offset(*index, elem_size)?,
+                        )?,
+                        bitfield_size: 0,
+                    };
+                }
+            }
+        }
+
+        Ok(Some(field))
+    }
+
+    fn compute_field(&self, relo: &CoreRelo, target_id: u32) -> Result<Option<u64>, Error> {
+        let (root_index, accesses, _) = self.parse_access(relo)?;
+        let field = match self.match_field(target_id, root_index, &accesses)? {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+
+        if relo.kind == FIELD_EXISTS {
+            return Ok(Some(1));
+        }
+
+        let mut byte_size = self.target.resolve_size(field.type_id)?;
+        let (byte_offset, bit_size) = if field.bitfield_size == 0 {
+            (field.bit_offset / 8, byte_size * 8)
+        } else {
+            /* find the smallest aligned load that covers the whole bitfield */
+            let fits = |byte_size: u32| {
+                let byte_offset = field.bit_offset / 8 / byte_size * byte_size;
+                (
+                    byte_offset,
+                    field.bit_offset + field.bitfield_size - byte_offset * 8 <= byte_size * 8,
+                )
+            };
+            loop {
+                match fits(byte_size) {
+                    (byte_offset, true) => break (byte_offset, field.bitfield_size),
+                    _ if byte_size >= 8 => {
+                        return Err(malformed("bitfield can't be loaded".into()))
+                    }
+                    _ => byte_size *= 2,
+                }
+            }
+        };
+
+        Ok(Some(match relo.kind {
+            FIELD_BYTE_OFFSET => byte_offset as u64,
+            FIELD_BYTE_SIZE => byte_size as u64,
+            FIELD_SIGNED => {
+                let t = self
+                    .target
+                    .get(self.target.skip_mods_and_typedefs(field.type_id)?)?;
+                let signed = match t.kind {
+                    BTF_KIND_INT => (t.extra >> 24) & BTF_INT_SIGNED != 0,
+                    BTF_KIND_ENUM | BTF_KIND_ENUM64 => t.kind_flag,
+                    _ => false,
+                };
+                signed as u64
+            }
+            FIELD_LSHIFT_U64 => (64 - (field.bit_offset + bit_size - byte_offset * 8)) as u64,
+            FIELD_RSHIFT_U64 => (64 - bit_size) as u64,
+            _ => unreachable!(),
+        }))
+    }
+
+    fn compute_enum(&self, relo: &CoreRelo, target_id: u32) -> Result<Option<u64>, Error> {
+        let index = relo
+            .access
+            .parse::<usize>()
+            .map_err(|_| malformed(format!("bad access string {}", relo.access)))?;
+        let local = self.local.get(relo.type_id)?;
+        let (name_off, _) = local
+            .enum_values
+            .get(index)
+            .ok_or_else(|| malformed(format!("enumerator {} out of bounds", index)))?;
+        let name = essential_name(self.local.string(*name_off));
+
+        let target = self.target.get(target_id)?;
+        Ok(target
+            .enum_values
+            .iter()
+            .find(|(off, _)| essential_name(self.target.string(*off)) == name)
+            .map(|(_, value)| match relo.kind {
+                ENUMVAL_EXISTS => 1,
+                _ => *value as u64,
+            }))
+    }
+
+    /// Writes a relocated value into an instruction: the immediate of an ALU
+    /// instruction or a 64-bit load, or the offset of a memory access.
+    fn patch(instructions: &mut [u64], index: usize, value: u64, kind: u32) -> Result<(), Error> {
+        let insn = instructions[index];
+        let opcode = insn as u8;
+        let class = opcode & 0x07;
+
+        if opcode == BPF_LD_IMM64 {
+            let next = instructions
+                .get_mut(index + 1)
+                .ok_or_else(|| malformed("truncated ld_imm64".into()))?;
+            *next = (*next & 0xffffffff) | (value >> 32) << 32;
+            instructions[index] = (insn & 0xffffffff) | (value & 0xffffffff) << 32;
+            return Ok(());
+        }
+
+        match class {
+            BPF_CLASS_ALU | BPF_CLASS_ALU64 if opcode & BPF_SRC_X == 0 => {
+                /* enumerators may be negative, and unsigned ones may use all 32 bits */
+                let value = value as i64;
+                if value < i32::MIN as i64 || value > u32::MAX as i64 {
+                    return Err(malformed(format!(
+                        "value {} doesn't fit an immediate",
+                        value
+                    )));
+                }
+                instructions[index] = (insn & 0xffffffff) | (value as u32 as u64) << 32;
+            }
+            BPF_CLASS_LDX | BPF_CLASS_ST | BPF_CLASS_STX if kind == FIELD_BYTE_OFFSET => {
+                let off: i16 = value
+                    .try_into()
+                    .map_err(|_| malformed(format!("offset {} doesn't fit", value)))?;
+                instructions[index] = (insn & !0xffff_0000) | (off as u16 as u64) << 16;
+            }
+            BPF_CLASS_LD => {
+                return Err(malformed(format!(
+                    "can't relocate legacy load at {}",
+                    index
+                )))
+            }
+            _ => {
+                return Err(malformed(format!(
+                    "can't relocate instruction {:#x} at {}",
+                    opcode, index
+                )))
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use crate::error::Error;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::collections::HashMap;
+
+const ELFMAG: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+pub const EM_BPF: u16 = 247;
+
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_REL: u32 = 9;
+pub const SHT_DYNSYM: u32 = 11;
+pub const SHT_GNU_VERDEF: u32 = 0x6ffffffd;
+pub const SHT_GNU_VERNEED: u32 = 0x6ffffffe;
+pub const SHT_GNU_VERSYM: u32 = 0x6fffffff;
+
+pub const PT_LOAD: u32 = 1;
+pub const PF_X: u32 = 0x1;
+
+pub const SHF_EXECINSTR: u64 = 0x4;
+
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+pub const STT_GNU_IFUNC: u8 = 10;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+
+pub const SHN_UNDEF: u16 = 0;
+
+/* versym entries with this bit set aren't the default version of a symbol */
+const VERSYM_HIDDEN: u16 = 0x8000;
+
+fn malformed(what: &str) -> Error {
+    Error::MalformedObject(what.to_string())
+}
+
+/// Computes `base + index * size`, for offsets of table entries from the file.
+fn entry_offset(base: usize, index: usize, size: usize) -> Result<usize, Error> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| offset.checked_add(base))
+        .ok_or_else(|| malformed("bad offset"))
+}
+
+/// A section header along with its resolved name.
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub index: usize,
+    pub name: String,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+}
+
+/// A program header, describing how a part of the file is mapped.
+#[derive(Clone, Debug)]
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+}
+
+/// A symbol table entry along with its resolved name.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub info: u8,
+    pub shndx: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl Symbol {
+    pub fn sym_type(&self) -> u8 {
+        self.info & 0xf
+    }
+
+    pub fn bind(&self) -> u8 {
+        self.info >> 4
+    }
+}
+
+/// The version a dynamic symbol is bound to, e.g.: `GLIBC_2.2.5`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SymbolVersion {
+    pub name: String,
+
+    /// Whether this isn't the version references without one bind to, i.e.: it
+    /// was `sym@VERSION` rather than `sym@@VERSION`.
+    pub hidden: bool,
+}
+
+/// A relocation entry from a SHT_REL or SHT_RELA section; BPF objects use SHT_REL, so
+/// addends aren't kept.
+#[derive(Clone, Debug)]
+pub struct Relocation {
+    pub offset: u64,
+    pub sym: u32,
+    pub rel_type: u32,
+}
+
+/// A minimal, read-only parser for 64-bit little-endian ELF files, covering what's
+/// needed to load BPF objects.
+pub struct Elf<'a> {
+    data: &'a [u8],
+    pub e_machine: u16,
+    pub e_entry: u64,
+    pub sections: Vec<Section>,
+    pub program_headers: Vec<ProgramHeader>,
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 64 || &data[0..4] != ELFMAG {
+            return Err(malformed("not an ELF file"));
+        }
+
+        if data[4] != ELFCLASS64 || data[5] != ELFDATA2LSB {
+            return Err(malformed(
+                "only 64-bit little-endian ELF files are supported",
+            ));
+        }
+
+        let e_machine = LittleEndian::read_u16(&data[18..]);
+        let e_entry = LittleEndian::read_u64(&data[24..]);
+        let phoff = LittleEndian::read_u64(&data[32..]) as usize;
+        let phentsize = LittleEndian::read_u16(&data[54..]) as usize;
+        let phnum = LittleEndian::read_u16(&data[56..]) as usize;
+        let shoff = LittleEndian::read_u64(&data[40..]) as usize;
+        let shentsize = LittleEndian::read_u16(&data[58..]) as usize;
+        let shnum = LittleEndian::read_u16(&data[60..]) as usize;
+        let shstrndx = LittleEndian::read_u16(&data[62..]) as usize;
+
+        let mut sections = vec![];
+        let mut name_offsets = vec![];
+        for i in 0..shnum {
+            let sh = Self::slice(data, entry_offset(shoff, i, shentsize)?, 64)?;
+            name_offsets.push(LittleEndian::read_u32(&sh[0..]) as usize);
+            sections.push(Section {
+                index: i,
+                name: String::new(),
+                sh_type: LittleEndian::read_u32(&sh[4..]),
+                flags: LittleEndian::read_u64(&sh[8..]),
+                offset: LittleEndian::read_u64(&sh[24..]),
+                size: LittleEndian::read_u64(&sh[32..]),
+                link: LittleEndian::read_u32(&sh[40..]),
+                info: LittleEndian::read_u32(&sh[44..]),
+            });
+        }
+
+        let mut program_headers = vec![];
+        for i in 0..phnum {
+            let ph = Self::slice(data, entry_offset(phoff, i, phentsize)?, 56)?;
+            program_headers.push(ProgramHeader {
+                p_type: LittleEndian::read_u32(&ph[0..]),
+                flags: LittleEndian::read_u32(&ph[4..]),
+                offset: LittleEndian::read_u64(&ph[8..]),
+                vaddr: LittleEndian::read_u64(&ph[16..]),
+                filesz: LittleEndian::read_u64(&ph[32..]),
+            });
+        }
+
+        let mut elf = Self {
+            data,
+            e_machine,
+            e_entry,
+            sections,
+            program_headers,
+        };
+
+        if shstrndx < elf.sections.len() {
+            let strtab = elf.sections[shstrndx].clone();
+            for (section, offset) in elf.sections.iter_mut().zip(name_offsets) {
+                section.name = Self::string_at(data, &strtab, offset)?;
+            }
+        }
+
+        Ok(elf)
+    }
+
+    fn slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+        data.get(
+            offset
+                ..offset
+                    .checked_add(len)
+                    .ok_or_else(|| malformed("bad offset"))?,
+        )
+        .ok_or_else(|| malformed("truncated file"))
+    }
+
+    fn string_at(data: &[u8], strtab: &Section, offset: usize) -> Result<String, Error> {
+        let table = Self::slice(data, strtab.offset as usize, strtab.size as usize)?;
+        let bytes = table
+            .get(offset..)
+            .ok_or_else(|| malformed("string offset out of bounds"))?;
+        let len = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..len]).to_string())
+    }
+
+    /// Returns the contents of a section; empty for SHT_NOBITS sections like .bss.
+    pub fn section_data(&self, section: &Section) -> Result<&'a [u8], Error> {
+        if section.sh_type == SHT_NOBITS {
+            return Ok(&[]);
+        }
+
+        Self::slice(self.data, section.offset as usize, section.size as usize)
+    }
+
+    pub fn section_by_name(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    fn read_symbols(&self, section: &Section) -> Result<Vec<Symbol>, Error> {
+        let data = self.section_data(section)?;
+        let strtab = self
+            .sections
+            .get(section.link as usize)
+            .ok_or_else(|| malformed("symbol table without string table"))?;
+
+        let mut symbols = vec![];
+        for sym in data.chunks_exact(24) {
+            symbols.push(Symbol {
+                name: Self::string_at(
+                    self.data,
+                    strtab,
+                    LittleEndian::read_u32(&sym[0..]) as usize,
+                )?,
+                info: sym[4],
+                shndx: LittleEndian::read_u16(&sym[6..]),
+                value: LittleEndian::read_u64(&sym[8..]),
+                size: LittleEndian::read_u64(&sym[16..]),
+            });
+        }
+
+        Ok(symbols)
+    }
+
+    /// Returns the static symbol table (SHT_SYMTAB), empty if the file is stripped.
+    pub fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        match self.sections.iter().find(|s| s.sh_type == SHT_SYMTAB) {
+            Some(section) => self.read_symbols(section),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the dynamic symbol table (SHT_DYNSYM), empty for static executables.
+    pub fn dynamic_symbols(&self) -> Result<Vec<Symbol>, Error> {
+        match self.sections.iter().find(|s| s.sh_type == SHT_DYNSYM) {
+            Some(section) => self.read_symbols(section),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the version of each dynamic symbol, by index; `None` for symbols
+    /// without one or if the file has no version sections.
+    pub fn dynamic_symbol_versions(&self) -> Result<Vec<Option<SymbolVersion>>, Error> {
+        let versym = match self.sections.iter().find(|s| s.sh_type == SHT_GNU_VERSYM) {
+            Some(section) => self.section_data(section)?,
+            None => return Ok(vec![]),
+        };
+
+        /* version indexes to names, from both the versions defined and needed */
+        let mut names = HashMap::new();
+        for section in self.sections.iter() {
+            match section.sh_type {
+                SHT_GNU_VERDEF => self.read_verdefs(section, &mut names)?,
+                SHT_GNU_VERNEED => self.read_verneeds(section, &mut names)?,
+                _ => {}
+            }
+        }
+
+        Ok(versym
+            .chunks_exact(2)
+            .map(|entry| {
+                let entry = LittleEndian::read_u16(entry);
+                names
+                    .get(&(entry & !VERSYM_HIDDEN))
+                    .map(|name: &String| SymbolVersion {
+                        name: name.clone(),
+                        hidden: entry & VERSYM_HIDDEN != 0,
+                    })
+            })
+            .collect())
+    }
+
+    /// Reads Elf64_Verdef entries, taking the first Elf64_Verdaux of each as its name.
+    fn read_verdefs(
+        &self,
+        section: &Section,
+        names: &mut HashMap<u16, String>,
+    ) -> Result<(), Error> {
+        let data = self.section_data(section)?;
+        let strtab = self
+            .sections
+            .get(section.link as usize)
+            .ok_or_else(|| malformed("version definitions without string table"))?;
+
+        let mut offset = 0;
+        for _ in 0..section.info {
+            let verdef = Self::slice(data, offset, 20)?;
+            let ndx = LittleEndian::read_u16(&verdef[4..]);
+            let cnt = LittleEndian::read_u16(&verdef[6..]);
+            let aux = LittleEndian::read_u32(&verdef[12..]) as usize;
+            if cnt > 0 {
+                let verdaux = Self::slice(data, entry_offset(offset, 1, aux)?, 8)?;
+                let name = LittleEndian::read_u32(&verdaux[0..]) as usize;
+                names.insert(ndx, Self::string_at(self.data, strtab, name)?);
+            }
+
+            match LittleEndian::read_u32(&verdef[16..]) as usize {
+                0 => break,
+                next => offset = entry_offset(offset, 1, next)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads Elf64_Verneed entries, naming versions by their Elf64_Vernaux entries.
+    fn read_verneeds(
+        &self,
+        section: &Section,
+        names: &mut HashMap<u16, String>,
+    ) -> Result<(), Error> {
+        let data = self.section_data(section)?;
+        let strtab = self
+            .sections
+            .get(section.link as usize)
+            .ok_or_else(|| malformed("version requirements without string table"))?;
+
+        let mut offset = 0;
+        for _ in 0..section.info {
+            let verneed = Self::slice(data, offset, 16)?;
+            let cnt = LittleEndian::read_u16(&verneed[2..]);
+
+            let mut aux = entry_offset(offset, 1, LittleEndian::read_u32(&verneed[8..]) as usize)?;
+            for _ in 0..cnt {
+                let vernaux = Self::slice(data, aux, 16)?;
+                let other = LittleEndian::read_u16(&vernaux[6..]);
+                let name = LittleEndian::read_u32(&vernaux[8..]) as usize;
+                names.insert(other, Self::string_at(self.data, strtab, name)?);
+                aux = entry_offset(aux, 1, LittleEndian::read_u32(&vernaux[12..]) as usize)?;
+            }
+
+            match LittleEndian::read_u32(&verneed[12..]) as usize {
+                0 => break,
+                next => offset = entry_offset(offset, 1, next)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the file offset of a function, as uprobes take. The symbol may carry a
+    /// version, e.g.: `malloc@GLIBC_2.2.5`; without one the default version is used.
+    pub fn function_file_offset(&self, symbol: &str) -> Result<u64, Error> {
+        let not_found = || Error::SymbolNotFound(symbol.to_string());
+        let (name, version) = match symbol.split_once('@') {
+            Some((name, version)) => (name, Some(version.trim_start_matches('@'))),
+            None => (symbol, None),
+        };
+        let is_function = |sym: &Symbol| {
+            matches!(sym.sym_type(), STT_FUNC | STT_GNU_IFUNC) && sym.shndx != SHN_UNDEF
+        };
+
+        /* the static symbol table has no versions, it only helps with plain names */
+        let mut value = None;
+        if version.is_none() {
+            value = self
+                .symbols()?
+                .into_iter()
+                .find(|sym| sym.name == name && is_function(sym))
+                .map(|sym| sym.value);
+        }
+
+        if value.is_none() {
+            let versions = self.dynamic_symbol_versions()?;
+            let mut candidates = self
+                .dynamic_symbols()?
+                .into_iter()
+                .enumerate()
+                .filter(|(_, sym)| sym.name == name && is_function(sym))
+                .map(|(i, sym)| (sym.value, versions.get(i).cloned().flatten()));
+
+            value = match version {
+                Some(version) => candidates
+                    .find(|(_, v)| v.as_ref().map(|v| v.name.as_str()) == Some(version))
+                    .map(|(value, _)| value),
+                None => {
+                    let candidates: Vec<_> = candidates.collect();
+                    candidates
+                        .iter()
+                        .find(|(_, v)| !v.as_ref().is_some_and(|v| v.hidden))
+                        .or_else(|| candidates.first())
+                        .map(|(value, _)| *value)
+                }
+            };
+        }
+
+        self.file_offset(value.ok_or_else(not_found)?)
+            .ok_or_else(not_found)
+    }
+
+    /// Translates a virtual address to a file offset through the loadable segment
+    /// containing it.
+    pub fn file_offset(&self, vaddr: u64) -> Option<u64> {
+        self.program_headers
+            .iter()
+            .find(|ph| {
+                ph.p_type == PT_LOAD
+                    && ph.flags & PF_X != 0
+                    && vaddr >= ph.vaddr
+                    && vaddr - ph.vaddr < ph.filesz
+            })
+            .and_then(|ph| (vaddr - ph.vaddr).checked_add(ph.offset))
+    }
+
+    /// Returns the relocations of a SHT_REL or SHT_RELA section.
+    pub fn relocations(&self, section: &Section) -> Result<Vec<Relocation>, Error> {
+        let data = self.section_data(section)?;
+        let entsize = if section.sh_type == SHT_RELA { 24 } else { 16 };
+
+        Ok(data
+            .chunks_exact(entsize)
+            .map(|rel| {
+                let info = LittleEndian::read_u64(&rel[8..]);
+                Relocation {
+                    offset: LittleEndian::read_u64(&rel[0..]),
+                    sym: (info >> 32) as u32,
+                    rel_type: info as u32,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,674 @@
+//! Resolution of `extern` declarations against the running kernel: kernel symbols
+//! (`__ksym`) and kernel configuration values (`__kconfig`).
+
+use crate::btf::{
+    btf_ids, Btf, BtfTypes, BTF_KIND_ARRAY, BTF_KIND_DATASEC, BTF_KIND_ENUM, BTF_KIND_FUNC,
+    BTF_KIND_INT, BTF_KIND_VAR,
+};
+use crate::error::Error;
+
+use super::elf::{Symbol, SHN_UNDEF, STB_WEAK};
+use super::{BPF_CALL, BPF_LD_IMM64};
+
+use flate2::read::GzDecoder;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+const KCONFIG_SECTION: &str = ".kconfig";
+const KSYMS_SECTION: &str = ".ksyms";
+
+const BTF_INT_SIGNED: u32 = 1 << 0;
+const BTF_INT_CHAR: u32 = 1 << 1;
+const BTF_INT_BOOL: u32 = 1 << 2;
+
+const BPF_PSEUDO_BTF_ID: u64 = 3;
+const BPF_PSEUDO_KFUNC_CALL: u64 = 2;
+
+const BTF_VAR_GLOBAL_ALLOCATED: u32 = 1;
+const BTF_FUNC_STATIC: u32 = 0;
+
+/// How a `.kconfig` value is stored, following libbpf's conventions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KconfigType {
+    /// `bool`: y is 1, n is 0.
+    Bool,
+
+    /// `char`: the raw 'y', 'n' or 'm'.
+    Char,
+
+    /// `enum libbpf_tristate`: y is 1, n is 0, m is 2.
+    Tristate,
+
+    /// An integer of the given size in bytes.
+    Int { size: usize, signed: bool },
+
+    /// A `char` array holding a string value, nul-terminated.
+    Str(usize),
+}
+
+impl KconfigType {
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Bool | Self::Char => 1,
+            Self::Tristate => 4,
+            Self::Int { size, .. } => *size,
+            Self::Str(len) => *len,
+        }
+    }
+
+    fn align(&self) -> usize {
+        match self {
+            Self::Str(_) => 1,
+            _ => self.size(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ExternKind {
+    /// A `.kconfig` value at the given offset of the `.kconfig` map.
+    Kconfig { offset: usize, ty: KconfigType },
+
+    /// A `__ksym` variable; typeless ones (`extern const void sym __ksym`) resolve to
+    /// the symbol's address, typed ones to their BTF id in the kernel.
+    Ksym { typed: bool },
+
+    /// A kernel function callable from programs (kfunc).
+    Kfunc,
+}
+
+/// A symbol an object declares `extern`, to be resolved when loading.
+#[derive(Clone, Debug)]
+pub struct Extern {
+    pub name: String,
+
+    /// Weak externs that can't be resolved are 0 rather than failing the load.
+    pub weak: bool,
+
+    pub kind: ExternKind,
+}
+
+impl Extern {
+    /// Collects the externs an object declares, from its symbol table and the `.kconfig`
+    /// and `.ksyms` data sections of its BTF. Also returns the size of the `.kconfig`
+    /// map, 0 if the object declares no kconfig values.
+    pub fn collect(
+        btf: Option<&BtfTypes>,
+        symbols: &[Symbol],
+    ) -> Result<(Vec<Extern>, usize), Error> {
+        let mut externs = vec![];
+        let mut kconfig_size = 0usize;
+
+        for sym in symbols.iter() {
+            if sym.shndx != SHN_UNDEF || sym.name.is_empty() {
+                continue;
+            }
+
+            let btf = btf.ok_or_else(|| {
+                Error::MalformedObject(format!("extern {} without BTF", sym.name))
+            })?;
+            let (section, type_id) = Self::find_btf_decl(btf, &sym.name)?;
+
+            let kind = match (section, btf.get(type_id)?.kind) {
+                (KCONFIG_SECTION, BTF_KIND_VAR) => {
+                    let ty = Self::kconfig_type(btf, type_id, &sym.name)?;
+                    let offset = kconfig_size.next_multiple_of(ty.align());
+                    kconfig_size = offset + ty.size();
+                    ExternKind::Kconfig { offset, ty }
+                }
+                (KSYMS_SECTION, BTF_KIND_VAR) => {
+                    let var_type = btf.skip_mods_and_typedefs(btf.get(type_id)?.size_or_type)?;
+                    ExternKind::Ksym {
+                        typed: var_type != 0,
+                    }
+                }
+                (KSYMS_SECTION, BTF_KIND_FUNC) => ExternKind::Kfunc,
+                _ => {
+                    return Err(Error::MalformedObject(format!(
+                        "unsupported extern {} in {}",
+                        sym.name, section
+                    )))
+                }
+            };
+
+            externs.push(Extern {
+                name: sym.name.clone(),
+                weak: sym.bind() == STB_WEAK,
+                kind,
+            });
+        }
+
+        Ok((externs, kconfig_size))
+    }
+
+    /// Rewrites the `.kconfig` and `.ksyms` data sections of an object's BTF into a form
+    /// the kernel accepts, as libbpf does: extern linkage isn't allowed, nor are
+    /// functions in data sections, so kconfig values become allocated variables laid
+    /// out like the `.kconfig` map, and ksyms become `int` variables.
+    pub fn sanitize_btf(
+        btf: &mut BtfTypes,
+        externs: &[Extern],
+        kconfig_size: usize,
+    ) -> Result<(), Error> {
+        for id in 1..btf.type_count() {
+            let t = btf.get(id)?;
+            if t.kind != BTF_KIND_DATASEC {
+                continue;
+            }
+
+            let section = btf.string(t.name_off).to_string();
+            let mut secinfos = t.secinfos.clone();
+            match section.as_str() {
+                KCONFIG_SECTION => {
+                    for secinfo in secinfos.iter_mut() {
+                        let name = btf.name(secinfo.type_id)?;
+                        let (offset, ty) = externs
+                            .iter()
+                            .find_map(|e| match e.kind {
+                                ExternKind::Kconfig { offset, ty } if e.name == name => {
+                                    Some((offset, ty))
+                                }
+                                _ => None,
+                            })
+                            .ok_or_else(|| Error::SymbolNotFound(name.to_string()))?;
+
+                        let var_type = btf.get(secinfo.type_id)?.size_or_type;
+                        btf.set_var(secinfo.type_id, var_type, BTF_VAR_GLOBAL_ALLOCATED)?;
+                        secinfo.offset = offset as u32;
+                        secinfo.size = ty.size() as u32;
+                    }
+
+                    /* the kernel wants variables in the order they're laid out */
+                    secinfos.sort_by_key(|secinfo| secinfo.offset);
+
+                    btf.set_datasec_entries(id, kconfig_size as u32, &secinfos)?;
+                }
+                KSYMS_SECTION => {
+                    let int_id = (1..btf.type_count())
+                        .find(|id| {
+                            btf.get(*id)
+                                .is_ok_and(|t| t.kind == BTF_KIND_INT && t.size_or_type == 4)
+                        })
+                        .ok_or_else(|| {
+                            Error::MalformedObject("ksyms without an int type".into())
+                        })?;
+
+                    for (i, secinfo) in secinfos.iter_mut().enumerate() {
+                        let t = btf.get(secinfo.type_id)?;
+                        if t.kind == BTF_KIND_FUNC {
+                            let name_off = t.name_off;
+                            btf.set_func_linkage(secinfo.type_id, BTF_FUNC_STATIC)?;
+                            secinfo.type_id =
+                                btf.add_var(name_off, int_id, BTF_VAR_GLOBAL_ALLOCATED);
+                        } else {
+                            btf.set_var(secinfo.type_id, int_id, BTF_VAR_GLOBAL_ALLOCATED)?;
+                        }
+
+                        secinfo.offset = i as u32 * 4;
+                        secinfo.size = 4;
+                    }
+
+                    btf.set_datasec_entries(id, secinfos.len() as u32 * 4, &secinfos)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the data section and the type id of an extern's BTF declaration.
+    fn find_btf_decl(btf: &BtfTypes, name: &str) -> Result<(&'static str, u32), Error> {
+        for id in 1..btf.type_count() {
+            let t = btf.get(id)?;
+            if t.kind != BTF_KIND_DATASEC {
+                continue;
+            }
+
+            let section = match btf.string(t.name_off) {
+                KCONFIG_SECTION => KCONFIG_SECTION,
+                KSYMS_SECTION => KSYMS_SECTION,
+                _ => continue,
+            };
+
+            for secinfo in t.secinfos.iter() {
+                if btf.name(secinfo.type_id)? == name {
+                    return Ok((section, secinfo.type_id));
+                }
+            }
+        }
+
+        Err(Error::SymbolNotFound(format!(
+            "BTF declaration of extern {}",
+            name
+        )))
+    }
+
+    fn kconfig_type(btf: &BtfTypes, var_id: u32, name: &str) -> Result<KconfigType, Error> {
+        let unsupported =
+            || Error::MalformedObject(format!("unsupported type of kconfig {}", name));
+
+        let id = btf.skip_mods_and_typedefs(btf.get(var_id)?.size_or_type)?;
+        let t = btf.get(id)?;
+        match t.kind {
+            BTF_KIND_INT => {
+                let encoding = t.extra >> 24;
+                let size = t.size_or_type as usize;
+                Ok(if encoding & BTF_INT_BOOL != 0 {
+                    KconfigType::Bool
+                } else if encoding & BTF_INT_CHAR != 0 && size == 1 {
+                    KconfigType::Char
+                } else if [1, 2, 4, 8].contains(&size) {
+                    KconfigType::Int {
+                        size,
+                        signed: encoding & BTF_INT_SIGNED != 0,
+                    }
+                } else {
+                    return Err(unsupported());
+                })
+            }
+            BTF_KIND_ENUM if btf.string(t.name_off) == "libbpf_tristate" => {
+                Ok(KconfigType::Tristate)
+            }
+            BTF_KIND_ARRAY => {
+                let elem = btf.get(btf.skip_mods_and_typedefs(t.elem_type)?)?;
+                if elem.kind == BTF_KIND_INT && elem.size_or_type == 1 {
+                    Ok(KconfigType::Str(t.extra as usize))
+                } else {
+                    Err(unsupported())
+                }
+            }
+            _ => Err(unsupported()),
+        }
+    }
+}
+
+/// The running kernel's configuration, as `CONFIG_*` names mapped to their raw
+/// values, e.g.: `y`, `m`, `100` or `"string"`. Options that aren't set map to `n`.
+pub struct Kconfig {
+    values: HashMap<String, String>,
+}
+
+impl Kconfig {
+    /// Reads the configuration from /proc/config.gz, or failing that, from
+    /// /boot/config-<release>.
+    pub fn read() -> Result<Self, Error> {
+        let text = match std::fs::File::open("/proc/config.gz") {
+            Ok(file) => {
+                let mut text = String::new();
+                GzDecoder::new(file).read_to_string(&mut text)?;
+                text
+            }
+            Err(_) => std::fs::read_to_string(format!("/boot/config-{}", kernel_release()?))?,
+        };
+
+        Ok(Self::parse(&text))
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut values = HashMap::new();
+        for line in text.lines() {
+            if let Some((name, value)) = line.split_once('=') {
+                values.insert(name.to_string(), value.to_string());
+            } else if let Some(name) = line
+                .strip_prefix("# ")
+                .and_then(|l| l.strip_suffix(" is not set"))
+            {
+                values.insert(name.to_string(), "n".to_string());
+            }
+        }
+
+        Self { values }
+    }
+
+    /// Returns the raw value of a configuration option.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| v.as_str())
+    }
+}
+
+fn kernel_release() -> Result<String, Error> {
+    Ok(std::fs::read_to_string("/proc/sys/kernel/osrelease")?
+        .trim()
+        .to_string())
+}
+
+/// The running kernel's version as `KERNEL_VERSION(a, b, c)` computes it.
+pub(crate) fn kernel_version() -> Result<u32, Error> {
+    let release = kernel_release()?;
+    let mut parts = release.split(['.', '-', '+']).map(|p| {
+        let digits = p.find(|c: char| !c.is_ascii_digit()).unwrap_or(p.len());
+        p[..digits].parse::<u32>().unwrap_or(0)
+    });
+
+    let mut next = || parts.next().unwrap_or(0);
+    let (major, minor, patch) = (next(), next(), next());
+    Ok(major << 16 | minor << 8 | patch.min(255))
+}
+
+/// Fills in the values of an object's `.kconfig` externs.
+///
+/// # Arguments
+///
+/// * `externs` - The object's externs.
+/// * `data` - The contents of the `.kconfig` map.
+pub fn fill_kconfig(externs: &[Extern], data: &mut [u8]) -> Result<(), Error> {
+    let needs_config = externs
+        .iter()
+        .any(|e| matches!(e.kind, ExternKind::Kconfig { .. }) && e.name.starts_with("CONFIG_"));
+
+    /* weak externs don't need the configuration to be available */
+    let config = if needs_config {
+        Kconfig::read().ok()
+    } else {
+        None
+    };
+
+    for ext in externs.iter() {
+        let ExternKind::Kconfig { offset, ty } = ext.kind else {
+            continue;
+        };
+
+        let value = match ext.name.as_str() {
+            "LINUX_KERNEL_VERSION" => Some(kernel_version()?.to_string()),
+            name => config
+                .as_ref()
+                .and_then(|config| config.get(name))
+                .map(|v| v.to_string()),
+        };
+
+        match value {
+            Some(value) => set_kconfig_value(&mut data[offset..offset + ty.size()], ty, &value)
+                .map_err(|e| Error::MalformedObject(format!("kconfig {}: {}", ext.name, e)))?,
+            None if ext.weak => {}
+            None => return Err(Error::SymbolNotFound(ext.name.clone())),
+        }
+    }
+
+    Ok(())
+}
+
+fn set_kconfig_value(buf: &mut [u8], ty: KconfigType, value: &str) -> Result<(), String> {
+    let tristate = |y: u32, n: u32, m: u32| match value {
+        "y" => Ok(y),
+        "n" => Ok(n),
+        "m" => Ok(m),
+        _ => Err(format!("{} isn't a tristate", value)),
+    };
+
+    match ty {
+        KconfigType::Bool if value == "m" => return Err("m isn't a bool".to_string()),
+        KconfigType::Bool => buf[0] = tristate(1, 0, 1)? as u8,
+        KconfigType::Char => buf[0] = tristate(b'y'.into(), b'n'.into(), b'm'.into())? as u8,
+        KconfigType::Tristate => buf.copy_from_slice(&tristate(1, 0, 2)?.to_ne_bytes()),
+        KconfigType::Int { size, signed } => {
+            let parsed = match value.strip_prefix("0x") {
+                Some(hex) => i128::from_str_radix(hex, 16),
+                None => value.parse::<i128>(),
+            }
+            .map_err(|_| format!("{} isn't a number", value))?;
+
+            let bits = size as u32 * 8;
+            let fits = if signed {
+                parsed >= -(1i128 << (bits - 1)) && parsed < 1i128 << (bits - 1)
+            } else {
+                parsed >= 0 && parsed < 1i128 << bits
+            };
+            if !fits {
+                return Err(format!("{} doesn't fit in {} bytes", value, size));
+            }
+
+            buf.copy_from_slice(&(parsed as u64).to_ne_bytes()[..size]);
+        }
+        KconfigType::Str(len) => {
+            let s = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .ok_or_else(|| format!("{} isn't a string", value))?;
+
+            /* truncated to leave room for the nul terminator */
+            let n = s.len().min(len.saturating_sub(1));
+            buf[..n].copy_from_slice(&s.as_bytes()[..n]);
+        }
+    }
+
+    Ok(())
+}
+
+/// What a `__ksym` extern resolved to.
+#[derive(Copy, Clone, Debug)]
+pub enum KsymValue {
+    /// The address of a typeless ksym.
+    Address(u64),
+
+    /// The BTF id of a typed ksym or a kfunc, in the BTF of vmlinux (fd 0) or of a
+    /// module. `fd_index` is the module BTF's index in the program's `fd_array`.
+    BtfId { id: u32, btf_fd: u32, fd_index: u16 },
+
+    /// A weak ksym that doesn't exist in the running kernel.
+    Missing,
+}
+
+/// The resolved ksyms of an object, along with the module BTF they refer to, which
+/// has to stay open until the programs are loaded.
+pub struct Ksyms {
+    /// The value of each extern, by the extern's index; `None` for kconfig externs.
+    pub values: Vec<Option<KsymValue>>,
+
+    /// The module BTF fds for the programs' `fd_array`, behind an unused entry for
+    /// vmlinux.
+    pub fd_array: Vec<u32>,
+
+    _modules: Vec<Btf>,
+}
+
+impl Ksyms {
+    /// Resolves the ksyms among an object's externs: typeless ones through
+    /// /proc/kallsyms, typed ones and kfuncs through the BTF of vmlinux and, failing
+    /// that, of the loaded kernel modules.
+    ///
+    /// # Arguments
+    ///
+    /// * `externs` - The object's externs.
+    /// * `vmlinux` - The kernel's BTF; required if there are typed ksyms or kfuncs.
+    pub fn resolve(externs: &[Extern], vmlinux: Option<&BtfTypes>) -> Result<Self, Error> {
+        let needs_kallsyms = externs
+            .iter()
+            .any(|e| matches!(e.kind, ExternKind::Ksym { typed: false }));
+        let kallsyms = if needs_kallsyms {
+            kallsyms()?
+        } else {
+            HashMap::new()
+        };
+
+        let mut modules: Option<Vec<(Btf, BtfTypes)>> = None;
+        let mut used_modules: Vec<usize> = vec![];
+        let mut values = vec![];
+
+        for ext in externs.iter() {
+            let kind = match ext.kind {
+                ExternKind::Kconfig { .. } => {
+                    values.push(None);
+                    continue;
+                }
+                ExternKind::Ksym { typed: false } => {
+                    values.push(Some(match kallsyms.get(&ext.name) {
+                        Some(addr) => KsymValue::Address(*addr),
+                        None if ext.weak => KsymValue::Missing,
+                        None => return Err(Error::SymbolNotFound(ext.name.clone())),
+                    }));
+                    continue;
+                }
+                ExternKind::Ksym { typed: true } => BTF_KIND_VAR,
+                ExternKind::Kfunc => BTF_KIND_FUNC,
+            };
+
+            let vmlinux = vmlinux.ok_or_else(|| Error::SymbolNotFound("vmlinux BTF".into()))?;
+            if let Some(id) = vmlinux.find_by_name_kind(&ext.name, kind) {
+                values.push(Some(KsymValue::BtfId {
+                    id,
+                    btf_fd: 0,
+                    fd_index: 0,
+                }));
+                continue;
+            }
+
+            /* module BTF is only read when vmlinux doesn't have the symbol */
+            let modules = match &mut modules {
+                Some(modules) => modules,
+                None => modules.insert(Self::module_btf(vmlinux)?),
+            };
+
+            let found = modules
+                .iter()
+                .enumerate()
+                .find_map(|(index, (btf, types))| {
+                    types
+                        .find_by_name_kind(&ext.name, kind)
+                        .map(|id| (index, btf.get_identifier(), id))
+                });
+
+            values.push(Some(match found {
+                Some((index, btf_fd, id)) => {
+                    let position = match used_modules.iter().position(|m| *m == index) {
+                        Some(position) => position,
+                        None => {
+                            used_modules.push(index);
+                            used_modules.len() - 1
+                        }
+                    };
+
+                    KsymValue::BtfId {
+                        id,
+                        btf_fd,
+                        fd_index: (position + 1).try_into()?,
+                    }
+                }
+                None if ext.weak => KsymValue::Missing,
+                None => return Err(Error::SymbolNotFound(ext.name.clone())),
+            }));
+        }
+
+        /* keep only the module BTF that's referenced */
+        let mut modules: Vec<Option<Btf>> = modules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(btf, _)| Some(btf))
+            .collect();
+        let used: Vec<Btf> = used_modules
+            .iter()
+            .filter_map(|index| modules[*index].take())
+            .collect();
+
+        let fd_array = if used.is_empty() {
+            vec![]
+        } else {
+            std::iter::once(0)
+                .chain(used.iter().map(|btf| btf.get_identifier()))
+                .collect()
+        };
+
+        Ok(Self {
+            values,
+            fd_array,
+            _modules: used,
+        })
+    }
+
+    /// Patches a reference to a ksym: a ld_imm64 gets the symbol's address, or its BTF
+    /// id and the fd of the BTF it's in; a call gets the kfunc's BTF id and the index of
+    /// its BTF in `fd_array`. Missing weak ksyms load as 0 or call a null kfunc, which
+    /// the verifier only accepts in dead code.
+    ///
+    /// # Arguments
+    ///
+    /// * `instructions` - The program's instructions.
+    /// * `insn_index` - The index of the referencing instruction.
+    /// * `extern_index` - The index of the referenced extern.
+    pub fn apply(
+        &self,
+        instructions: &mut [u64],
+        insn_index: usize,
+        extern_index: usize,
+    ) -> Result<(), Error> {
+        let value = self
+            .values
+            .get(extern_index)
+            .copied()
+            .flatten()
+            .ok_or_else(|| Error::MalformedObject(format!("bad ksym relocation {}", insn_index)))?;
+
+        let truncated = || Error::MalformedObject(format!("truncated ld_imm64 at {}", insn_index));
+        let insn = *instructions.get(insn_index).ok_or_else(truncated)?;
+
+        /* keep the opcode and dst_reg; src_reg, off and imm are replaced */
+        let (src, off, imm, next_imm) = match (insn as u8, value) {
+            (BPF_LD_IMM64, KsymValue::Address(addr)) => (0, 0, addr as u32, (addr >> 32) as u32),
+            (BPF_LD_IMM64, KsymValue::BtfId { id, btf_fd, .. }) => {
+                (BPF_PSEUDO_BTF_ID, 0, id, btf_fd)
+            }
+            (BPF_LD_IMM64, KsymValue::Missing) => (0, 0, 0, 0),
+            (BPF_CALL, KsymValue::BtfId { id, fd_index, .. }) => {
+                (BPF_PSEUDO_KFUNC_CALL, fd_index, id, 0)
+            }
+            (BPF_CALL, KsymValue::Missing) => (BPF_PSEUDO_KFUNC_CALL, 0, 0, 0),
+            _ => {
+                return Err(Error::MalformedObject(format!(
+                    "bad ksym reference at instruction {}",
+                    insn_index
+                )))
+            }
+        };
+
+        instructions[insn_index] =
+            (insn & 0x0fff) | src << 12 | (off as u64) << 16 | (imm as u64) << 32;
+        if insn as u8 == BPF_LD_IMM64 {
+            let next = instructions.get_mut(insn_index + 1).ok_or_else(truncated)?;
+            *next = (*next & 0xffffffff) | (next_imm as u64) << 32;
+        }
+
+        Ok(())
+    }
+
+    /// Opens the BTF of every loaded kernel module, parsed as split BTF on top of
+    /// vmlinux's.
+    fn module_btf(vmlinux: &BtfTypes) -> Result<Vec<(Btf, BtfTypes)>, Error> {
+        let mut modules = vec![];
+        for id in btf_ids() {
+            let btf = match Btf::from_id(id?) {
+                Ok(btf) => btf,
+                Err(_) => continue,
+            };
+
+            let name = btf.name()?;
+            if name.is_empty() || name == "vmlinux" {
+                continue;
+            }
+
+            if let Ok(data) = std::fs::read(format!("/sys/kernel/btf/{}", name)) {
+                let types = BtfTypes::parse_split(&data, vmlinux)?;
+                modules.push((btf, types));
+            }
+        }
+
+        Ok(modules)
+    }
+}
+
+/// Reads the addresses of kernel symbols from /proc/kallsyms. Addresses read as 0
+/// without CAP_SYSLOG, depending on kptr_restrict.
+fn kallsyms() -> Result<HashMap<String, u64>, Error> {
+    let text = std::fs::read_to_string("/proc/kallsyms")?;
+    let mut symbols = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        if let (Some(addr), Some(_), Some(name)) = (fields.next(), fields.next(), fields.next()) {
+            if let Ok(addr) = u64::from_str_radix(addr, 16) {
+                symbols.entry(name.to_string()).or_insert(addr);
+            }
+        }
+    }
+
+    Ok(symbols)
+}
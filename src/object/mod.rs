@@ -0,0 +1,1437 @@
+//! A loader for BPF objects compiled by clang, e.g.: `clang -target bpf -O2 -g -c prog.c`.
+//!
+//! Programs are discovered by the section they live in, using the same section names
+//! as libbpf (`kprobe/...`, `tracepoint/...`, `xdp`, `tc`, etc.). Maps are created from
+//! the BTF-defined `.maps` section or the legacy `maps` section, and references to them
+//! are patched into the programs' instructions before loading.
+//!
+//! Functions called from a program (BPF-to-BPF calls), whether in `.text` or another
+//! program's section, are appended to the program's instructions and the calls are
+//! pointed at them. A subprogram called from several programs is copied into each.
+//!
+//! Programs compiled with CO-RE relocations (e.g.: using `BPF_CORE_READ`) are adjusted
+//! to the running kernel's type layouts, from `/sys/kernel/btf/vmlinux`, before loading.
+//!
+//! `extern` declarations are resolved against the running kernel: `__kconfig` values
+//! from /proc/config.gz (or /boot/config-<release>) land in a read-only `.kconfig`
+//! map, `__ksym` variables resolve through /proc/kallsyms or the kernel's BTF, and
+//! kfunc calls through the BTF of vmlinux or of the module defining them. Weak externs
+//! that can't be resolved are 0.
+//!
+//! Global variables in `.data`, `.rodata` and `.bss` are backed by single-entry,
+//! mmapable array maps, like libbpf does. They can be read and changed by name before
+//! loading with `Object::global`/`Object::set_global`, and afterwards through the
+//! mapping with `LoadedObject::global`/`LoadedObject::set_global`. `.rodata` is frozen
+//! on load, so it's read-only for both user space and programs from then on. Values
+//! are copied in and out as types implementing `Pod`, since their bytes come from the
+//! object or from memory programs write to.
+//!
+//! ## Usage
+//! ```no_run
+//! use bpf_api::object::Object;
+//! use bpf_api::probes::{AttachInfo, Probe};
+//!
+//! let mut object = Object::from_file("probe.bpf.o").unwrap();
+//! object.set_global("target_pid", &1234u32).unwrap();
+//! let mut loaded = object.load().unwrap();
+//!
+//! let program = loaded.program("trace_exec").unwrap();
+//! let mut probe = Probe::create(AttachInfo::RawTracepoint("sched_process_exec".into()));
+//! probe.attach(program).unwrap();
+//!
+//! let counts = loaded.map::<u32, u64>("counts").unwrap();
+//! loaded.set_global("events_seen", &0u64).unwrap();
+//! ```
+
+mod btf_ext;
+mod core_relo;
+mod elf;
+mod externs;
+
+use crate::btf::{
+    Btf, BtfTypes, FuncInfo, LineInfo, BTF_KIND_ARRAY, BTF_KIND_DATASEC, BTF_KIND_PTR,
+    BTF_KIND_STRUCT,
+};
+use crate::error::Error;
+use crate::platform::{map_flags, AttachType, Map, MapDefinition, MapType, RawMap};
+use crate::platform::{Program, ProgramAttr, ProgramBtf, ProgramType};
+
+use btf_ext::BtfExt;
+use elf::{
+    Section, EM_BPF, SHF_EXECINSTR, SHN_UNDEF, SHT_REL, STB_GLOBAL, STT_FUNC, STT_OBJECT,
+    STT_SECTION,
+};
+use externs::{Extern, ExternKind, Ksyms};
+
+pub(crate) use btf_ext::CoreRelo;
+pub(crate) use core_relo::CoreRelocator;
+pub(crate) use elf::Elf;
+pub(crate) use externs::kernel_version;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::collections::HashMap;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+
+const LEGACY_MAPS_SECTION: &str = "maps";
+const BTF_MAPS_SECTION: &str = ".maps";
+
+const R_BPF_64_64: u32 = 1;
+const R_BPF_64_32: u32 = 10;
+
+const BPF_LD_IMM64: u8 = 0x18;
+const BPF_PSEUDO_MAP_FD: u64 = 1;
+const BPF_PSEUDO_MAP_VALUE: u64 = 2;
+const BPF_CALL: u8 = 0x85;
+const BPF_PSEUDO_CALL: u64 = 1;
+const BPF_PSEUDO_FUNC: u64 = 4;
+
+const KCONFIG_SECTION: &str = ".kconfig";
+const LICENSE_SECTION: &str = "license";
+const VERSION_SECTION: &str = "version";
+
+/// Sections holding global variables; `.rodata.*` also covers string literals, e.g.:
+/// `.rodata.str1.1`.
+const GLOBAL_SECTIONS: &[&str] = &[".data", ".rodata", ".bss"];
+
+fn is_global_section(name: &str) -> bool {
+    GLOBAL_SECTIONS
+        .iter()
+        .any(|s| name == *s || (name.starts_with(s) && name[s.len()..].starts_with('.')))
+}
+
+/// How the BTF id a program attaches to is looked up in the kernel's BTF.
+#[derive(Copy, Clone, Debug)]
+enum BtfTarget {
+    /// A function of the given name, e.g.: for fentry/fexit.
+    Func,
+
+    /// A function with a prefix, e.g.: `bpf_lsm_` for LSM hooks.
+    PrefixedFunc(&'static str),
+
+    /// The `btf_trace_<name>` typedef of a raw tracepoint.
+    RawTracepoint,
+}
+
+/// Maps a section name (prefix) to the program type and attach type it implies.
+#[derive(Debug)]
+struct SectionDef {
+    prefix: &'static str,
+    prog_type: ProgramType,
+    attach_type: Option<AttachType>,
+    btf_target: Option<BtfTarget>,
+}
+
+const fn def(
+    prefix: &'static str,
+    prog_type: ProgramType,
+    attach_type: Option<AttachType>,
+) -> SectionDef {
+    SectionDef {
+        prefix,
+        prog_type,
+        attach_type,
+        btf_target: None,
+    }
+}
+
+const fn btf_def(
+    prefix: &'static str,
+    prog_type: ProgramType,
+    attach_type: AttachType,
+    btf_target: BtfTarget,
+) -> SectionDef {
+    SectionDef {
+        prefix,
+        prog_type,
+        attach_type: Some(attach_type),
+        btf_target: Some(btf_target),
+    }
+}
+
+const SECTION_DEFS: &[SectionDef] = {
+    use AttachType as A;
+    use ProgramType as P;
+    &[
+        def("socket", P::SocketFilter, None),
+        def(
+            "sk_reuseport/migrate",
+            P::SkReuseport,
+            Some(A::SkReuseportSelectOrMigrate),
+        ),
+        def("sk_reuseport", P::SkReuseport, Some(A::SkReuseportSelect)),
+        def("kprobe.multi", P::KProbe, Some(A::TraceKprobeMulti)),
+        def("kretprobe.multi", P::KProbe, Some(A::TraceKprobeMulti)),
+        def("kprobe", P::KProbe, None),
+        def("kretprobe", P::KProbe, None),
+        def("uprobe", P::KProbe, None),
+        def("uretprobe", P::KProbe, None),
+        def("ksyscall", P::KProbe, None),
+        def("kretsyscall", P::KProbe, None),
+        def("tc", P::SchedCls, None),
+        def("classifier", P::SchedCls, None),
+        def("action", P::SchedAct, None),
+        def("tracepoint", P::Tracepoint, None),
+        def("tp", P::Tracepoint, None),
+        def("raw_tracepoint", P::RawTracepoint, None),
+        def("raw_tp", P::RawTracepoint, None),
+        def("raw_tracepoint.w", P::RawTracepointWritable, None),
+        def("raw_tp.w", P::RawTracepointWritable, None),
+        btf_def(
+            "tp_btf",
+            P::Tracing,
+            A::TraceRawTp,
+            BtfTarget::RawTracepoint,
+        ),
+        btf_def("fentry", P::Tracing, A::TraceFentry, BtfTarget::Func),
+        btf_def("fmod_ret", P::Tracing, A::ModifyReturn, BtfTarget::Func),
+        btf_def("fexit", P::Tracing, A::TraceFexit, BtfTarget::Func),
+        btf_def(
+            "lsm",
+            P::Lsm,
+            A::LsmMac,
+            BtfTarget::PrefixedFunc("bpf_lsm_"),
+        ),
+        btf_def(
+            "iter",
+            P::Tracing,
+            A::TraceIter,
+            BtfTarget::PrefixedFunc("bpf_iter_"),
+        ),
+        def("syscall", P::Syscall, None),
+        def("xdp/devmap", P::Xdp, Some(A::XdpDevmap)),
+        def("xdp/cpumap", P::Xdp, Some(A::XdpCpumap)),
+        def("xdp", P::Xdp, None),
+        def("perf_event", P::PerfEvent, None),
+        def("lwt_in", P::LwtIn, None),
+        def("lwt_out", P::LwtOut, None),
+        def("lwt_xmit", P::LwtXmit, None),
+        def("lwt_seg6local", P::LwtSeg6local, None),
+        def("sockops", P::SockOps, Some(A::CgroupSockOps)),
+        def("sk_skb/stream_parser", P::SkSkb, Some(A::SkSkbStreamParser)),
+        def(
+            "sk_skb/stream_verdict",
+            P::SkSkb,
+            Some(A::SkSkbStreamVerdict),
+        ),
+        def("sk_skb", P::SkSkb, None),
+        def("sk_msg", P::SkMsg, Some(A::SkMsgVerdict)),
+        def("lirc_mode2", P::LircMode2, Some(A::LircMode2)),
+        def("flow_dissector", P::FlowDissector, Some(A::FlowDissector)),
+        def(
+            "cgroup_skb/ingress",
+            P::CgroupSkb,
+            Some(A::CgroupInetIngress),
+        ),
+        def("cgroup_skb/egress", P::CgroupSkb, Some(A::CgroupInetEgress)),
+        def("cgroup/skb", P::CgroupSkb, None),
+        def(
+            "cgroup/sock_create",
+            P::CgroupSock,
+            Some(A::CgroupInetSockCreate),
+        ),
+        def(
+            "cgroup/sock_release",
+            P::CgroupSock,
+            Some(A::CgroupInetSockRelease),
+        ),
+        def("cgroup/sock", P::CgroupSock, Some(A::CgroupInetSockCreate)),
+        def(
+            "cgroup/post_bind4",
+            P::CgroupSock,
+            Some(A::CgroupInet4PostBind),
+        ),
+        def(
+            "cgroup/post_bind6",
+            P::CgroupSock,
+            Some(A::CgroupInet6PostBind),
+        ),
+        def("cgroup/bind4", P::CgroupSockAddr, Some(A::CgroupInet4Bind)),
+        def("cgroup/bind6", P::CgroupSockAddr, Some(A::CgroupInet6Bind)),
+        def(
+            "cgroup/connect4",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet4Connect),
+        ),
+        def(
+            "cgroup/connect6",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet6Connect),
+        ),
+        def(
+            "cgroup/sendmsg4",
+            P::CgroupSockAddr,
+            Some(A::CgroupUdp4Sendmsg),
+        ),
+        def(
+            "cgroup/sendmsg6",
+            P::CgroupSockAddr,
+            Some(A::CgroupUdp6Sendmsg),
+        ),
+        def(
+            "cgroup/recvmsg4",
+            P::CgroupSockAddr,
+            Some(A::CgroupUdp4Recvmsg),
+        ),
+        def(
+            "cgroup/recvmsg6",
+            P::CgroupSockAddr,
+            Some(A::CgroupUdp6Recvmsg),
+        ),
+        def(
+            "cgroup/getpeername4",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet4Getpeername),
+        ),
+        def(
+            "cgroup/getpeername6",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet6Getpeername),
+        ),
+        def(
+            "cgroup/getsockname4",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet4Getsockname),
+        ),
+        def(
+            "cgroup/getsockname6",
+            P::CgroupSockAddr,
+            Some(A::CgroupInet6Getsockname),
+        ),
+        def("cgroup/sysctl", P::CgroupSysctl, Some(A::CgroupSysctl)),
+        def(
+            "cgroup/getsockopt",
+            P::CgroupSockopt,
+            Some(A::CgroupGetsockopt),
+        ),
+        def(
+            "cgroup/setsockopt",
+            P::CgroupSockopt,
+            Some(A::CgroupSetsockopt),
+        ),
+        def("cgroup/dev", P::CgroupDevice, Some(A::CgroupDevice)),
+        def("sk_lookup", P::SkLookup, Some(A::SkLookup)),
+    ]
+};
+
+fn find_section_def(section: &str) -> Option<&'static SectionDef> {
+    SECTION_DEFS.iter().find(|def| {
+        section == def.prefix
+            || (section.starts_with(def.prefix) && section[def.prefix.len()..].starts_with('/'))
+    })
+}
+
+/// A map reference within a program that has to be patched with the map's fd, or
+/// for global variables, with the map's fd and the variable's offset in the value.
+#[derive(Clone, Debug)]
+struct MapRelocation {
+    insn_index: usize,
+    map_index: usize,
+    value_offset: Option<u32>,
+}
+
+/// A reference to a `__ksym` extern: a ld_imm64 of a kernel variable or a call to a
+/// kernel function.
+#[derive(Clone, Debug)]
+struct KsymRelocation {
+    insn_index: usize,
+    extern_index: usize,
+}
+
+/// A reference from one function to another: a BPF-to-BPF call, or a function
+/// pointer loaded with ld_imm64, e.g.: the callback of `bpf_loop`.
+#[derive(Clone, Debug)]
+struct Call {
+    insn_index: usize,
+
+    /// The section of the target and the index of the target instruction in it.
+    section_index: usize,
+    target_index: usize,
+
+    is_pointer: bool,
+}
+
+/// A function in an executable section: either an entry program or a subprogram.
+#[derive(Clone, Debug)]
+struct Function {
+    name: String,
+    section: String,
+    section_index: usize,
+
+    /// The index of the function's first instruction in its section.
+    start: usize,
+
+    /// The section definition for global functions in program sections.
+    entry: Option<&'static SectionDef>,
+
+    instructions: Vec<u64>,
+    map_relocations: Vec<MapRelocation>,
+    ksym_relocations: Vec<KsymRelocation>,
+    calls: Vec<Call>,
+    func_info: Vec<FuncInfo>,
+    line_info: Vec<LineInfo>,
+    core_relos: Vec<(usize, CoreRelo)>,
+}
+
+fn is_pseudo_call(insn: u64) -> bool {
+    insn as u8 == BPF_CALL && (insn >> 12) & 0xf == BPF_PSEUDO_CALL
+}
+
+/// A section of global variables and the internal map backing it.
+#[derive(Clone, Debug)]
+struct GlobalSection {
+    name: String,
+    section_index: usize,
+    map_index: usize,
+    read_only: bool,
+
+    /// The initial contents, zeroes for `.bss`.
+    data: Vec<u8>,
+
+    /// The offset and size of each variable by name.
+    variables: HashMap<String, (usize, usize)>,
+}
+
+/// Finds a global variable, or a whole global section by its name, returning the
+/// index of its section and its offset. `T` has to be the variable's size, or for a
+/// section, no larger than the section.
+fn find_global<T>(globals: &[GlobalSection], name: &str) -> Result<(usize, usize), Error> {
+    for (index, global) in globals.iter().enumerate() {
+        let offset = if global.name == name {
+            0
+        } else {
+            match global.variables.get(name) {
+                Some((offset, size)) if *size == size_of::<T>() => *offset,
+                Some(_) => return Err(Error::InvalidArgument),
+                None => continue,
+            }
+        };
+
+        /* never past the map's value, whatever the symbol table says */
+        match offset.checked_add(size_of::<T>()) {
+            Some(end) if end <= global.data.len() => return Ok((index, offset)),
+            _ => return Err(Error::OutOfRange),
+        }
+    }
+
+    Err(Error::SymbolNotFound(name.to_string()))
+}
+
+/// Types that can be made from any bytes of the right size and have no padding, so
+/// they can be copied to and from the memory of global sections.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value of the type, which rules out e.g.: `bool`,
+/// `char`, enums and references, and the type must have no padding bytes. Structs
+/// should be `#[repr(C)]` with fields that are `Pod` themselves.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A .BTF.ext record that refers to an instruction.
+trait InsnRecord {
+    fn insn_off(&self) -> u32;
+    fn with_insn_off(self, insn_off: u32) -> Self;
+}
+
+impl InsnRecord for FuncInfo {
+    fn insn_off(&self) -> u32 {
+        self.insn_off
+    }
+
+    fn with_insn_off(self, insn_off: u32) -> Self {
+        Self { insn_off, ..self }
+    }
+}
+
+impl InsnRecord for LineInfo {
+    fn insn_off(&self) -> u32 {
+        self.insn_off
+    }
+
+    fn with_insn_off(self, insn_off: u32) -> Self {
+        Self { insn_off, ..self }
+    }
+}
+
+/// A program found in an object file, not yet loaded.
+#[derive(Clone, Debug)]
+pub struct ObjectProgram {
+    /// The name of the program's function.
+    pub name: String,
+
+    /// The section the program was found in, e.g.: `kprobe/do_sys_open`.
+    pub section: String,
+
+    /// The program type implied by the section name.
+    pub prog_type: ProgramType,
+
+    /// The attach type implied by the section name.
+    pub expected_attach_type: Option<AttachType>,
+
+    /// The instructions of the program followed by those of the subprograms it calls,
+    /// before map and CO-RE relocations are applied.
+    pub instructions: Vec<u64>,
+
+    btf_target: Option<BtfTarget>,
+    map_relocations: Vec<MapRelocation>,
+    ksym_relocations: Vec<KsymRelocation>,
+    func_info: Vec<FuncInfo>,
+    line_info: Vec<LineInfo>,
+
+    /// CO-RE relocations and the index of the instruction they apply to.
+    core_relos: Vec<(usize, CoreRelo)>,
+}
+
+impl ObjectProgram {
+    /// The attach target encoded in the section name, e.g.: the function name for
+    /// `kprobe/<function>`; empty if the section has no target.
+    pub fn attach_target(&self) -> &str {
+        self.section.split_once('/').map(|(_, t)| t).unwrap_or("")
+    }
+}
+
+/// A parsed BPF object file.
+#[derive(Clone, Debug)]
+pub struct Object {
+    programs: Vec<ObjectProgram>,
+    maps: Vec<MapDefinition>,
+    globals: Vec<GlobalSection>,
+    externs: Vec<Extern>,
+    btf: Option<BtfTypes>,
+    license: Option<String>,
+    kern_version: Option<u32>,
+}
+
+impl Object {
+    /// Reads and parses a BPF object file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the object file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Parses a BPF object from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The contents of the object file.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let elf = Elf::parse(data)?;
+        if elf.e_machine != EM_BPF {
+            return Err(Error::MalformedObject("not a BPF object".into()));
+        }
+
+        let symbols = elf.symbols()?;
+
+        let mut btf = match elf.section_by_name(".BTF") {
+            Some(section) => Some(BtfTypes::parse(elf.section_data(section)?)?),
+            None => None,
+        };
+
+        let btf_ext = match (elf.section_by_name(".BTF.ext"), &btf) {
+            (Some(section), Some(btf)) => BtfExt::parse(elf.section_data(section)?, btf)?,
+            _ => BtfExt::default(),
+        };
+
+        if let Some(btf) = btf.as_mut() {
+            Self::fixup_datasecs(btf, &elf, &symbols)?;
+        }
+
+        /*
+         * maps are indexed by (section index, symbol name) so relocations, which
+         * reference the map's symbol, can find them.
+         */
+        let mut maps = vec![];
+        let mut map_symbols = HashMap::new();
+        if let Some(section) = elf.section_by_name(LEGACY_MAPS_SECTION) {
+            let data = elf.section_data(section)?;
+            for sym in symbols.iter().filter(|s| s.shndx as usize == section.index) {
+                maps.push(Self::parse_legacy_map(&sym.name, data, sym.value as usize)?);
+                map_symbols.insert((section.index, sym.name.clone()), maps.len() - 1);
+            }
+        }
+
+        if let Some(section) = elf.section_by_name(BTF_MAPS_SECTION) {
+            let btf = btf
+                .as_ref()
+                .ok_or_else(|| Error::MalformedObject(".maps section without BTF".into()))?;
+            for def in Self::parse_btf_maps(btf)? {
+                map_symbols.insert((section.index, def.name.clone()), maps.len());
+                maps.push(def);
+            }
+        }
+
+        let mut globals = vec![];
+        for section in elf.sections.iter() {
+            if !is_global_section(&section.name) || section.size == 0 {
+                continue;
+            }
+
+            let read_only = section.name.starts_with(".rodata");
+            let mut flags = map_flags::MMAPABLE;
+            if read_only {
+                flags |= map_flags::RDONLY_PROG;
+            }
+
+            let mut data = elf.section_data(section)?.to_vec();
+            data.resize(section.size as usize, 0);
+
+            let variables = symbols
+                .iter()
+                .filter(|s| s.shndx as usize == section.index && s.sym_type() == STT_OBJECT)
+                .map(|s| (s.name.clone(), (s.value as usize, s.size as usize)))
+                .collect();
+
+            globals.push(GlobalSection {
+                name: section.name.clone(),
+                section_index: section.index,
+                map_index: maps.len(),
+                read_only,
+                data,
+                variables,
+            });
+
+            maps.push(MapDefinition {
+                name: section.name.clone(),
+                map_type: MapType::Array,
+                key_size: 4,
+                value_size: section.size as u32,
+                max_entries: 1,
+                flags,
+            });
+        }
+
+        /*
+         * kconfig values live in a read-only map of their own, filled in when loading;
+         * it has no ELF section, like the externs in it.
+         */
+        let (externs, kconfig_size) = Extern::collect(btf.as_ref(), &symbols)?;
+        if let Some(btf) = btf.as_mut() {
+            Extern::sanitize_btf(btf, &externs, kconfig_size)?;
+        }
+
+        if kconfig_size > 0 {
+            let variables = externs
+                .iter()
+                .filter_map(|e| match e.kind {
+                    ExternKind::Kconfig { offset, ty } => {
+                        Some((e.name.clone(), (offset, ty.size())))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            globals.push(GlobalSection {
+                name: KCONFIG_SECTION.to_string(),
+                section_index: SHN_UNDEF as usize,
+                map_index: maps.len(),
+                read_only: true,
+                data: vec![0; kconfig_size],
+                variables,
+            });
+
+            maps.push(MapDefinition {
+                name: KCONFIG_SECTION.to_string(),
+                map_type: MapType::Array,
+                key_size: 4,
+                value_size: kconfig_size as u32,
+                max_entries: 1,
+                flags: map_flags::MMAPABLE | map_flags::RDONLY_PROG,
+            });
+        }
+
+        let mut functions = vec![];
+        for section in elf.sections.iter() {
+            if section.flags & SHF_EXECINSTR == 0 {
+                continue;
+            }
+
+            let data = elf.section_data(section)?;
+            let relocations = Self::section_relocations(&elf, section)?;
+
+            /* .text only holds subprograms, whatever their linkage */
+            let section_def = match section.name.as_str() {
+                ".text" => None,
+                name => find_section_def(name),
+            };
+
+            for sym in symbols
+                .iter()
+                .filter(|s| s.shndx as usize == section.index && s.sym_type() == STT_FUNC)
+            {
+                /* a program the loader wouldn't know the type of */
+                if section_def.is_none() && section.name != ".text" && sym.bind() == STB_GLOBAL {
+                    return Err(Error::MalformedObject(format!(
+                        "program {} in unrecognized section {}",
+                        sym.name, section.name
+                    )));
+                }
+
+                let out_of_bounds =
+                    || Error::MalformedObject(format!("function {} out of bounds", sym.name));
+                let start = sym.value as usize;
+                let end = start
+                    .checked_add(sym.size as usize)
+                    .ok_or_else(out_of_bounds)?;
+                let bytes = data.get(start..end).ok_or_else(out_of_bounds)?;
+
+                let mut map_relocations = vec![];
+                let mut ksym_relocations = vec![];
+                let mut calls = vec![];
+                for rel in relocations.iter() {
+                    let offset = rel.offset as usize;
+                    if offset < start || offset >= end {
+                        continue;
+                    }
+
+                    let target = symbols.get(rel.sym as usize).ok_or_else(|| {
+                        Error::MalformedObject(format!("bad relocation in {}", section.name))
+                    })?;
+
+                    let unsupported = || {
+                        Error::MalformedObject(format!(
+                            "unsupported relocation against {} in {}",
+                            target.name, section.name
+                        ))
+                    };
+
+                    let insn_index = (offset - start) / 8;
+                    let imm = bytes
+                        .get(offset - start + 4..offset - start + 8)
+                        .map(LittleEndian::read_i32)
+                        .ok_or_else(unsupported)? as i64;
+
+                    if target.shndx == SHN_UNDEF {
+                        let extern_index = externs
+                            .iter()
+                            .position(|e| e.name == target.name)
+                            .ok_or_else(unsupported)?;
+
+                        match externs[extern_index].kind {
+                            ExternKind::Kconfig { offset, .. } => {
+                                map_relocations.push(MapRelocation {
+                                    insn_index,
+                                    map_index: globals
+                                        .iter()
+                                        .find(|g| g.name == KCONFIG_SECTION)
+                                        .ok_or_else(unsupported)?
+                                        .map_index,
+                                    value_offset: Some((offset as i64 + imm).try_into()?),
+                                });
+                            }
+                            _ => ksym_relocations.push(KsymRelocation {
+                                insn_index,
+                                extern_index,
+                            }),
+                        }
+                        continue;
+                    }
+
+                    /*
+                     * references to functions in other sections, e.g.: .text, either
+                     * through the function's symbol or the section symbol with the
+                     * function's offset encoded in the immediate.
+                     */
+                    let target_section = elf.sections.get(target.shndx as usize);
+                    if target_section.is_some_and(|s| s.flags & SHF_EXECINSTR != 0) {
+                        let target_index = match rel.rel_type {
+                            R_BPF_64_32 => target.value as i64 / 8 + imm + 1,
+                            R_BPF_64_64 => (target.value as i64 + imm) / 8,
+                            _ => return Err(unsupported()),
+                        };
+
+                        calls.push(Call {
+                            insn_index,
+                            section_index: target.shndx as usize,
+                            target_index: target_index.try_into()?,
+                            is_pointer: rel.rel_type == R_BPF_64_64,
+                        });
+                        continue;
+                    }
+
+                    if rel.rel_type != R_BPF_64_64 {
+                        return Err(unsupported());
+                    }
+
+                    let key = (target.shndx as usize, target.name.clone());
+                    if let Some(map_index) = map_symbols.get(&key) {
+                        map_relocations.push(MapRelocation {
+                            insn_index,
+                            map_index: *map_index,
+                            value_offset: None,
+                        });
+                    } else if let Some(global) = globals
+                        .iter()
+                        .find(|g| g.section_index == target.shndx as usize)
+                    {
+                        /*
+                         * static variables are referenced through the section symbol with
+                         * the offset in the instruction's immediate, globals through their
+                         * own symbol.
+                         */
+                        let base = if target.sym_type() == STT_SECTION {
+                            0
+                        } else {
+                            target.value as i64
+                        };
+
+                        map_relocations.push(MapRelocation {
+                            insn_index,
+                            map_index: global.map_index,
+                            value_offset: Some((base + imm).try_into()?),
+                        });
+                    } else {
+                        return Err(unsupported());
+                    }
+                }
+
+                let instructions: Vec<u64> =
+                    bytes.chunks_exact(8).map(LittleEndian::read_u64).collect();
+
+                /* calls within the section are already resolved by the compiler */
+                for (insn_index, insn) in instructions.iter().enumerate() {
+                    if !is_pseudo_call(*insn)
+                        || calls.iter().any(|c| c.insn_index == insn_index)
+                        || ksym_relocations.iter().any(|r| r.insn_index == insn_index)
+                    {
+                        continue;
+                    }
+
+                    let target_index =
+                        (start / 8 + insn_index) as i64 + (*insn >> 32) as i32 as i64 + 1;
+                    calls.push(Call {
+                        insn_index,
+                        section_index: section.index,
+                        target_index: target_index.try_into()?,
+                        is_pointer: false,
+                    });
+                }
+
+                functions.push(Function {
+                    name: sym.name.clone(),
+                    section: section.name.clone(),
+                    section_index: section.index,
+                    start: start / 8,
+                    entry: section_def.filter(|_| sym.bind() == STB_GLOBAL),
+                    instructions,
+                    map_relocations,
+                    ksym_relocations,
+                    calls,
+                    func_info: Self::program_records(&btf_ext.func_info, section, start, end),
+                    line_info: Self::program_records(&btf_ext.line_info, section, start, end),
+                    core_relos: btf_ext
+                        .core_relos
+                        .get(&section.name)
+                        .map(|relos| {
+                            relos
+                                .iter()
+                                .filter(|r| (start..end).contains(&(r.insn_off as usize)))
+                                .map(|r| ((r.insn_off as usize - start) / 8, r.clone()))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        let mut programs = vec![];
+        for (index, function) in functions.iter().enumerate() {
+            if let Some(section_def) = function.entry {
+                programs.push(Self::link(&functions, index, section_def)?);
+            }
+        }
+
+        /* the license is a nul-terminated string, the version a u32 */
+        let license = match elf.section_by_name(LICENSE_SECTION) {
+            Some(section) => {
+                let data = elf.section_data(section)?;
+                let len = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+                let license = std::str::from_utf8(&data[..len])
+                    .map_err(|_| Error::MalformedObject("invalid license".into()))?;
+                Some(license.to_string())
+            }
+            None => None,
+        };
+
+        let kern_version = match elf.section_by_name(VERSION_SECTION) {
+            Some(section) => {
+                let data = elf.section_data(section)?;
+                if data.len() != size_of::<u32>() {
+                    return Err(Error::MalformedObject("invalid version section".into()));
+                }
+                Some(LittleEndian::read_u32(data))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            programs,
+            maps,
+            externs,
+            globals,
+            btf,
+            license,
+            kern_version,
+        })
+    }
+
+    /// Builds an entry program by appending every subprogram it calls, directly or
+    /// indirectly, after its own instructions and pointing the calls at the copies.
+    /// Each program gets its own copy of a subprogram shared by several entry points.
+    fn link(
+        functions: &[Function],
+        entry: usize,
+        section_def: &SectionDef,
+    ) -> Result<ObjectProgram, Error> {
+        let function = &functions[entry];
+        let mut program = ObjectProgram {
+            name: function.name.clone(),
+            section: function.section.clone(),
+            prog_type: section_def.prog_type,
+            expected_attach_type: section_def.attach_type,
+            instructions: vec![],
+            btf_target: section_def.btf_target,
+            map_relocations: vec![],
+            ksym_relocations: vec![],
+            func_info: vec![],
+            line_info: vec![],
+            core_relos: vec![],
+        };
+
+        let mut bases = HashMap::from([(entry, 0)]);
+        let mut pending = vec![entry];
+        Self::append(&mut program, function);
+
+        while let Some(index) = pending.pop() {
+            let base = bases[&index];
+            for call in functions[index].calls.iter() {
+                let (target, target_function) = functions
+                    .iter()
+                    .enumerate()
+                    .find(|(_, f)| {
+                        f.section_index == call.section_index
+                            && (f.start..f.start + f.instructions.len())
+                                .contains(&call.target_index)
+                    })
+                    .ok_or_else(|| {
+                        Error::MalformedObject(format!(
+                            "no call target for instruction {} in {}",
+                            call.insn_index, functions[index].name
+                        ))
+                    })?;
+
+                let target_base = match bases.get(&target) {
+                    Some(target_base) => *target_base,
+                    None => {
+                        let target_base = program.instructions.len();
+                        bases.insert(target, target_base);
+                        Self::append(&mut program, target_function);
+                        pending.push(target);
+                        target_base
+                    }
+                };
+
+                /* both calls and function pointers are relative to the next instruction */
+                let insn_index = base + call.insn_index;
+                let target_index = target_base + call.target_index - target_function.start;
+                let imm = target_index as i64 - insn_index as i64 - 1;
+                let src = if call.is_pointer {
+                    BPF_PSEUDO_FUNC
+                } else {
+                    BPF_PSEUDO_CALL
+                };
+
+                let insn = &mut program.instructions[insn_index];
+                *insn = (*insn & 0xffff_0fff) | src << 12 | (imm as i32 as u32 as u64) << 32;
+                if call.is_pointer {
+                    let next = program
+                        .instructions
+                        .get_mut(insn_index + 1)
+                        .ok_or_else(|| {
+                            Error::MalformedObject(format!(
+                                "truncated ld_imm64 in {}",
+                                program.name
+                            ))
+                        })?;
+                    *next &= 0xffffffff;
+                }
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// Appends a function's instructions to a program, along with its relocations and
+    /// BTF records.
+    fn append(program: &mut ObjectProgram, function: &Function) {
+        let base = program.instructions.len();
+        program.instructions.extend(&function.instructions);
+        program
+            .map_relocations
+            .extend(function.map_relocations.iter().map(|r| MapRelocation {
+                insn_index: r.insn_index + base,
+                ..r.clone()
+            }));
+        program
+            .ksym_relocations
+            .extend(function.ksym_relocations.iter().map(|r| KsymRelocation {
+                insn_index: r.insn_index + base,
+                ..r.clone()
+            }));
+        program.func_info.extend(
+            function
+                .func_info
+                .iter()
+                .map(|r| r.with_insn_off(r.insn_off + base as u32)),
+        );
+        program.line_info.extend(
+            function
+                .line_info
+                .iter()
+                .map(|r| r.with_insn_off(r.insn_off + base as u32)),
+        );
+        program.core_relos.extend(
+            function
+                .core_relos
+                .iter()
+                .map(|(index, relo)| (index + base, relo.clone())),
+        );
+    }
+
+    /// Fills in the sizes of data sections and the offsets of their variables, which
+    /// the compiler leaves as 0, from the ELF section and symbol tables.
+    fn fixup_datasecs(btf: &mut BtfTypes, elf: &Elf, symbols: &[elf::Symbol]) -> Result<(), Error> {
+        for id in 1..btf.type_count() {
+            let t = btf.get(id)?;
+            if t.kind != BTF_KIND_DATASEC {
+                continue;
+            }
+
+            /* externs (.kconfig, .ksyms) have no section of their own */
+            let section = match elf.section_by_name(btf.string(t.name_off)) {
+                Some(section) => section,
+                None => continue,
+            };
+
+            let mut offsets = vec![];
+            for secinfo in t.secinfos.iter() {
+                let name = btf.name(secinfo.type_id)?;
+                let sym = symbols
+                    .iter()
+                    .find(|s| s.shndx as usize == section.index && s.name == name)
+                    .ok_or_else(|| Error::SymbolNotFound(name.to_string()))?;
+                offsets.push(sym.value as u32);
+            }
+
+            btf.set_datasec_layout(id, section.size as u32, &offsets)?;
+        }
+
+        Ok(())
+    }
+
+    /// Picks the records of a section that fall within a program, converting their
+    /// byte offsets in the section to instruction indexes in the program.
+    fn program_records<T: Copy + InsnRecord>(
+        records: &HashMap<String, Vec<T>>,
+        section: &Section,
+        start: usize,
+        end: usize,
+    ) -> Vec<T> {
+        records
+            .get(&section.name)
+            .map(|records| {
+                records
+                    .iter()
+                    .filter(|r| (start..end).contains(&(r.insn_off() as usize)))
+                    .map(|r| r.with_insn_off((r.insn_off() - start as u32) / 8))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the relocations that apply to the given section.
+    fn section_relocations(elf: &Elf, section: &Section) -> Result<Vec<elf::Relocation>, Error> {
+        match elf
+            .sections
+            .iter()
+            .find(|s| s.sh_type == SHT_REL && s.info as usize == section.index)
+        {
+            Some(rel_section) => elf.relocations(rel_section),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Parses a `struct bpf_map_def` from the legacy maps section.
+    fn parse_legacy_map(name: &str, data: &[u8], offset: usize) -> Result<MapDefinition, Error> {
+        let def = offset
+            .checked_add(20)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| Error::MalformedObject(format!("map {} out of bounds", name)))?;
+
+        Ok(MapDefinition {
+            name: name.to_string(),
+            map_type: MapType::try_from(LittleEndian::read_u32(&def[0..]))?,
+            key_size: LittleEndian::read_u32(&def[4..]),
+            value_size: LittleEndian::read_u32(&def[8..]),
+            max_entries: LittleEndian::read_u32(&def[12..]),
+            flags: LittleEndian::read_u32(&def[16..]),
+        })
+    }
+
+    /// Parses BTF-defined maps: each variable in the `.maps` data section is a struct
+    /// whose members encode the map's attributes, e.g.: `__uint(max_entries, 10)` is a
+    /// member `int (*max_entries)[10]` and `__type(key, u32)` is a member `u32 *key`.
+    fn parse_btf_maps(btf: &BtfTypes) -> Result<Vec<MapDefinition>, Error> {
+        let datasec = btf
+            .find_by_name_kind(BTF_MAPS_SECTION, BTF_KIND_DATASEC)
+            .ok_or_else(|| Error::MalformedObject("no BTF for .maps section".into()))?;
+
+        let mut maps = vec![];
+        for secinfo in btf.get(datasec)?.secinfos.iter() {
+            let var = btf.get(secinfo.type_id)?;
+            let name = btf.string(var.name_off).to_string();
+            let def_id = btf.skip_mods_and_typedefs(var.size_or_type)?;
+            let def = btf.get(def_id)?;
+            if def.kind != BTF_KIND_STRUCT {
+                return Err(Error::MalformedObject(format!(
+                    "map {} isn't a struct",
+                    name
+                )));
+            }
+
+            let mut map_type = None;
+            let mut key_size = 0;
+            let mut value_size = 0;
+            let mut max_entries = 0;
+            let mut flags = 0;
+            for member in def.members.iter() {
+                let member_name = btf.string(member.name_off);
+                let ptr = btf.get(btf.skip_mods_and_typedefs(member.type_id)?)?;
+                if ptr.kind != BTF_KIND_PTR {
+                    return Err(Error::MalformedObject(format!(
+                        "map {} member {} isn't a pointer",
+                        name, member_name
+                    )));
+                }
+
+                /* the value of a __uint() is the element count of the pointed-to array */
+                let uint_value = || -> Result<u32, Error> {
+                    let array = btf.get(btf.skip_mods_and_typedefs(ptr.size_or_type)?)?;
+                    if array.kind != BTF_KIND_ARRAY {
+                        return Err(Error::MalformedObject(format!(
+                            "map {} member {} isn't a __uint",
+                            name, member_name
+                        )));
+                    }
+                    Ok(array.extra)
+                };
+
+                match member_name {
+                    "type" => map_type = Some(MapType::try_from(uint_value()?)?),
+                    "key_size" => key_size = uint_value()?,
+                    "value_size" => value_size = uint_value()?,
+                    "max_entries" => max_entries = uint_value()?,
+                    "map_flags" => flags = uint_value()?,
+                    "key" => key_size = btf.resolve_size(ptr.size_or_type)?,
+                    "value" => value_size = btf.resolve_size(ptr.size_or_type)?,
+                    "values" | "inner_map" => return Err(Error::NotImplemented),
+                    _ => {}
+                }
+            }
+
+            maps.push(MapDefinition {
+                map_type: map_type
+                    .ok_or_else(|| Error::MalformedObject(format!("map {} has no type", name)))?,
+                name,
+                key_size,
+                value_size,
+                max_entries,
+                flags,
+            });
+        }
+
+        Ok(maps)
+    }
+
+    /// Returns the programs found in the object.
+    pub fn programs(&self) -> &[ObjectProgram] {
+        &self.programs
+    }
+
+    /// Returns the definitions of the maps found in the object.
+    pub fn maps(&self) -> &[MapDefinition] {
+        &self.maps
+    }
+
+    /// The license declared in the object's `license` section, if any.
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Reads the initial value of a global variable, or of a whole global section,
+    /// e.g.: `.rodata`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the variable or section.
+    pub fn global<T: Pod>(&self, name: &str) -> Result<T, Error> {
+        let (index, offset) = find_global::<T>(&self.globals, name)?;
+        let data = &self.globals[index].data;
+        Ok(unsafe { std::ptr::read_unaligned(data[offset..].as_ptr() as *const T) })
+    }
+
+    /// Changes the initial value of a global variable, or of a whole global section.
+    /// This is how `.rodata` constants are configured, since they can't be changed
+    /// after loading.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the variable or section.
+    /// * `value` - The new value.
+    pub fn set_global<T: Pod>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let (index, offset) = find_global::<T>(&self.globals, name)?;
+        let data = &mut self.globals[index].data;
+        let bytes =
+            unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Creates the object's maps and loads all of its programs into the kernel.
+    pub fn load(&self) -> Result<LoadedObject, Error> {
+        let mut loaded = LoadedObject {
+            maps: vec![],
+            programs: HashMap::new(),
+            globals: self.globals.clone(),
+            global_mappings: vec![],
+        };
+
+        if let Some(kconfig) = loaded
+            .globals
+            .iter_mut()
+            .find(|g| g.name == KCONFIG_SECTION)
+        {
+            externs::fill_kconfig(&self.externs, &mut kconfig.data)?;
+        }
+
+        for def in self.maps.iter() {
+            loaded.maps.push((def.name.clone(), RawMap::create(def)?));
+        }
+
+        for global in loaded.globals.iter() {
+            let map = &mut loaded.maps[global.map_index].1;
+            map.update(&0u32.to_ne_bytes(), &global.data)?;
+            if global.read_only {
+                map.freeze()?;
+            }
+
+            let buf = map.mmap(global.data.len(), !global.read_only)?;
+            loaded.global_mappings.push(buf as usize);
+        }
+
+        /* the kernel keeps its own reference for each program loaded with it */
+        let btf = match &self.btf {
+            Some(btf) => Some(Btf::load(&btf.to_bytes(), None)?),
+            None => None,
+        };
+
+        let needs_vmlinux =
+            self.programs
+                .iter()
+                .any(|p| p.btf_target.is_some() || !p.core_relos.is_empty())
+                || self.externs.iter().any(|e| {
+                    matches!(e.kind, ExternKind::Ksym { typed: true } | ExternKind::Kfunc)
+                });
+        let vmlinux_btf = if needs_vmlinux {
+            Some(BtfTypes::parse(&std::fs::read(VMLINUX_BTF_PATH)?)?)
+        } else {
+            None
+        };
+
+        let relocator = match (&self.btf, &vmlinux_btf) {
+            (Some(local), Some(target)) => Some(CoreRelocator::new(local, target)),
+            _ => None,
+        };
+
+        let ksyms = Ksyms::resolve(&self.externs, vmlinux_btf.as_ref())?;
+
+        for program in self.programs.iter() {
+            let mut instructions = program.instructions.clone();
+            for (insn_index, relo) in program.core_relos.iter() {
+                let relocator = relocator.as_ref().ok_or_else(|| {
+                    Error::MalformedObject("CO-RE relocations without BTF".into())
+                })?;
+                relocator.apply(&mut instructions, *insn_index, relo)?;
+            }
+            for rel in program.map_relocations.iter() {
+                let insn = instructions.get_mut(rel.insn_index).ok_or_else(|| {
+                    Error::MalformedObject(format!("bad relocation in {}", program.name))
+                })?;
+                if *insn as u8 != BPF_LD_IMM64 {
+                    return Err(Error::MalformedObject(format!(
+                        "map relocation on non ld_imm64 instruction in {}",
+                        program.name
+                    )));
+                }
+
+                /*
+                 * src_reg = BPF_PSEUDO_MAP_FD, imm = fd; or for values, src_reg =
+                 * BPF_PSEUDO_MAP_VALUE and the offset in the second half's imm.
+                 */
+                let fd = loaded.maps[rel.map_index].1.fd() as u64;
+                let src = match rel.value_offset {
+                    Some(_) => BPF_PSEUDO_MAP_VALUE,
+                    None => BPF_PSEUDO_MAP_FD,
+                };
+                *insn = (*insn & 0x0fff) | src << 12 | fd << 32;
+
+                if let Some(value_offset) = rel.value_offset {
+                    let next = instructions.get_mut(rel.insn_index + 1).ok_or_else(|| {
+                        Error::MalformedObject(format!("truncated ld_imm64 in {}", program.name))
+                    })?;
+                    *next = (*next & 0xffffffff) | (value_offset as u64) << 32;
+                }
+            }
+            for rel in program.ksym_relocations.iter() {
+                ksyms.apply(&mut instructions, rel.insn_index, rel.extern_index)?;
+            }
+
+            let attach_btf_id = match (program.btf_target, &vmlinux_btf) {
+                (Some(target), Some(btf)) => Some(Self::find_attach_btf_id(
+                    btf,
+                    target,
+                    program.attach_target(),
+                )?),
+                _ => None,
+            };
+
+            let attr = ProgramAttr {
+                prog_name: Some(program.name.clone()),
+                prog_type: program.prog_type,
+                expected_attach_type: program.expected_attach_type,
+                attach_btf_id,
+                btf: btf.as_ref().map(|btf| ProgramBtf {
+                    btf_fd: btf.get_identifier(),
+                    func_info: program.func_info.clone(),
+                    line_info: program.line_info.clone(),
+                    fd_array: ksyms.fd_array.clone(),
+                }),
+                license: self.license.clone(),
+                kern_version: self.kern_version,
+                ..Default::default()
+            };
+
+            let prog = Program::create(&attr, &instructions, None)?;
+            loaded.programs.insert(program.name.clone(), prog);
+        }
+
+        Ok(loaded)
+    }
+
+    fn find_attach_btf_id(btf: &BtfTypes, target: BtfTarget, name: &str) -> Result<u32, Error> {
+        use crate::btf::{BTF_KIND_FUNC, BTF_KIND_TYPEDEF};
+
+        let (name, kind) = match target {
+            BtfTarget::Func => (name.to_string(), BTF_KIND_FUNC),
+            BtfTarget::PrefixedFunc(prefix) => (format!("{}{}", prefix, name), BTF_KIND_FUNC),
+            BtfTarget::RawTracepoint => (format!("btf_trace_{}", name), BTF_KIND_TYPEDEF),
+        };
+
+        btf.find_by_name_kind(&name, kind)
+            .ok_or(Error::SymbolNotFound(name))
+    }
+}
+
+const VMLINUX_BTF_PATH: &str = "/sys/kernel/btf/vmlinux";
+
+/// The maps and programs of an object after loading it into the kernel.
+pub struct LoadedObject {
+    /// The maps by name; they live as long as the `LoadedObject`.
+    maps: Vec<(String, RawMap)>,
+    programs: HashMap<String, Program>,
+    globals: Vec<GlobalSection>,
+
+    /// The address each global section is mapped at, owned by its map.
+    global_mappings: Vec<usize>,
+}
+
+impl LoadedObject {
+    /// Returns a loaded program by function name.
+    pub fn program(&self, name: &str) -> Option<&Program> {
+        self.programs.get(name)
+    }
+
+    /// Returns the names of all loaded programs.
+    pub fn program_names(&self) -> impl Iterator<Item = &str> {
+        self.programs.keys().map(|k| k.as_str())
+    }
+
+    /// Opens one of the object's maps by name, with the given key and value types. The
+    /// types' sizes must match the map definition.
+    pub fn map<K: Copy + Default, V: Copy + Default>(
+        &self,
+        name: &str,
+    ) -> Result<Map<K, V>, Error> {
+        let (_, map) = self
+            .maps
+            .iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| Error::SymbolNotFound(name.to_string()))?;
+
+        Map::from_id(map.id()?)
+    }
+
+    /// Reads a global variable, or a whole global section, e.g.: `.bss`, from the
+    /// memory shared with the loaded programs. Programs may be writing to it at the
+    /// same time, so this is a copy rather than a reference, and a value larger than
+    /// the CPU's word may be read torn.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the variable or section.
+    pub fn global<T: Pod>(&self, name: &str) -> Result<T, Error> {
+        let (index, offset) = find_global::<T>(&self.globals, name)?;
+        if offset % align_of::<T>() != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let ptr = (self.global_mappings[index] + offset) as *const T;
+        Ok(unsafe { std::ptr::read_volatile(ptr) })
+    }
+
+    /// Writes a global variable, or a whole global section, in the memory shared with
+    /// the loaded programs. Variables in `.rodata` are frozen after loading and can't
+    /// be changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the variable or section.
+    /// * `value` - The new value.
+    pub fn set_global<T: Pod>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let (index, offset) = find_global::<T>(&self.globals, name)?;
+        if self.globals[index].read_only {
+            return Err(Error::InvalidArgument);
+        }
+
+        if offset % align_of::<T>() != 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let ptr = (self.global_mappings[index] + offset) as *mut T;
+        unsafe { std::ptr::write_volatile(ptr, *value) };
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
-use super::syscalls::bpf;
-use crate::error::Error;
+use super::syscalls::{bpf, bpf_mut, cbzero};
+use crate::error::{Error, Operation};
+
+use std::fmt::{Display, Formatter};
 
 use std::mem::size_of;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum AttachType {
     CgroupInetIngress,
     CgroupInetEgress,
@@ -49,10 +51,14 @@ pub enum AttachType {
     SkReuseportSelectOrMigrate,
     PerfEvent,
     TraceKprobeMulti,
+    LsmCgroup,
+    StructOps,
+    Netfilter,
 }
 
+/// A `bpf()` command, as in the kernel's `enum bpf_cmd`.
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Command {
     MapCreate = 0,
     MapLookupElem,
@@ -77,7 +83,7 @@ pub enum Command {
     TaskFdQuery,
     MapLookupAndDeleteElem,
     MapFreeze,
-    GetNextId,
+    BtfGetNextId,
     MapLookupBatch,
     MapLookupAndDeleteBatch,
     MapUpdateBatch,
@@ -92,7 +98,56 @@ pub enum Command {
     ProgBindMap,
 }
 
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::MapCreate => "BPF_MAP_CREATE",
+            Self::MapLookupElem => "BPF_MAP_LOOKUP_ELEM",
+            Self::MapUpdateElem => "BPF_MAP_UPDATE_ELEM",
+            Self::MapDeleteElem => "BPF_MAP_DELETE_ELEM",
+            Self::MapGetNextKey => "BPF_MAP_GET_NEXT_KEY",
+            Self::ProgLoad => "BPF_PROG_LOAD",
+            Self::ObjPin => "BPF_OBJ_PIN",
+            Self::ObjGet => "BPF_OBJ_GET",
+            Self::ProgAttach => "BPF_PROG_ATTACH",
+            Self::ProgDetach => "BPF_PROG_DETACH",
+            Self::ProgTestRun => "BPF_PROG_TEST_RUN",
+            Self::ProgGetNextId => "BPF_PROG_GET_NEXT_ID",
+            Self::MapGetNextId => "BPF_MAP_GET_NEXT_ID",
+            Self::ProgGetFdById => "BPF_PROG_GET_FD_BY_ID",
+            Self::MapGetFdById => "BPF_MAP_GET_FD_BY_ID",
+            Self::ObjGetInfoByFd => "BPF_OBJ_GET_INFO_BY_FD",
+            Self::ProgQuery => "BPF_PROG_QUERY",
+            Self::RawTracepointOpen => "BPF_RAW_TRACEPOINT_OPEN",
+            Self::BtfLoad => "BPF_BTF_LOAD",
+            Self::BtfGetFdById => "BPF_BTF_GET_FD_BY_ID",
+            Self::TaskFdQuery => "BPF_TASK_FD_QUERY",
+            Self::MapLookupAndDeleteElem => "BPF_MAP_LOOKUP_AND_DELETE_ELEM",
+            Self::MapFreeze => "BPF_MAP_FREEZE",
+            Self::BtfGetNextId => "BPF_BTF_GET_NEXT_ID",
+            Self::MapLookupBatch => "BPF_MAP_LOOKUP_BATCH",
+            Self::MapLookupAndDeleteBatch => "BPF_MAP_LOOKUP_AND_DELETE_BATCH",
+            Self::MapUpdateBatch => "BPF_MAP_UPDATE_BATCH",
+            Self::MapDeleteBatch => "BPF_MAP_DELETE_BATCH",
+            Self::LinkCreate => "BPF_LINK_CREATE",
+            Self::LinkUpdate => "BPF_LINK_UPDATE",
+            Self::LinkGetFdById => "BPF_LINK_GET_FD_BY_ID",
+            Self::LinkGetNextId => "BPF_LINK_GET_NEXT_ID",
+            Self::EnableStats => "BPF_ENABLE_STATS",
+            Self::IterCreate => "BPF_ITER_CREATE",
+            Self::LinkDetach => "BPF_LINK_DETACH",
+            Self::ProgBindMap => "BPF_PROG_BIND_MAP",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 pub trait CallBpf {
+    /// The fd of the map or program the command acts on, for error reporting.
+    fn target_fd(&self) -> Option<u32> {
+        None
+    }
+
     fn call_bpf(&self, cmd: Command) -> Result<u32, Error>
     where
         Self: Sized,
@@ -103,9 +158,136 @@ pub trait CallBpf {
             size_of::<Self>(),
         );
         if r < 0 {
-            Err(Error::SystemError(r))
+            Err(Error::system(
+                Operation::Bpf {
+                    cmd,
+                    fd: self.target_fd(),
+                },
+                r,
+            ))
         } else {
             Ok(r as u32)
         }
     }
+
+    /// Same as `call_bpf`, but any fields the kernel writes back into the attribute
+    /// structure are copied back into `self`.
+    fn call_bpf_mut(&mut self, cmd: Command) -> Result<u32, Error>
+    where
+        Self: Sized,
+    {
+        let r = bpf_mut(cmd as u32, self as *mut Self as *mut u8, size_of::<Self>());
+        if r < 0 {
+            Err(Error::system(
+                Operation::Bpf {
+                    cmd,
+                    fd: self.target_fd(),
+                },
+                r,
+            ))
+        } else {
+            Ok(r as u32)
+        }
+    }
+}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfGetIdAttr {
+    pub id: u32,
+    pub next_id: u32,
+    pub open_flags: u32,
+}
+
+impl CallBpf for BpfGetIdAttr {}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfObjGetInfoAttr {
+    pub bpf_fd: u32,
+    pub info_len: u32,
+    pub info: u64,
+}
+
+impl CallBpf for BpfObjGetInfoAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.bpf_fd)
+    }
+}
+
+/// An iterator over the ids of all objects of a given kind (maps, programs, etc.)
+/// currently loaded in the kernel, regardless of which process created them.
+pub struct IdIter {
+    cmd: Command,
+    id: u32,
+    done: bool,
+}
+
+impl IdIter {
+    pub(crate) fn new(cmd: Command) -> Self {
+        Self {
+            cmd,
+            id: 0,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for IdIter {
+    type Item = Result<u32, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut attr = BpfGetIdAttr::default();
+        cbzero(&mut attr);
+        attr.id = self.id;
+
+        match attr.call_bpf_mut(self.cmd) {
+            Ok(_) => {
+                self.id = attr.next_id;
+                Some(Ok(attr.next_id))
+            }
+            Err(e) if e.is_not_found() => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Opens a new fd for the object with the given id. `cmd` selects the kind of
+/// object, e.g.: `Command::MapGetFdById`.
+pub(crate) fn get_fd_by_id(cmd: Command, id: u32) -> Result<u32, Error> {
+    let mut attr = BpfGetIdAttr::default();
+    cbzero(&mut attr);
+    attr.id = id;
+
+    attr.call_bpf(cmd)
+}
+
+/// Fills `info` with the kernel's description of the object referred to by `fd`.
+/// `T` must match the layout of the kernel's `bpf_*_info` structure for the object
+/// kind; the kernel only writes as many bytes as both sides know about.
+pub(crate) fn get_info_by_fd<T>(fd: u32, info: &mut T) -> Result<(), Error> {
+    let attr = BpfObjGetInfoAttr {
+        bpf_fd: fd,
+        info_len: size_of::<T>() as u32,
+        info: info as *mut T as u64,
+    };
+
+    attr.call_bpf(Command::ObjGetInfoByFd)?;
+    Ok(())
+}
+
+/// Converts a fixed-size, nul-padded name buffer as reported by the kernel into a `String`.
+pub(crate) fn name_from_bytes(buf: &[u8]) -> String {
+    let len = buf.iter().position(|c| *c == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).to_string()
 }
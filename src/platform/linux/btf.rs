@@ -0,0 +1,188 @@
+use crate::error::Error;
+use crate::platform::linux::bpf::{
+    get_fd_by_id, get_info_by_fd, name_from_bytes, CallBpf, Command, IdIter,
+};
+use crate::platform::linux::syscalls::{cbzero, close};
+
+use std::io::Write;
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfBtfLoadAttr {
+    pub btf: u64,
+    pub btf_log_buf: u64,
+    pub btf_size: u32,
+    pub btf_log_size: u32,
+    pub btf_log_level: u32,
+}
+
+impl CallBpf for BpfBtfLoadAttr {}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfBtfInfo {
+    pub btf: u64,
+    pub btf_size: u32,
+    pub id: u32,
+    pub name: u64,
+    pub name_len: u32,
+    pub kernel_btf: u32,
+}
+
+/// Associates a function in a program's BTF with the instruction it starts at, as in
+/// the kernel's `struct bpf_func_info`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct FuncInfo {
+    /// The index of the function's first instruction.
+    pub insn_off: u32,
+
+    /// The BTF id of the function's `BTF_KIND_FUNC` type.
+    pub type_id: u32,
+}
+
+/// Associates an instruction with a source line, as in the kernel's
+/// `struct bpf_line_info`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct LineInfo {
+    /// The index of the instruction.
+    pub insn_off: u32,
+
+    /// The offset of the source file's name in the BTF string section.
+    pub file_name_off: u32,
+
+    /// The offset of the source line's text in the BTF string section.
+    pub line_off: u32,
+
+    /// The line number in the upper 22 bits and the column in the lower 10 bits.
+    pub line_col: u32,
+}
+
+impl LineInfo {
+    /// The source line number.
+    pub fn line(&self) -> u32 {
+        self.line_col >> 10
+    }
+
+    /// The source column number.
+    pub fn column(&self) -> u32 {
+        self.line_col & 0x3ff
+    }
+}
+
+/// Type information loaded into the kernel. Programs reference it to describe their
+/// functions and source lines, maps to describe their keys and values.
+pub struct Btf {
+    fd: u32,
+}
+
+impl Btf {
+    /// Loads a raw BTF blob, e.g.: the .BTF section of an object file, into the kernel.
+    /// Optionally, an object implementing the Write trait can be passed in that receives
+    /// the kernel's BTF verifier output.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The BTF blob, starting with the BTF header.
+    /// * `log_out` - The logger object.
+    pub fn load(data: &[u8], log_out: Option<&mut dyn Write>) -> Result<Self, Error> {
+        /* the log is only worth a buffer when someone reads it */
+        let mut buf = match log_out {
+            Some(_) => vec![0u8; 1 << 20],
+            None => vec![],
+        };
+
+        let (log_buf, log_size, log_level) = if buf.is_empty() {
+            (0, 0, 0)
+        } else {
+            (buf.as_mut_ptr() as u64, buf.len() as u32, 1)
+        };
+
+        let mut attr = BpfBtfLoadAttr::default();
+        cbzero(&mut attr);
+        attr.btf = data.as_ptr() as u64;
+        attr.btf_log_buf = log_buf;
+        attr.btf_size = data.len() as u32;
+        attr.btf_log_size = log_size;
+        attr.btf_log_level = log_level;
+
+        let r = attr.call_bpf(Command::BtfLoad);
+
+        if let Some(log_out) = log_out {
+            let len = buf.iter().position(|c| *c == 0).unwrap_or(buf.len());
+            if let Ok(s) = std::str::from_utf8(&buf[..len]) {
+                let _ = write!(log_out, "{}", s);
+            }
+        }
+
+        Ok(Self { fd: r? })
+    }
+
+    /// Opens BTF loaded into the kernel by its id, e.g.: as reported by `ProgramInfo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The kernel id of the BTF object.
+    pub fn from_id(id: u32) -> Result<Self, Error> {
+        Ok(Self {
+            fd: get_fd_by_id(Command::BtfGetFdById, id)?,
+        })
+    }
+
+    /// Gets the underlying identifier of the BTF object; this is what's passed as the
+    /// `btf_fd` of `ProgramBtf`.
+    pub fn get_identifier(&self) -> u32 {
+        self.fd
+    }
+
+    /// Gets the kernel id of the BTF object.
+    pub fn id(&self) -> Result<u32, Error> {
+        let mut info = BpfBtfInfo::default();
+        get_info_by_fd(self.fd, &mut info)?;
+        Ok(info.id)
+    }
+
+    /// Reads the raw BTF blob back from the kernel, e.g.: to parse it with
+    /// `BtfTypes::parse`.
+    pub fn data(&self) -> Result<Vec<u8>, Error> {
+        /* the first query gets the size, the second one copies the data out */
+        let mut info = BpfBtfInfo::default();
+        get_info_by_fd(self.fd, &mut info)?;
+
+        let mut data = vec![0u8; info.btf_size as usize];
+        let mut info = BpfBtfInfo {
+            btf: data.as_mut_ptr() as u64,
+            btf_size: data.len() as u32,
+            ..Default::default()
+        };
+        get_info_by_fd(self.fd, &mut info)?;
+        data.truncate(info.btf_size as usize);
+
+        Ok(data)
+    }
+
+    /// Gets the name of kernel BTF: `vmlinux` or the name of the module it describes.
+    /// BTF loaded from user space has no name.
+    pub fn name(&self) -> Result<String, Error> {
+        let mut name = [0u8; 64];
+        let mut info = BpfBtfInfo {
+            name: name.as_mut_ptr() as u64,
+            name_len: name.len() as u32,
+            ..Default::default()
+        };
+        get_info_by_fd(self.fd, &mut info)?;
+        Ok(name_from_bytes(&name))
+    }
+}
+
+impl Drop for Btf {
+    fn drop(&mut self) {
+        close(self.fd);
+    }
+}
+
+/// Returns an iterator over the ids of all BTF objects loaded in the kernel.
+pub fn btf_ids() -> IdIter {
+    IdIter::new(Command::BtfGetNextId)
+}
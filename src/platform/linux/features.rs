@@ -0,0 +1,425 @@
+use crate::error::Error;
+use crate::object::kernel_version;
+use crate::platform::linux::bpf::{AttachType, CallBpf, Command};
+use crate::platform::linux::btf::Btf;
+use crate::platform::linux::map::{create_map, MapAttr, MapDefinition, MapType};
+use crate::platform::linux::prog::{prog_flags, LogLevel, Program, ProgramAttr, ProgramType};
+use crate::platform::linux::syscalls::{cbzero, close};
+
+use std::sync::Mutex;
+
+const ENOENT: i32 = 2;
+const ESRCH: i32 = 3;
+const E2BIG: i32 = 7;
+const EINVAL: i32 = 22;
+const EOPNOTSUPP: i32 = 95;
+const ENOTSUPP: i32 = 524;
+
+const PAGE_SIZE: u32 = 4096;
+
+/* r0 = 0; exit */
+const RETURN_ZERO: [u64; 2] = [0x0000_0000_0000_00b7, 0x0000_0000_0000_0095];
+
+/* an fd no process has open, so commands that get this far fail with EBADF */
+const INVALID_FD: u32 = u32::MAX;
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfProgAttachAttr {
+    pub target_fd: u32,
+    pub attach_bpf_fd: u32,
+    pub attach_type: u32,
+    pub attach_flags: u32,
+    pub replace_bpf_fd: u32,
+}
+
+impl CallBpf for BpfProgAttachAttr {}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfLinkCreateKprobeMultiAttr {
+    pub prog_fd: u32,
+    pub target_fd: u32,
+    pub attach_type: u32,
+    pub flags: u32,
+    pub kprobe_multi_flags: u32,
+    pub cnt: u32,
+    pub syms: u64,
+    pub addrs: u64,
+    pub cookies: u64,
+}
+
+impl CallBpf for BpfLinkCreateKprobeMultiAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.prog_fd)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Feature {
+    ProgramType(ProgramType),
+    MapType(MapType),
+    AttachType(AttachType),
+    Helper(ProgramType, u32),
+    Btf,
+    Link,
+    KprobeMulti,
+}
+
+/// Probe results are cached for the life of the process; the kernel doesn't change
+/// under us.
+static CACHE: Mutex<Vec<(Feature, bool)>> = Mutex::new(Vec::new());
+
+fn cached<F: FnOnce() -> Result<bool, Error>>(feature: Feature, probe: F) -> Result<bool, Error> {
+    {
+        let cache = CACHE.lock().or(Err(Error::MutexPoisoned))?;
+        if let Some((_, supported)) = cache.iter().find(|(f, _)| *f == feature) {
+            return Ok(*supported);
+        }
+    }
+
+    let supported = probe()?;
+    CACHE
+        .lock()
+        .or(Err(Error::MutexPoisoned))?
+        .push((feature, supported));
+    Ok(supported)
+}
+
+/// Whether an error is how the kernel says it doesn't know a type or command.
+fn is_unsupported(e: &Error) -> bool {
+    matches!(
+        e.errno(),
+        Some(EINVAL) | Some(E2BIG) | Some(EOPNOTSUPP) | Some(ENOTSUPP)
+    )
+}
+
+/// Program types that can't load without a BTF attach target. They're probed by
+/// loading against a bogus target and looking for the verifier's complaint about it,
+/// as libbpf does.
+fn attach_target_message(prog_type: ProgramType) -> Option<&'static str> {
+    match prog_type {
+        ProgramType::Tracing | ProgramType::Lsm => Some("attach_btf_id 1 is not a function"),
+        ProgramType::Ext => Some("Cannot replace kernel functions"),
+        ProgramType::StructOps => Some("attach_btf_id 1 is not a supported struct"),
+        _ => None,
+    }
+}
+
+/// The attributes of a minimal program of the given type.
+fn probe_program_attr(prog_type: ProgramType) -> Result<ProgramAttr, Error> {
+    let expected_attach_type = match prog_type {
+        ProgramType::CgroupSockAddr => Some(AttachType::CgroupInet4Connect),
+        ProgramType::CgroupSockopt => Some(AttachType::CgroupGetsockopt),
+        ProgramType::SkLookup => Some(AttachType::SkLookup),
+        ProgramType::Netfilter => Some(AttachType::Netfilter),
+        ProgramType::Tracing => Some(AttachType::TraceFentry),
+        ProgramType::Lsm => Some(AttachType::LsmMac),
+        _ => None,
+    };
+
+    /* syscall programs can only be sleepable */
+    let prog_flags = match prog_type {
+        ProgramType::Syscall => prog_flags::SLEEPABLE,
+        _ => 0,
+    };
+
+    Ok(ProgramAttr {
+        prog_type,
+        expected_attach_type,
+        prog_flags,
+        attach_btf_id: attach_target_message(prog_type).map(|_| 1),
+        log_level: LogLevel::Basic,
+        log_size: Some(4096),
+        /* kprobes had to match the running kernel before 5.0 */
+        kern_version: Some(kernel_version()?),
+        ..Default::default()
+    })
+}
+
+/// Loads a minimal program; `Ok(true)` if it loads or fails the way a supported
+/// program type is expected to.
+fn probe_load(attr: &ProgramAttr, instructions: &[u64]) -> Result<bool, Error> {
+    match Program::create_unchecked(attr, instructions, None) {
+        Ok(_) => Ok(true),
+        Err(e) => match (attach_target_message(attr.prog_type), e.verifier_log()) {
+            (Some(message), Some(log)) if log.contains(message) => Ok(true),
+            _ if is_unsupported(&e) => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Checks whether the running kernel can load programs of the given type.
+///
+/// # Arguments
+///
+/// * `prog_type` - The program type.
+pub fn program_type_supported(prog_type: ProgramType) -> Result<bool, Error> {
+    cached(Feature::ProgramType(prog_type), || {
+        if prog_type == ProgramType::Unspec {
+            return Ok(false);
+        }
+
+        probe_load(&probe_program_attr(prog_type)?, &RETURN_ZERO)
+    })
+}
+
+/// Checks whether programs of the given type can call a helper function.
+/// Program types that need an attach target (tracing, LSM, extension and struct_ops
+/// programs) can't be probed and return `Error::InvalidArgument`.
+///
+/// # Arguments
+///
+/// * `prog_type` - The program type.
+/// * `helper_id` - The helper's id, e.g.: 1 for `bpf_map_lookup_elem`.
+pub fn helper_supported(prog_type: ProgramType, helper_id: u32) -> Result<bool, Error> {
+    if attach_target_message(prog_type).is_some() {
+        return Err(Error::InvalidArgument);
+    }
+
+    cached(Feature::Helper(prog_type, helper_id), || {
+        if !program_type_supported(prog_type)? {
+            return Ok(false);
+        }
+
+        /* call <helper_id>; r0 = 0; exit */
+        let instructions = [
+            0x0000_0000_0000_0085 | (helper_id as u64) << 32,
+            RETURN_ZERO[0],
+            RETURN_ZERO[1],
+        ];
+
+        /* any complaint but about the helper itself means the verifier knows it */
+        match Program::create_unchecked(&probe_program_attr(prog_type)?, &instructions, None) {
+            Ok(_) => Ok(true),
+            Err(e) => match e.verifier_log() {
+                Some(log) => Ok(!(log.contains("invalid func ")
+                    || log.contains("unknown func ")
+                    || log.contains("program of this type cannot use helper "))),
+                None => Err(e),
+            },
+        }
+    })
+}
+
+/// A BTF blob with a single type, `int`.
+fn int_btf() -> Vec<u8> {
+    let types: [u32; 4] = [1, 0x0100_0000, 4, 0x0100_0020];
+    let strings = b"\0int\0";
+
+    let mut data = vec![0x9f, 0xeb, 1, 0];
+    for field in [24, 0, 16, 16, strings.len() as u32] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for word in types {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    data.extend_from_slice(strings);
+    data
+}
+
+/// Checks whether the running kernel can create maps of the given type.
+///
+/// # Arguments
+///
+/// * `map_type` - The map type.
+pub fn map_type_supported(map_type: MapType) -> Result<bool, Error> {
+    cached(Feature::MapType(map_type), || {
+        use crate::platform::linux::map::map_flags::{MMAPABLE, NO_PREALLOC};
+
+        let mut attr = MapAttr::default();
+        cbzero(&mut attr);
+        attr.map_type = map_type as u32;
+        attr.key_size = 4;
+        attr.value_size = 4;
+        attr.max_entries = 1;
+
+        /* these stay open until the probe is done */
+        let mut btf = None;
+        let mut inner_map = None;
+
+        match map_type {
+            MapType::Unspec => return Ok(false),
+            MapType::LpmTrie => {
+                attr.key_size = 8;
+                attr.value_size = 8;
+                attr.map_flags = NO_PREALLOC;
+            }
+            MapType::StackTrace => attr.value_size = 8,
+            MapType::Queue | MapType::Stack | MapType::BloomFilter => attr.key_size = 0,
+            MapType::RingBuf | MapType::UserRingBuf => {
+                attr.key_size = 0;
+                attr.value_size = 0;
+                attr.max_entries = PAGE_SIZE;
+            }
+            MapType::Arena => {
+                attr.key_size = 0;
+                attr.value_size = 0;
+                attr.map_flags = MMAPABLE;
+            }
+            MapType::CgroupStorage | MapType::PerCpuCgroupStorage => {
+                /* struct bpf_cgroup_storage_key */
+                attr.key_size = 16;
+                attr.max_entries = 0;
+            }
+            MapType::SkStorage
+            | MapType::InodeStorage
+            | MapType::TaskStorage
+            | MapType::CgrpStorage => {
+                /* local storage needs BTF for its key and value */
+                let int_btf = match Btf::load(&int_btf(), None) {
+                    Ok(btf) => btf,
+                    Err(e) if is_unsupported(&e) => return Ok(false),
+                    Err(e) => return Err(e),
+                };
+                attr.btf_fd = int_btf.get_identifier();
+                attr.btf_key_type_id = 1;
+                attr.btf_value_type_id = 1;
+                attr.max_entries = 0;
+                attr.map_flags = NO_PREALLOC;
+                btf = Some(int_btf);
+            }
+            MapType::ArrayOfMaps | MapType::HashOfMaps => {
+                let fd = create_map(&MapDefinition {
+                    name: String::new(),
+                    map_type: MapType::Array,
+                    key_size: 4,
+                    value_size: 4,
+                    max_entries: 1,
+                    flags: 0,
+                })?;
+                attr.inner_map_fd = fd;
+                inner_map = Some(fd);
+            }
+            /* a bogus vmlinux type is refused with ENOTSUPP by kernels that know it */
+            MapType::StructOps => attr.btf_vmlinux_value_type_id = 1,
+            _ => {}
+        }
+
+        let r = attr.call_bpf(Command::MapCreate);
+        drop(btf);
+        if let Some(fd) = inner_map {
+            close(fd);
+        }
+
+        match r {
+            Ok(fd) => {
+                close(fd);
+                Ok(true)
+            }
+            Err(e) if map_type == MapType::StructOps && e.errno() == Some(ENOTSUPP) => Ok(true),
+            Err(e) if is_unsupported(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// Checks whether the running kernel knows an attach type. Types programs attach
+/// with are probed through `BPF_PROG_ATTACH`; types that only qualify a program at
+/// load time, e.g.: `XdpDevmap`, by loading a program with them.
+///
+/// # Arguments
+///
+/// * `attach_type` - The attach type.
+pub fn attach_type_supported(attach_type: AttachType) -> Result<bool, Error> {
+    cached(Feature::AttachType(attach_type), || {
+        let prog_type = match attach_type {
+            AttachType::XdpDevmap | AttachType::XdpCpumap => ProgramType::Xdp,
+            AttachType::SkReuseportSelect | AttachType::SkReuseportSelectOrMigrate => {
+                ProgramType::SkReuseport
+            }
+            AttachType::PerfEvent => ProgramType::PerfEvent,
+            AttachType::TraceKprobeMulti => ProgramType::KProbe,
+            AttachType::StructOps => return program_type_supported(ProgramType::StructOps),
+            AttachType::Netfilter => return program_type_supported(ProgramType::Netfilter),
+            _ => {
+                /* the attach type is checked before the program fd */
+                let mut attr = BpfProgAttachAttr::default();
+                cbzero(&mut attr);
+                attr.attach_bpf_fd = INVALID_FD;
+                attr.attach_type = attach_type as u32;
+
+                return match attr.call_bpf(Command::ProgAttach) {
+                    Err(e) if is_unsupported(&e) => Ok(false),
+                    _ => Ok(true),
+                };
+            }
+        };
+
+        let attr = ProgramAttr {
+            expected_attach_type: Some(attach_type),
+            ..probe_program_attr(prog_type)?
+        };
+        probe_load(&attr, &RETURN_ZERO)
+    })
+}
+
+/// Checks whether the running kernel accepts BTF.
+pub fn btf_supported() -> Result<bool, Error> {
+    cached(Feature::Btf, || match Btf::load(&int_btf(), None) {
+        Ok(_) => Ok(true),
+        Err(e) if is_unsupported(&e) => Ok(false),
+        Err(e) => Err(e),
+    })
+}
+
+/// Checks whether the running kernel has BPF ring buffers.
+pub fn ring_buffer_supported() -> Result<bool, Error> {
+    map_type_supported(MapType::RingBuf)
+}
+
+/// Checks whether the running kernel has `BPF_LINK_CREATE`.
+pub fn link_supported() -> Result<bool, Error> {
+    cached(Feature::Link, || {
+        let mut attr = BpfLinkCreateKprobeMultiAttr::default();
+        cbzero(&mut attr);
+        attr.prog_fd = INVALID_FD;
+
+        /* the command looks the program up first, if it exists */
+        match attr.call_bpf(Command::LinkCreate) {
+            Err(e) if is_unsupported(&e) => Ok(false),
+            _ => Ok(true),
+        }
+    })
+}
+
+/// Checks whether the running kernel can attach a program to many kprobes at once.
+pub fn kprobe_multi_supported() -> Result<bool, Error> {
+    cached(Feature::KprobeMulti, || {
+        let attr = ProgramAttr {
+            expected_attach_type: Some(AttachType::TraceKprobeMulti),
+            ..probe_program_attr(ProgramType::KProbe)?
+        };
+
+        /* kernels without it refuse the attach type at load time */
+        let program = match Program::create_unchecked(&attr, &RETURN_ZERO, None) {
+            Ok(program) => program,
+            Err(e) if is_unsupported(&e) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        /*
+         * like libbpf, ask for a symbol that doesn't exist: only kernels that know
+         * the attach type get as far as looking it up, older ones say EINVAL.
+         */
+        let sym = "bpf_api_kprobe_multi_probe_nonexistent\0";
+        let syms = [sym.as_ptr() as u64];
+        let mut link_attr = BpfLinkCreateKprobeMultiAttr::default();
+        cbzero(&mut link_attr);
+        link_attr.prog_fd = program.get_fd();
+        link_attr.attach_type = AttachType::TraceKprobeMulti as u32;
+        link_attr.cnt = 1;
+        link_attr.syms = syms.as_ptr() as u64;
+
+        match link_attr.call_bpf(Command::LinkCreate) {
+            Ok(fd) => {
+                close(fd);
+                Ok(true)
+            }
+            Err(e) if matches!(e.errno(), Some(ENOENT) | Some(ESRCH)) => Ok(true),
+            Err(e) if is_unsupported(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    })
+}
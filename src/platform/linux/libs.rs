@@ -0,0 +1,158 @@
+use crate::error::Error;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::fs::{read, read_to_string};
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Path, PathBuf};
+
+const LD_SO_CACHE: &str = "/etc/ld.so.cache";
+const LD_SO_CACHE_MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+
+/* the header and entries of the new cache format, following the old one if present */
+const LD_SO_CACHE_HEADER_SIZE: usize = 48;
+const LD_SO_CACHE_ENTRY_SIZE: usize = 24;
+
+/* ELF libc6 libraries for x86-64, the only architecture supported */
+const FLAG_ELF_LIBC6: u32 = 0x0003;
+const FLAG_X8664_LIB64: u32 = 0x0300;
+
+/* where the dynamic linker looks after the cache */
+const DEFAULT_LIBRARY_DIRS: [&str; 4] = ["/lib64", "/usr/lib64", "/lib", "/usr/lib"];
+
+/// Whether a library file name is what `name` asks for, e.g.: `libc.so.6` for
+/// `libc.so.6`, `libc.so` or `libc`.
+fn library_matches(file_name: &str, name: &str) -> bool {
+    match file_name.strip_prefix(name) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('-'),
+        None => false,
+    }
+}
+
+/// Splits a line of `/proc/<pid>/maps` into its address range and path, if it maps a
+/// file: "7f..-7f.. r-xp 00028000 fd:01 1234    /usr/lib/x86_64-linux-gnu/libc.so.6".
+/// The path is the rest of the line, so it may contain spaces.
+fn mapped_file(line: &str) -> Option<(&str, &str)> {
+    let range = line.split_whitespace().next()?;
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+
+    let path = rest.trim_start();
+    path.starts_with('/').then_some((range, path))
+}
+
+/// Finds a library mapped into a process through `/proc/<pid>/maps`. The paths there
+/// are in the process' mount namespace, e.g.: a container's, so the library is
+/// returned through `/proc/<pid>/root`, or `/proc/<pid>/map_files` if it has been
+/// deleted since.
+fn library_in_process(name: &str, pid: u32) -> Result<Option<PathBuf>, Error> {
+    let maps = read_to_string(format!("/proc/{}/maps", pid))?;
+
+    for (range, path) in maps.lines().filter_map(mapped_file) {
+        let (path, deleted) = match path.strip_suffix(" (deleted)") {
+            Some(path) => (path, true),
+            None => (path, false),
+        };
+
+        let matches = Path::new(path)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .is_some_and(|f| library_matches(f, name));
+        if !matches {
+            continue;
+        }
+
+        return Ok(Some(if deleted {
+            PathBuf::from(format!("/proc/{}/map_files/{}", pid, range))
+        } else {
+            PathBuf::from(format!("/proc/{}/root{}", pid, path))
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Finds a library in `/etc/ld.so.cache`, as the dynamic linker would.
+fn library_in_cache(name: &str) -> Result<Option<PathBuf>, Error> {
+    let data = read(LD_SO_CACHE)?;
+
+    /* old format caches carry the new format after their own entries */
+    let start = match data
+        .windows(LD_SO_CACHE_MAGIC.len())
+        .position(|w| w == LD_SO_CACHE_MAGIC)
+    {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let cache = &data[start..];
+    if cache.len() < LD_SO_CACHE_HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let string_at = |offset: usize| {
+        let bytes = cache.get(offset..)?;
+        let len = bytes.iter().position(|c| *c == 0)?;
+        std::str::from_utf8(&bytes[..len]).ok()
+    };
+
+    let nlibs = LittleEndian::read_u32(&cache[20..]) as usize;
+    for i in 0..nlibs {
+        let offset = LD_SO_CACHE_HEADER_SIZE + i * LD_SO_CACHE_ENTRY_SIZE;
+        let entry = match cache.get(offset..offset + LD_SO_CACHE_ENTRY_SIZE) {
+            Some(entry) => entry,
+            None => break,
+        };
+
+        let flags = LittleEndian::read_u32(&entry[0..]);
+        if flags & 0xff != FLAG_ELF_LIBC6 || flags & 0xff00 != FLAG_X8664_LIB64 {
+            continue;
+        }
+
+        let key = string_at(LittleEndian::read_u32(&entry[4..]) as usize);
+        let value = string_at(LittleEndian::read_u32(&entry[8..]) as usize);
+        if let (Some(key), Some(value)) = (key, value) {
+            if library_matches(key, name) {
+                return Ok(Some(PathBuf::from(value)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolves a library name, e.g.: `libc.so.6`, to its path. With a pid, the library
+/// that process has mapped is preferred, as a path under `/proc/<pid>`; otherwise, or
+/// if it has none, it's looked up like the dynamic linker does: `/etc/ld.so.cache`,
+/// then the default directories.
+///
+/// # Arguments
+///
+/// * `name` - The file name of the library, or a prefix of it up to a `.` or `-`.
+/// * `pid` - The process whose mapped libraries to search first, if any.
+pub fn resolve_library(name: &str, pid: Option<u32>) -> Result<PathBuf, Error> {
+    if name.contains('/') {
+        return Err(Error::InvalidArgument);
+    }
+
+    if let Some(pid) = pid {
+        if let Some(path) = library_in_process(name, pid)? {
+            return Ok(path);
+        }
+    }
+
+    if let Ok(Some(path)) = library_in_cache(name) {
+        return Ok(path);
+    }
+
+    for dir in DEFAULT_LIBRARY_DIRS {
+        let path = Path::new(dir).join(name);
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
+    Err(IoError::new(ErrorKind::NotFound, format!("library not found: {}", name)).into())
+}
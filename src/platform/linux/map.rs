@@ -1,10 +1,11 @@
-use crate::error::Error;
-use crate::platform::linux::bpf::{CallBpf, Command};
-use crate::platform::linux::syscalls::{
-    close, mmap, munmap, MmapFlags, MmapProtection, MAP_FAILED,
+use crate::error::{Error, Operation};
+use crate::platform::linux::bpf::{
+    get_fd_by_id, get_info_by_fd, name_from_bytes, CallBpf, Command, IdIter,
 };
+use crate::platform::linux::syscalls::{cbzero, close, mmap, munmap, MmapFlags, MmapProtection};
 
 use std::collections::HashMap;
+use std::fs::read_to_string;
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::sync::Mutex;
@@ -26,20 +27,56 @@ struct MapOperationAttr {
     pub flags: u64,
 }
 
-impl CallBpf for MapOperationAttr {}
+impl CallBpf for MapOperationAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.map_fd)
+    }
+}
 
 #[derive(Default, Debug)]
 #[repr(C, align(8))]
-struct MapAttr {
+pub(crate) struct MapAttr {
     pub map_type: u32,
     pub key_size: u32,
     pub value_size: u32,
     pub max_entries: u32,
+    pub map_flags: u32,
+    pub inner_map_fd: u32,
+    pub numa_node: u32,
+    pub map_name: [u8; 16],
+    pub map_ifindex: u32,
+    pub btf_fd: u32,
+    pub btf_key_type_id: u32,
+    pub btf_value_type_id: u32,
+    pub btf_vmlinux_value_type_id: u32,
+    pub map_extra: u64,
 }
 
 impl CallBpf for MapAttr {}
 
+#[derive(Default, Debug)]
+#[repr(C, align(8))]
+struct BpfMapInfo {
+    pub map_type: u32,
+    pub id: u32,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    pub map_flags: u32,
+    pub name: [u8; 16],
+    pub ifindex: u32,
+    pub btf_vmlinux_value_type_id: u32,
+    pub netns_dev: u64,
+    pub netns_ino: u64,
+    pub btf_id: u32,
+    pub btf_key_type_id: u32,
+    pub btf_value_type_id: u32,
+    pub btf_vmlinux_id: u32,
+    pub map_extra: u64,
+}
+
 #[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum MapType {
     Unspec = 0,
     Hash,
@@ -72,6 +109,243 @@ pub enum MapType {
     InodeStorage,
     TaskStorage,
     BloomFilter,
+    UserRingBuf,
+    CgrpStorage,
+    Arena,
+}
+
+impl TryFrom<u32> for MapType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        use MapType::*;
+        const TYPES: [MapType; 34] = [
+            Unspec,
+            Hash,
+            Array,
+            ProgArray,
+            PerfEventArray,
+            PerCpuHash,
+            PerCpuArray,
+            StackTrace,
+            CgroupArray,
+            LruHash,
+            LruPerCpuHash,
+            LpmTrie,
+            ArrayOfMaps,
+            HashOfMaps,
+            DevMap,
+            SockMap,
+            CpuMap,
+            XSkMap,
+            SockHash,
+            CgroupStorage,
+            ReusePortSockArray,
+            PerCpuCgroupStorage,
+            Queue,
+            Stack,
+            SkStorage,
+            DevMapHash,
+            StructOps,
+            RingBuf,
+            InodeStorage,
+            TaskStorage,
+            BloomFilter,
+            UserRingBuf,
+            CgrpStorage,
+            Arena,
+        ];
+
+        TYPES.get(value as usize).copied().ok_or(Error::OutOfRange)
+    }
+}
+
+/// Information about a map as reported by the kernel.
+#[derive(Clone, Debug)]
+pub struct MapInfo {
+    /// The type of map.
+    pub map_type: MapType,
+
+    /// The kernel id of the map.
+    pub id: u32,
+
+    /// The size of a key, in bytes.
+    pub key_size: u32,
+
+    /// The size of a value, in bytes.
+    pub value_size: u32,
+
+    /// The maximum number of entries in the map.
+    pub max_entries: u32,
+
+    /// The flags the map was created with.
+    pub flags: u32,
+
+    /// The name of the map; empty if it was created without one.
+    pub name: String,
+
+    /// The id of the BTF object describing the map, 0 if there is none.
+    pub btf_id: u32,
+
+    /// The BTF type id of the key within `btf_id`.
+    pub btf_key_type_id: u32,
+
+    /// The BTF type id of the value within `btf_id`.
+    pub btf_value_type_id: u32,
+
+    /// The amount of memory charged to the map, in bytes.
+    pub memlock: u64,
+}
+
+/// Map creation flags, for `MapDefinition::flags`.
+pub mod map_flags {
+    pub const NO_PREALLOC: u32 = 1 << 0;
+    pub const NO_COMMON_LRU: u32 = 1 << 1;
+    pub const NUMA_NODE: u32 = 1 << 2;
+    pub const RDONLY: u32 = 1 << 3;
+    pub const WRONLY: u32 = 1 << 4;
+    pub const STACK_BUILD_ID: u32 = 1 << 5;
+    pub const ZERO_SEED: u32 = 1 << 6;
+    pub const RDONLY_PROG: u32 = 1 << 7;
+    pub const WRONLY_PROG: u32 = 1 << 8;
+    pub const CLONE: u32 = 1 << 9;
+    pub const MMAPABLE: u32 = 1 << 10;
+    pub const PRESERVE_ELEMS: u32 = 1 << 11;
+    pub const INNER_MAP: u32 = 1 << 12;
+}
+
+/// Describes a map whose key and value sizes are only known at run-time, e.g.: a map
+/// defined in an object file.
+#[derive(Clone, Debug)]
+pub struct MapDefinition {
+    /// The name of the map; only the first 15 characters are kept by the kernel.
+    pub name: String,
+
+    /// The type of map.
+    pub map_type: MapType,
+
+    /// The size of a key, in bytes.
+    pub key_size: u32,
+
+    /// The size of a value, in bytes.
+    pub value_size: u32,
+
+    /// The maximum number of entries in the map.
+    pub max_entries: u32,
+
+    /// Map creation flags, see `map_flags`.
+    pub flags: u32,
+}
+
+/// Creates a map from a definition, returning the new map's fd.
+pub(crate) fn create_map(def: &MapDefinition) -> Result<u32, Error> {
+    let mut map_name = [0u8; 16];
+    for (i, c) in def.name.bytes().take(15).enumerate() {
+        map_name[i] = c;
+    }
+
+    let attr = MapAttr {
+        map_type: def.map_type as u32,
+        key_size: def.key_size,
+        value_size: def.value_size,
+        max_entries: def.max_entries,
+        map_flags: def.flags,
+        map_name,
+        ..Default::default()
+    };
+
+    attr.call_bpf(Command::MapCreate)
+}
+
+/// A map that is owned by fd alone, without key and value types, e.g.: the maps
+/// created while loading an object file.
+pub(crate) struct RawMap {
+    fd: u32,
+    mapping: Option<(usize, usize)>,
+}
+
+impl RawMap {
+    pub(crate) fn create(def: &MapDefinition) -> Result<Self, Error> {
+        Ok(Self {
+            fd: create_map(def)?,
+            mapping: None,
+        })
+    }
+
+    pub(crate) fn fd(&self) -> u32 {
+        self.fd
+    }
+
+    pub(crate) fn id(&self) -> Result<u32, Error> {
+        let mut info = BpfMapInfo::default();
+        get_info_by_fd(self.fd, &mut info)?;
+        Ok(info.id)
+    }
+
+    /// Sets the value at `key`; both have to be exactly the map's key and value sizes.
+    pub(crate) fn update(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let attr = MapOperationAttr {
+            map_fd: self.fd,
+            key: key.as_ptr() as u64,
+            val: value.as_ptr() as u64,
+            flags: MapLookupFlags::Any as u64,
+        };
+
+        attr.call_bpf(Command::MapUpdateElem)?;
+        Ok(())
+    }
+
+    /// Makes the map read-only for user space; programs can still write to it unless
+    /// it was created with `BPF_F_RDONLY_PROG`.
+    pub(crate) fn freeze(&self) -> Result<(), Error> {
+        /* only map_fd is used, the padding after it has to be zero too */
+        let mut attr = MapOperationAttr::default();
+        cbzero(&mut attr);
+        attr.map_fd = self.fd;
+
+        attr.call_bpf(Command::MapFreeze)?;
+        Ok(())
+    }
+
+    /// Maps the first `length` bytes of an mmapable map, which stay mapped until the
+    /// map is dropped. Frozen maps can only be mapped read-only.
+    pub(crate) fn mmap(&mut self, length: usize, writable: bool) -> Result<*mut u8, Error> {
+        if let Some((buf, _)) = self.mapping {
+            return Ok(buf as *mut u8);
+        }
+
+        let prot = if writable {
+            MmapProtection::Write
+        } else {
+            MmapProtection::Read
+        };
+
+        let buf = mmap(
+            0,
+            length,
+            prot as usize,
+            MmapFlags::Shared as usize,
+            self.fd.try_into()?,
+            0,
+        );
+
+        /* the raw syscall returns -errno on failure */
+        if (-4095..0).contains(&buf) {
+            return Err(Error::system(Operation::Mmap { fd: self.fd }, buf));
+        }
+
+        self.mapping = Some((buf as usize, length));
+        Ok(buf as *mut u8)
+    }
+}
+
+impl Drop for RawMap {
+    fn drop(&mut self) {
+        if let Some((buf, length)) = self.mapping {
+            munmap(buf, length);
+        }
+        close(self.fd);
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -97,14 +371,16 @@ impl<K: Copy + Default, V: Copy + Default> Map<K, V> {
     /// * `map_type` - The type of BPF map to create.
     /// * `max_entries` - The number of entries in the map.
     pub fn with_capacity(map_type: MapType, max_entries: u32) -> Result<Self, Error> {
-        let attr = MapAttr {
-            map_type: map_type as u32,
+        let def = MapDefinition {
+            name: String::new(),
+            map_type,
             key_size: size_of::<K>() as u32,
             value_size: size_of::<V>() as u32,
             max_entries,
+            flags: 0,
         };
 
-        match attr.call_bpf(Command::MapCreate) {
+        match create_map(&def) {
             Err(e) => Err(e),
             Ok(fd) => Ok(Self {
                 fd,
@@ -115,6 +391,47 @@ impl<K: Copy + Default, V: Copy + Default> Map<K, V> {
         }
     }
 
+    /// Opens an existing map, possibly created by another process, by its kernel id.
+    /// The map's key and value sizes must match the sizes of `K` and `V`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The kernel id of the map, e.g.: as returned by `map_ids()`.
+    pub fn from_id(id: u32) -> Result<Self, Error> {
+        let map = Self {
+            fd: get_fd_by_id(Command::MapGetFdById, id)?,
+            mapped_areas: Default::default(),
+            phantom1: PhantomData::<K>,
+            phantom2: PhantomData::<V>,
+        };
+
+        let info = map.get_raw_info()?;
+        if info.key_size as usize != size_of::<K>() || info.value_size as usize != size_of::<V>() {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(map)
+    }
+
+    /// Queries the kernel for information about the map.
+    pub fn info(&self) -> Result<MapInfo, Error> {
+        let info = self.get_raw_info()?;
+
+        Ok(MapInfo {
+            map_type: MapType::try_from(info.map_type)?,
+            id: info.id,
+            key_size: info.key_size,
+            value_size: info.value_size,
+            max_entries: info.max_entries,
+            flags: info.map_flags,
+            name: name_from_bytes(&info.name),
+            btf_id: info.btf_id,
+            btf_key_type_id: info.btf_key_type_id,
+            btf_value_type_id: info.btf_value_type_id,
+            memlock: self.get_memlock()?,
+        })
+    }
+
     /// Gets an entry from the map by key, in the case of a hash this is the hash key,
     /// for arrays, this is an index, for stacks/queues, this is null.
     ///
@@ -250,8 +567,9 @@ impl<K: Copy + Default, V: Copy + Default> Map<K, V> {
             offset,
         );
 
-        if buf == MAP_FAILED {
-            return Err(Error::SystemError(buf));
+        /* the raw syscall returns -errno on failure */
+        if (-4095..0).contains(&buf) {
+            return Err(Error::system(Operation::Mmap { fd: self.fd }, buf));
         }
 
         mapped_areas.insert(mapped_area, buf as usize);
@@ -286,13 +604,50 @@ impl<K: Copy + Default, V: Copy + Default> Map<K, V> {
             offset,
         );
 
-        if buf == MAP_FAILED {
-            return Err(Error::SystemError(buf));
+        /* the raw syscall returns -errno on failure */
+        if (-4095..0).contains(&buf) {
+            return Err(Error::system(Operation::Mmap { fd: self.fd }, buf));
         }
 
         mapped_areas.insert(mapped_area, buf as usize);
         Ok(unsafe { std::slice::from_raw_parts_mut(buf as *mut T, count) })
     }
+
+    fn get_raw_info(&self) -> Result<BpfMapInfo, Error> {
+        let mut info = BpfMapInfo::default();
+        get_info_by_fd(self.fd, &mut info)?;
+        Ok(info)
+    }
+
+    /// The memory charged to a map isn't part of `bpf_map_info`, the kernel only
+    /// exposes it through the fd's fdinfo.
+    fn get_memlock(&self) -> Result<u64, Error> {
+        let fdinfo = read_to_string(format!("/proc/self/fdinfo/{}", self.fd))?;
+        for line in fdinfo.lines() {
+            if let Some(value) = line.strip_prefix("memlock:") {
+                return Ok(value.trim().parse::<u64>()?);
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+/// Returns an iterator over the ids of all maps loaded in the kernel.
+pub fn map_ids() -> IdIter {
+    IdIter::new(Command::MapGetNextId)
+}
+
+/// A map that programs can refer to by fd, e.g.: a `Map` or one of the collections.
+pub trait MapFd {
+    /// The fd of the underlying map, see `get_identifier()`.
+    fn map_fd(&self) -> u32;
+}
+
+impl<K: Copy + Default, V: Copy + Default> MapFd for Map<K, V> {
+    fn map_fd(&self) -> u32 {
+        self.fd
+    }
 }
 
 impl<K: Copy + Default, V: Copy + Default> Drop for Map<K, V> {
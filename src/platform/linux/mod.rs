@@ -1,8 +1,14 @@
 pub mod bpf;
+pub mod btf;
+pub mod features;
+pub mod libs;
 pub mod map;
 pub mod perf;
+pub mod preflight;
 pub mod probes;
 pub mod prog;
+pub mod stats;
+pub mod test_run;
 
 #[cfg(target_arch = "x86_64")]
 mod x86_64;
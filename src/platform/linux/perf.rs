@@ -1,17 +1,32 @@
 use super::syscalls::{
-    cbzero, perf_event_attach as arch_perf_event_attach,
+    cbzero, close, perf_event_attach as arch_perf_event_attach,
     perf_event_enable as arch_perf_event_enable, perf_event_open,
 };
 
-use crate::error::Error;
+use crate::error::{Error, Operation};
 
 use num_cpus;
 
-use std::fs::read_to_string;
+use std::fs::{read_to_string, OpenOptions};
+use std::io::Write;
 use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const DYNAMIC_PMU_PATH_KPROBE: &str = "/sys/bus/event_source/devices/";
 
+/* tracefs, then where it lives under debugfs on older kernels */
+const TRACEFS_PATHS: [&str; 2] = ["/sys/kernel/tracing/", "/sys/kernel/debug/tracing/"];
+
+const PERF_TYPE_TRACEPOINT: u32 = 2;
+
+/* PerfEventAttr::flags: children of the task get their own copy of the event */
+const PERF_FLAG_INHERIT: u64 = 1 << 1;
+
+/* the tracefs group of the kretprobes added through kprobe_events */
+const PROBE_EVENT_GROUP: &str = "bpf_api";
+
+static PROBE_EVENT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 pub fn get_pmu_typeid(name: &str) -> Result<u32, Error> {
     if name.contains('/') {
         return Err(Error::InvalidArgument);
@@ -25,6 +40,64 @@ pub fn get_pmu_typeid(name: &str) -> Result<u32, Error> {
     Ok(s.trim_end().parse::<u32>()?)
 }
 
+/// Gets the bit of `config` that makes a kprobe or uprobe PMU event a return probe,
+/// from e.g.: "config:0" in its `format/retprobe`.
+pub fn get_pmu_retprobe_bit(name: &str) -> Result<u32, Error> {
+    if name.contains('/') {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut path = String::from(DYNAMIC_PMU_PATH_KPROBE);
+    path.push_str(name);
+    path.push_str("/format/retprobe");
+
+    let s = read_to_string(path)?;
+    let bit = s
+        .trim_end()
+        .strip_prefix("config:")
+        .ok_or(Error::NotImplemented)?;
+    Ok(bit.parse::<u32>()?)
+}
+
+/// Appends a line to a file at the root of tracefs, e.g.: `kprobe_events`.
+fn write_tracefs(file: &str, line: &str) -> Result<(), Error> {
+    let mut last_error = None;
+    for tracefs in TRACEFS_PATHS {
+        let path = format!("{}{}", tracefs, file);
+        match OpenOptions::new().append(true).open(path) {
+            Ok(mut f) => return Ok(f.write_all(line.as_bytes())?),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap().into())
+}
+
+/// Reads a file under `events/` of tracefs, trying the debugfs mount if tracefs
+/// isn't mounted at its usual place.
+pub(crate) fn read_tracefs_event(category: &str, name: &str, file: &str) -> Result<String, Error> {
+    if category.contains('/') || name.contains('/') {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut last_error = None;
+    for tracefs in TRACEFS_PATHS {
+        let path = format!("{}events/{}/{}/{}", tracefs, category, name, file);
+        match read_to_string(path) {
+            Ok(s) => return Ok(s),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap().into())
+}
+
+/// Gets the id perf uses for the tracepoint `category:name`.
+pub fn get_tracepoint_id(category: &str, name: &str) -> Result<u32, Error> {
+    let s = read_tracefs_event(category, name, "id")?;
+    Ok(s.trim_end().parse::<u32>()?)
+}
+
 #[derive(Default)]
 #[repr(C, align(8))]
 struct PerfEventAttr {
@@ -49,7 +122,16 @@ struct PerfEventAttr {
     pub reserved: u16,
 }
 
-pub fn perf_event_open_by_name(kind: &str, name: &str, addr: u64) -> Result<Vec<u32>, Error> {
+/// Opens a kprobe or uprobe PMU event, on every CPU or, given a pid, for that
+/// process (and, with `inherit`, the processes it creates afterwards).
+pub fn perf_event_open_by_name(
+    kind: &str,
+    name: &str,
+    addr: u64,
+    retprobe: bool,
+    pid: Option<u32>,
+    inherit: bool,
+) -> Result<Vec<u32>, Error> {
     let mut attr = PerfEventAttr::default();
     cbzero(&mut attr);
 
@@ -60,18 +142,95 @@ pub fn perf_event_open_by_name(kind: &str, name: &str, addr: u64) -> Result<Vec<
     attr.size = size_of::<PerfEventAttr>() as u32;
     attr.probe_name = probe_name.as_ptr() as u64;
     attr.probe_addr = addr;
+    if retprobe {
+        attr.config |= 1 << get_pmu_retprobe_bit(kind)?;
+    }
+    if inherit {
+        attr.flags |= PERF_FLAG_INHERIT;
+    }
+
+    match pid {
+        Some(pid) => perf_event_open_for_pid(&attr, name, pid),
+        None => perf_event_open_per_cpu(&attr, name),
+    }
+}
+
+/// Adds a kretprobe on `function` through `kprobe_events` of tracefs, which unlike
+/// the kprobe PMU takes the number of instances that can be probed at once, and
+/// opens its tracepoint on every CPU. Returns the event, to be removed with
+/// `remove_kretprobe_event` once the fds are closed.
+pub fn perf_event_open_kretprobe_event(
+    function: &str,
+    maxactive: u32,
+) -> Result<(String, Vec<u32>), Error> {
+    if function.contains(char::is_whitespace) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let event = format!(
+        "kretprobe_{}_{}",
+        std::process::id(),
+        PROBE_EVENT_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    write_tracefs(
+        "kprobe_events",
+        &format!(
+            "r{}:{}/{} {}\n",
+            maxactive, PROBE_EVENT_GROUP, event, function
+        ),
+    )?;
+
+    match perf_event_open_tracepoint(PROBE_EVENT_GROUP, &event) {
+        Ok(fds) => Ok((event, fds)),
+        Err(e) => {
+            let _ = remove_kretprobe_event(&event);
+            Err(e)
+        }
+    }
+}
+
+/// Removes a kretprobe added by `perf_event_open_kretprobe_event`.
+pub fn remove_kretprobe_event(event: &str) -> Result<(), Error> {
+    write_tracefs(
+        "kprobe_events",
+        &format!("-:{}/{}\n", PROBE_EVENT_GROUP, event),
+    )
+}
+
+/// Opens a perf event on every CPU for the tracepoint `category:name`.
+pub fn perf_event_open_tracepoint(category: &str, name: &str) -> Result<Vec<u32>, Error> {
+    let mut attr = PerfEventAttr::default();
+    cbzero(&mut attr);
+
+    attr.event_type = PERF_TYPE_TRACEPOINT;
+    attr.size = size_of::<PerfEventAttr>() as u32;
+    attr.config = get_tracepoint_id(category, name)? as u64;
+
+    perf_event_open_per_cpu(&attr, &format!("{}:{}", category, name))
+}
 
+fn perf_event_open_per_cpu(attr: &PerfEventAttr, name: &str) -> Result<Vec<u32>, Error> {
     let mut fds = vec![];
     for i in 0..num_cpus::get() {
         let r = perf_event_open(
-            &attr as *const _ as *const u8,
+            attr as *const _ as *const u8,
             u32::MAX,
             i as u32,
             u32::MAX,
             0,
         );
         if r < 0 {
-            return Err(Error::SystemError(r));
+            for fd in fds {
+                close(fd);
+            }
+            return Err(Error::system(
+                Operation::PerfEventOpen {
+                    name: name.to_string(),
+                    cpu: i as u32,
+                    pid: None,
+                },
+                r,
+            ));
         } else {
             fds.push(r as u32);
         }
@@ -80,13 +239,36 @@ pub fn perf_event_open_by_name(kind: &str, name: &str, addr: u64) -> Result<Vec<
     Ok(fds)
 }
 
+/* cpu -1: the event follows the process to whichever CPU it runs on */
+fn perf_event_open_for_pid(attr: &PerfEventAttr, name: &str, pid: u32) -> Result<Vec<u32>, Error> {
+    let r = perf_event_open(attr as *const _ as *const u8, pid, u32::MAX, u32::MAX, 0);
+    if r < 0 {
+        return Err(Error::system(
+            Operation::PerfEventOpen {
+                name: name.to_string(),
+                cpu: u32::MAX,
+                pid: Some(pid),
+            },
+            r,
+        ));
+    }
+
+    Ok(vec![r as u32])
+}
+
 /*
  * ioctl(probe_fd, PERF_EVENT_IOC_SET_BPF, prog_fd)
  */
 pub fn perf_event_attach(probe_fd: u32, prog_fd: u32) -> Result<(), Error> {
     match arch_perf_event_attach(probe_fd, prog_fd) {
         0 => Ok(()),
-        n => Err(Error::SystemError(n)),
+        n => Err(Error::system(
+            Operation::Ioctl {
+                request: "PERF_EVENT_IOC_SET_BPF",
+                fd: probe_fd,
+            },
+            n,
+        )),
     }
 }
 
@@ -96,6 +278,12 @@ pub fn perf_event_attach(probe_fd: u32, prog_fd: u32) -> Result<(), Error> {
 pub fn perf_event_enable(probe_fd: u32) -> Result<(), Error> {
     match arch_perf_event_enable(probe_fd) {
         0 => Ok(()),
-        n => Err(Error::SystemError(n)),
+        n => Err(Error::system(
+            Operation::Ioctl {
+                request: "PERF_EVENT_IOC_ENABLE",
+                fd: probe_fd,
+            },
+            n,
+        )),
     }
 }
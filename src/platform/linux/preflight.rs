@@ -0,0 +1,231 @@
+use crate::error::{Error, Operation};
+use crate::platform::linux::features::helper_supported;
+use crate::platform::linux::prog::ProgramType;
+use crate::platform::linux::syscalls::prlimit;
+
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_SYS_ADMIN: u32 = 21;
+const CAP_PERFMON: u32 = 38;
+const CAP_BPF: u32 = 39;
+
+const RLIMIT_MEMLOCK: u32 = 8;
+const RLIM_INFINITY: u64 = u64::MAX;
+
+/* bpf_ktime_get_coarse_ns came with memcg accounting of BPF memory in 5.11 */
+const BPF_FUNC_KTIME_GET_COARSE_NS: u32 = 160;
+
+/// The capabilities that matter for BPF, from the calling thread's effective set.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    /// `CAP_BPF`: loading programs and creating maps, since Linux 5.8.
+    pub bpf: bool,
+
+    /// `CAP_PERFMON`: tracing programs and perf events, since Linux 5.8.
+    pub perfmon: bool,
+
+    /// `CAP_SYS_ADMIN`: everything, and what older kernels require.
+    pub sys_admin: bool,
+
+    /// `CAP_NET_ADMIN`: networking programs, e.g.: XDP and tc.
+    pub net_admin: bool,
+}
+
+/// The kernel lockdown mode, which restricts what BPF programs can read.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Lockdown {
+    None,
+
+    /// Programs can't write to kernel memory.
+    Integrity,
+
+    /// Programs can't read kernel memory either, e.g.: `bpf_probe_read_kernel`.
+    Confidentiality,
+}
+
+/// A resource limit; `u64::MAX` is unlimited.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Rlimit {
+    pub cur: u64,
+    pub max: u64,
+}
+
+/// A report of the privileges and resources that loading programs depends on, for
+/// explaining failures before they happen.
+#[derive(Clone, Debug)]
+pub struct Preflight {
+    /// The effective capabilities of the calling thread.
+    pub capabilities: Capabilities,
+
+    /// `kernel.unprivileged_bpf_disabled`: 0 lets unprivileged users load socket
+    /// filters, 1 and 2 don't. `None` if it couldn't be read.
+    pub unprivileged_bpf_disabled: Option<u32>,
+
+    /// The lockdown mode; `None` if securityfs isn't mounted or the kernel has no
+    /// lockdown support.
+    pub lockdown: Option<Lockdown>,
+
+    /// `RLIMIT_MEMLOCK` of the process.
+    pub memlock: Rlimit,
+
+    /// Whether BPF memory is charged against `RLIMIT_MEMLOCK`, as it was before
+    /// Linux 5.11; later kernels charge the memory cgroup instead. Telling them
+    /// apart takes loading a socket filter, so this is `None` when that isn't
+    /// allowed, e.g.: without privileges.
+    pub memlock_charged: Option<bool>,
+
+    /// Where the BPF filesystem is mounted, for pinning.
+    pub bpffs: Option<PathBuf>,
+
+    /// Where tracefs is mounted, for tracepoints and probe events.
+    pub tracefs: Option<PathBuf>,
+
+    /// Where debugfs is mounted; older kernels only have tracefs under it.
+    pub debugfs: Option<PathBuf>,
+}
+
+impl Preflight {
+    /// Gathers the report. Nothing is changed, see `raise_memlock_rlimit` for that,
+    /// though a socket filter is loaded and closed again to probe the kernel.
+    pub fn check() -> Result<Self, Error> {
+        let mounts = read_to_string("/proc/self/mounts")?;
+        let mount_point = |fs_type: &str| {
+            mounts.lines().find_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (point, ty) = (fields.nth(1)?, fields.next()?);
+                (ty == fs_type).then(|| PathBuf::from(point))
+            })
+        };
+
+        Ok(Self {
+            capabilities: Self::capabilities()?,
+            unprivileged_bpf_disabled: read_to_string("/proc/sys/kernel/unprivileged_bpf_disabled")
+                .ok()
+                .and_then(|s| s.trim().parse().ok()),
+            lockdown: Self::lockdown(),
+            memlock: memlock_rlimit()?,
+            memlock_charged: memlock_charged(),
+            bpffs: mount_point("bpf"),
+            tracefs: mount_point("tracefs"),
+            debugfs: mount_point("debugfs"),
+        })
+    }
+
+    /// Describes what in the report is likely to make loading or attaching fail.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let caps = &self.capabilities;
+
+        if !caps.bpf && !caps.sys_admin {
+            let unprivileged = match self.unprivileged_bpf_disabled {
+                Some(0) => "only socket filters can be loaded",
+                _ => "no programs can be loaded",
+            };
+            problems.push(format!(
+                "missing CAP_BPF and CAP_SYS_ADMIN: {}",
+                unprivileged
+            ));
+        }
+        if !caps.perfmon && !caps.sys_admin {
+            problems.push("missing CAP_PERFMON: tracing programs can't be loaded".into());
+        }
+        if !caps.net_admin && !caps.sys_admin {
+            problems.push("missing CAP_NET_ADMIN: networking programs can't be loaded".into());
+        }
+        if self.lockdown == Some(Lockdown::Confidentiality) {
+            problems.push("kernel lockdown: programs can't read kernel memory".into());
+        }
+        if self.memlock_charged == Some(true) && self.memlock.cur != RLIM_INFINITY {
+            problems.push(format!(
+                "RLIMIT_MEMLOCK is {} bytes and this kernel charges BPF memory to it",
+                self.memlock.cur
+            ));
+        }
+        if self.tracefs.is_none() && self.debugfs.is_none() {
+            problems.push("neither tracefs nor debugfs is mounted".into());
+        }
+
+        problems
+    }
+
+    fn capabilities() -> Result<Capabilities, Error> {
+        /* capabilities are per thread, /proc/self is the main thread's */
+        let status = read_to_string("/proc/thread-self/status")?;
+        let effective = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .unwrap_or("0");
+        let effective = u64::from_str_radix(effective.trim(), 16)?;
+
+        let has = |cap: u32| effective & (1 << cap) != 0;
+        Ok(Capabilities {
+            bpf: has(CAP_BPF),
+            perfmon: has(CAP_PERFMON),
+            sys_admin: has(CAP_SYS_ADMIN),
+            net_admin: has(CAP_NET_ADMIN),
+        })
+    }
+
+    /// Reads e.g.: "none [integrity] confidentiality", the active mode in brackets.
+    fn lockdown() -> Option<Lockdown> {
+        let modes = read_to_string("/sys/kernel/security/lockdown").ok()?;
+        let start = modes.find('[')?;
+        let end = modes[start..].find(']')? + start;
+        match &modes[start + 1..end] {
+            "none" => Some(Lockdown::None),
+            "integrity" => Some(Lockdown::Integrity),
+            "confidentiality" => Some(Lockdown::Confidentiality),
+            _ => None,
+        }
+    }
+}
+
+fn memlock_rlimit() -> Result<Rlimit, Error> {
+    let mut limit = [0u64; 2];
+    let r = prlimit(RLIMIT_MEMLOCK, std::ptr::null(), limit.as_mut_ptr());
+    if r < 0 {
+        return Err(Error::system(
+            Operation::Prlimit {
+                resource: "RLIMIT_MEMLOCK",
+            },
+            r,
+        ));
+    }
+
+    Ok(Rlimit {
+        cur: limit[0],
+        max: limit[1],
+    })
+}
+
+/// Guesses whether BPF memory counts against `RLIMIT_MEMLOCK`, or `None` if the
+/// probe program can't be loaded.
+fn memlock_charged() -> Option<bool> {
+    helper_supported(ProgramType::SocketFilter, BPF_FUNC_KTIME_GET_COARSE_NS)
+        .ok()
+        .map(|supported| !supported)
+}
+
+/// Raises `RLIMIT_MEMLOCK` to unlimited if the kernel charges BPF memory to it, so
+/// maps and programs beyond the (often 64 KiB) default can be created, or if that
+/// can't be told. Returns whether the limit was raised.
+pub fn raise_memlock_rlimit() -> Result<bool, Error> {
+    if memlock_charged() == Some(false) || memlock_rlimit()?.cur == RLIM_INFINITY {
+        return Ok(false);
+    }
+
+    let limit = [RLIM_INFINITY; 2];
+    let r = prlimit(RLIMIT_MEMLOCK, limit.as_ptr(), std::ptr::null_mut());
+    if r < 0 {
+        return Err(Error::system(
+            Operation::Prlimit {
+                resource: "RLIMIT_MEMLOCK",
+            },
+            r,
+        ));
+    }
+
+    Ok(true)
+}
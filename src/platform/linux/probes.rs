@@ -1,10 +1,16 @@
 use crate::error::Error;
+use crate::object::Elf;
 use crate::platform::linux::bpf::{CallBpf, Command};
-use crate::platform::linux::perf::{perf_event_attach, perf_event_enable, perf_event_open_by_name};
+use crate::platform::linux::libs::resolve_library;
+use crate::platform::linux::perf::{
+    perf_event_attach, perf_event_enable, perf_event_open_by_name, perf_event_open_kretprobe_event,
+    perf_event_open_tracepoint, remove_kretprobe_event,
+};
 use crate::platform::linux::prog::Program;
 use crate::platform::linux::syscalls::{cbzero, close};
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Default)]
 #[repr(C, align(8))]
@@ -13,7 +19,11 @@ struct BpfRawTracepointOpenAttr {
     pub prog_fd: u32,
 }
 
-impl CallBpf for BpfRawTracepointOpenAttr {}
+impl CallBpf for BpfRawTracepointOpenAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.prog_fd)
+    }
+}
 
 #[derive(Default)]
 #[repr(C, align(8))]
@@ -25,18 +35,158 @@ struct BpfLinkCreateAttr {
     pub target_btf_id: u32,
 }
 
-impl CallBpf for BpfLinkCreateAttr {}
+impl CallBpf for BpfLinkCreateAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.prog_fd)
+    }
+}
 
 #[derive(Clone)]
 pub enum AttachInfo {
     RawTracepoint(String),
+
+    /// A tracepoint under `events/` of tracefs, e.g.: `syscalls`/`sys_enter_openat`,
+    /// for `ProgramType::Tracepoint` programs.
+    Tracepoint {
+        category: String,
+        name: String,
+    },
+
     KProbe((String, u64)),
     UProbe((String, u64)),
+
+    /// A kprobe on the return of the named function. `maxactive` is how many calls
+    /// can be probed at once, returns beyond it are missed; the kernel picks one by
+    /// the number of CPUs if it isn't given. Setting it needs `kprobe_events` of
+    /// tracefs, the kprobe PMU doesn't take it.
+    KRetProbe {
+        name: String,
+        maxactive: Option<u32>,
+    },
+
+    /// A uprobe on the return of the function at an offset in a file.
+    URetProbe((String, u64)),
+
+    /// A uprobe, or a uretprobe with `retprobe`, optionally limited to a process.
+    UserProbe {
+        uprobe: UProbe,
+        retprobe: bool,
+    },
+
+    /// Replaces the function an `Ext` program was loaded against, until detached.
+    Extension,
+}
+
+/// A uprobe location: a file and an offset in it, and optionally the process the
+/// probe is limited to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UProbe {
+    pub path: String,
+    pub offset: u64,
+
+    /// The process to probe; every process mapping the file if `None`. The kernel
+    /// runs programs on uprobes by address space, so this takes in all its threads.
+    pub pid: Option<u32>,
+
+    /// Whether processes the probed one creates after attaching are probed too.
+    pub inherit: bool,
+}
+
+impl UProbe {
+    /// Resolves a function in an executable or shared library to the file offset
+    /// the uprobe goes at, from the static or dynamic symbol table. The symbol may
+    /// name a version, e.g.: `malloc@GLIBC_2.2.5`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The executable or library; a library name without a `/`, e.g.:
+    ///   `libc.so.6`, is looked up with `resolve_library`.
+    /// * `symbol` - The function.
+    /// * `offset` - An offset into the function, 0 for its entry.
+    pub fn by_symbol(path: &str, symbol: &str, offset: u64) -> Result<Self, Error> {
+        Self::resolve(path, Some(symbol), offset, None)
+    }
+
+    /// As `by_symbol`, probing only the given process, and looking a library name
+    /// up in the libraries it has mapped first.
+    pub fn by_symbol_in_process(
+        pid: u32,
+        path: &str,
+        symbol: &str,
+        offset: u64,
+    ) -> Result<Self, Error> {
+        Self::resolve(path, Some(symbol), offset, Some(pid))
+    }
+
+    /// As `by_symbol`, at the entry point of an executable rather than a function.
+    pub fn by_entry(path: &str) -> Result<Self, Error> {
+        Self::resolve(path, None, 0, None)
+    }
+
+    /// As `by_symbol_in_process`, at the entry point of an executable rather than a
+    /// function.
+    pub fn by_entry_in_process(pid: u32, path: &str) -> Result<Self, Error> {
+        Self::resolve(path, None, 0, Some(pid))
+    }
+
+    fn resolve(
+        path: &str,
+        symbol: Option<&str>,
+        offset: u64,
+        pid: Option<u32>,
+    ) -> Result<Self, Error> {
+        let path = if path.contains('/') {
+            PathBuf::from(path)
+        } else {
+            resolve_library(path, pid)?
+        };
+
+        let data = std::fs::read(&path)?;
+        let elf = Elf::parse(&data)?;
+
+        let function = match symbol {
+            Some(symbol) => elf.function_file_offset(symbol)?,
+            None => elf
+                .file_offset(elf.e_entry)
+                .ok_or_else(|| Error::SymbolNotFound("entry point".into()))?,
+        };
+
+        Ok(Self {
+            path: path.to_string_lossy().into_owned(),
+            offset: function.checked_add(offset).ok_or(Error::OutOfRange)?,
+            pid,
+            inherit: false,
+        })
+    }
+
+    /// Sets whether processes the probed one creates are probed too.
+    pub fn inherit(mut self, inherit: bool) -> Self {
+        self.inherit = inherit;
+        self
+    }
+
+    /// Makes a probe on the return of the function rather than the offset.
+    pub fn retprobe(self) -> AttachInfo {
+        AttachInfo::UserProbe {
+            uprobe: self,
+            retprobe: true,
+        }
+    }
+}
+
+impl From<UProbe> for AttachInfo {
+    fn from(uprobe: UProbe) -> Self {
+        AttachInfo::UserProbe {
+            uprobe,
+            retprobe: false,
+        }
+    }
 }
 
 pub struct Probe {
     attach_info: AttachInfo,
     attach_fds: HashMap<u32, Vec<u32>>,
+    kretprobe_events: HashMap<u32, Vec<String>>,
 }
 
 impl Probe {
@@ -49,6 +199,7 @@ impl Probe {
         Self {
             attach_info,
             attach_fds: HashMap::new(),
+            kretprobe_events: HashMap::new(),
         }
     }
 
@@ -57,31 +208,111 @@ impl Probe {
         let attach_info = self.attach_info.clone();
         match &attach_info {
             AttachInfo::RawTracepoint(name) => self.attach_raw_tracepoint(program, name),
-            AttachInfo::KProbe((name, addr)) => self.attach_probe(program, "kprobe", name, *addr),
-            AttachInfo::UProbe((name, addr)) => self.attach_probe(program, "uprobe", name, *addr),
+            AttachInfo::Tracepoint { category, name } => {
+                self.attach_perf_events(program, perf_event_open_tracepoint(category, name)?)
+            }
+            AttachInfo::KProbe((name, addr)) => {
+                self.attach_probe(program, "kprobe", name, *addr, false)
+            }
+            AttachInfo::UProbe((name, addr)) => {
+                self.attach_probe(program, "uprobe", name, *addr, false)
+            }
+            AttachInfo::KRetProbe {
+                name,
+                maxactive: None,
+            } => self.attach_probe(program, "kprobe", name, 0, true),
+            AttachInfo::KRetProbe {
+                name,
+                maxactive: Some(maxactive),
+            } => self.attach_kretprobe_event(program, name, *maxactive),
+            AttachInfo::URetProbe((name, addr)) => {
+                self.attach_probe(program, "uprobe", name, *addr, true)
+            }
+            AttachInfo::UserProbe { uprobe, retprobe } => {
+                let fds = perf_event_open_by_name(
+                    "uprobe",
+                    &uprobe.path,
+                    uprobe.offset,
+                    *retprobe,
+                    uprobe.pid,
+                    uprobe.inherit,
+                )?;
+                self.attach_perf_events(program, fds)
+            }
+            AttachInfo::Extension => self.attach_link(program),
         }
     }
 
+    fn attach_link(&mut self, program: &Program) -> Result<(), Error> {
+        let mut bpf_attr = BpfLinkCreateAttr::default();
+        cbzero(&mut bpf_attr);
+
+        /* a zero target_fd and target_btf_id keep the target given at load time */
+        bpf_attr.prog_fd = program.get_fd();
+
+        let fds = vec![bpf_attr.call_bpf(Command::LinkCreate)?];
+        self.add_attach_fds(program, fds);
+
+        Ok(())
+    }
+
     fn attach_probe(
         &mut self,
         program: &Program,
         probe_name: &str,
         name: &str,
         addr: u64,
+        retprobe: bool,
     ) -> Result<(), Error> {
-        let perf_event_fds = perf_event_open_by_name(probe_name, name, addr)?;
+        let perf_event_fds =
+            perf_event_open_by_name(probe_name, name, addr, retprobe, None, false)?;
+        self.attach_perf_events(program, perf_event_fds)
+    }
 
-        let mut fds = vec![];
-        for fd in perf_event_fds {
-            perf_event_attach(fd, program.get_fd())?;
-            perf_event_enable(fd)?;
-            fds.push(fd);
+    fn attach_kretprobe_event(
+        &mut self,
+        program: &Program,
+        name: &str,
+        maxactive: u32,
+    ) -> Result<(), Error> {
+        let (event, fds) = perf_event_open_kretprobe_event(name, maxactive)?;
+        if let Err(e) = self.attach_perf_events(program, fds) {
+            let _ = remove_kretprobe_event(&event);
+            return Err(e);
         }
-        self.attach_fds.insert(program.get_fd(), fds);
+        self.kretprobe_events
+            .entry(program.get_fd())
+            .or_default()
+            .push(event);
 
         Ok(())
     }
 
+    fn attach_perf_events(&mut self, program: &Program, fds: Vec<u32>) -> Result<(), Error> {
+        for fd in &fds {
+            if let Err(e) =
+                perf_event_attach(*fd, program.get_fd()).and_then(|_| perf_event_enable(*fd))
+            {
+                for fd in &fds {
+                    close(*fd);
+                }
+                return Err(e);
+            }
+        }
+        self.add_attach_fds(program, fds);
+
+        Ok(())
+    }
+
+    /// Keeps the fds of an attachment until the program is detached; a program
+    /// attached more than once keeps all of them.
+    fn add_attach_fds(&mut self, program: &Program, fds: Vec<u32>) {
+        self.attach_fds
+            .entry(program.get_fd())
+            .or_default()
+            .extend(fds);
+    }
+
     fn attach_raw_tracepoint(&mut self, program: &Program, name: &str) -> Result<(), Error> {
         let mut bpf_attr = BpfRawTracepointOpenAttr::default();
 
@@ -104,7 +335,7 @@ impl Probe {
         bpf_attr.name = attach_name.as_ptr() as u64;
 
         let fds = vec![bpf_attr.call_bpf(Command::RawTracepointOpen)?];
-        self.attach_fds.insert(program.get_fd(), fds);
+        self.add_attach_fds(program, fds);
 
         Ok(())
     }
@@ -118,6 +349,15 @@ impl Probe {
         }
         self.attach_fds.remove(&program.get_fd());
 
+        /* the events can only be removed once nothing has them open */
+        for event in self
+            .kretprobe_events
+            .remove(&program.get_fd())
+            .unwrap_or_default()
+        {
+            remove_kretprobe_event(&event)?;
+        }
+
         Ok(())
     }
 }
@@ -129,5 +369,8 @@ impl Drop for Probe {
                 close(*fd);
             }
         }
+        for event in self.kretprobe_events.values().flatten() {
+            let _ = remove_kretprobe_event(event);
+        }
     }
 }
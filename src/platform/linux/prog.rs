@@ -1,8 +1,19 @@
+use crate::btf::{BtfTypes, BTF_KIND_FUNC};
 use crate::error::Error;
-use crate::platform::linux::bpf::{AttachType, CallBpf, Command};
-use crate::platform::linux::syscalls::close;
+use crate::platform::linux::bpf::{
+    get_fd_by_id, get_info_by_fd, name_from_bytes, AttachType, CallBpf, Command, IdIter,
+};
+use crate::platform::linux::btf::{Btf, FuncInfo, LineInfo};
+use crate::platform::linux::map::MapFd;
+use crate::platform::linux::syscalls::{cbzero, close};
+use crate::platform::linux::test_run::TestRun;
 
 use std::io::Write;
+use std::mem::size_of;
+
+const EACCES: i32 = 13;
+const EINVAL: i32 = 22;
+const ENOSPC: i32 = 28;
 
 #[derive(Default)]
 #[repr(C, align(8))]
@@ -28,12 +39,76 @@ struct BpfProgramAttr {
     pub line_info: u64,
     pub line_info_count: u32,
     pub attach_btf_id: u32,
+    pub attach_prog_fd: u32,
+    pub core_relo_cnt: u32,
+    pub fd_array: u64,
+    pub core_relos: u64,
+    pub core_relo_rec_size: u32,
+    pub log_true_size: u32,
 }
 
 impl CallBpf for BpfProgramAttr {}
 
-#[derive(Copy, Clone)]
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfProgBindMapAttr {
+    pub prog_fd: u32,
+    pub map_fd: u32,
+    pub flags: u32,
+}
+
+impl CallBpf for BpfProgBindMapAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.prog_fd)
+    }
+}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfProgInfo {
+    pub prog_type: u32,
+    pub id: u32,
+    pub tag: [u8; 8],
+    pub jited_prog_len: u32,
+    pub xlated_prog_len: u32,
+    pub jited_prog_insns: u64,
+    pub xlated_prog_insns: u64,
+    pub load_time: u64,
+    pub created_by_uid: u32,
+    pub nr_map_ids: u32,
+    pub map_ids: u64,
+    pub name: [u8; 16],
+    pub ifindex: u32,
+    pub gpl_compatible: u32,
+    pub netns_dev: u64,
+    pub netns_ino: u64,
+    pub nr_jited_ksyms: u32,
+    pub nr_jited_func_lens: u32,
+    pub jited_ksyms: u64,
+    pub jited_func_lens: u64,
+    pub btf_id: u32,
+    pub func_info_rec_size: u32,
+    pub func_info: u64,
+    pub nr_func_info: u32,
+    pub nr_line_info: u32,
+    pub line_info: u64,
+    pub jited_line_info: u64,
+    pub nr_jited_line_info: u32,
+    pub line_info_rec_size: u32,
+    pub jited_line_info_rec_size: u32,
+    pub nr_prog_tags: u32,
+    pub prog_tags: u64,
+    pub run_time_ns: u64,
+    pub run_cnt: u64,
+    pub recursion_misses: u64,
+    pub verified_insns: u32,
+    pub attach_btf_obj_id: u32,
+    pub attach_btf_id: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum ProgramType {
+    #[default]
     Unspec = 0,
     SocketFilter,
     KProbe,
@@ -66,9 +141,108 @@ pub enum ProgramType {
     Lsm,
     SkLookup,
     Syscall,
+    Netfilter,
+}
+
+impl TryFrom<u32> for ProgramType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        use ProgramType::*;
+        const TYPES: [ProgramType; 33] = [
+            Unspec,
+            SocketFilter,
+            KProbe,
+            SchedCls,
+            SchedAct,
+            Tracepoint,
+            Xdp,
+            PerfEvent,
+            CgroupSkb,
+            CgroupSock,
+            LwtIn,
+            LwtOut,
+            LwtXmit,
+            SockOps,
+            SkSkb,
+            CgroupDevice,
+            SkMsg,
+            RawTracepoint,
+            CgroupSockAddr,
+            LwtSeg6local,
+            LircMode2,
+            SkReuseport,
+            FlowDissector,
+            CgroupSysctl,
+            RawTracepointWritable,
+            CgroupSockopt,
+            Tracing,
+            StructOps,
+            Ext,
+            Lsm,
+            SkLookup,
+            Syscall,
+            Netfilter,
+        ];
+
+        TYPES.get(value as usize).copied().ok_or(Error::OutOfRange)
+    }
+}
+
+/// The BTF describing a program: the functions it's made of and the source lines its
+/// instructions came from. The verifier log then shows source lines, and tools like
+/// bpftool can display the program's functions.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramBtf {
+    /// The BTF object holding the types and strings the records refer to, as returned
+    /// by `Btf::get_identifier()`.
+    pub btf_fd: u32,
+
+    /// One record per function, the first one for the program's entry at
+    /// instruction 0.
+    pub func_info: Vec<FuncInfo>,
+
+    /// Source line records, ordered by instruction.
+    pub line_info: Vec<LineInfo>,
+
+    /// The BTF of kernel modules whose functions (kfuncs) the program calls, as
+    /// returned by `Btf::get_identifier()`. A kfunc call's offset field indexes this,
+    /// 0 standing for vmlinux, so the first entry is unused.
+    pub fd_array: Vec<u32>,
+}
+
+/// How much the verifier logs while loading a program. The levels map to the kernel's
+/// `log_level` bits; `Stats` only logs the final statistics.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LogLevel {
+    /// No log, unless loading fails, in which case the program is loaded again with
+    /// `Basic` logging to capture why.
+    #[default]
+    Off = 0,
+
+    /// The instructions the verifier walked and the error, if any.
+    Basic = 1,
+
+    /// Everything in `Basic`, plus the register and stack states at each instruction.
+    Verbose = 2,
+
+    /// Only the verification statistics, e.g.: the number of instructions processed.
+    Stats = 4,
+}
+
+/// Program load flags, for `ProgramAttr::prog_flags`.
+pub mod prog_flags {
+    pub const STRICT_ALIGNMENT: u32 = 1 << 0;
+    pub const ANY_ALIGNMENT: u32 = 1 << 1;
+    pub const TEST_RND_HI32: u32 = 1 << 2;
+    pub const TEST_STATE_FREQ: u32 = 1 << 3;
+    pub const SLEEPABLE: u32 = 1 << 4;
+    pub const XDP_HAS_FRAGS: u32 = 1 << 5;
+    pub const XDP_DEV_BOUND_ONLY: u32 = 1 << 6;
+    pub const TEST_REG_INVARIANTS: u32 = 1 << 7;
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ProgramAttr {
     /// An optional name for the program.
     pub prog_name: Option<String>,
@@ -82,6 +256,161 @@ pub struct ProgramAttr {
 
     /// If the probe is being attached to a function using the BTF id, specify it here.
     pub attach_btf_id: Option<u32>,
+
+    /// The program an `Ext` program replaces a global function of, e.g.: from
+    /// `Program::get_fd()`. `attach_btf_id` is then the id of the function in that
+    /// program's BTF, see `Program::func_btf_id`.
+    pub attach_prog_fd: Option<u32>,
+
+    /// Optional BTF describing the program's functions and source lines.
+    pub btf: Option<ProgramBtf>,
+
+    /// How much the verifier logs. When a logger is passed to `Program::create` and
+    /// this is `Off`, `Basic` is used.
+    pub log_level: LogLevel,
+
+    /// The initial size of the log buffer in bytes; it grows as needed when the log
+    /// doesn't fit. Defaults to 64 KiB.
+    pub log_size: Option<u32>,
+
+    /// Load flags, see `prog_flags`.
+    pub prog_flags: u32,
+
+    /// The program's license; GPL-only helpers can't be used unless it's GPL
+    /// compatible. Defaults to "GPL".
+    pub license: Option<String>,
+
+    /// The kernel version the program was built for, which kprobe programs had to
+    /// match before Linux 5.0. Defaults to 0.
+    pub kern_version: Option<u32>,
+
+    /// The interface to offload the program to, or to bind it to with
+    /// `prog_flags::XDP_DEV_BOUND_ONLY`.
+    pub prog_ifindex: Option<u32>,
+}
+
+impl ProgramAttr {
+    /// Checks for attributes the kernel would reject, so the error is reported
+    /// without a load attempt.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self
+            .license
+            .as_ref()
+            .is_some_and(|license| license.contains('\0'))
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        /* mirrors the verifier's can_be_sleepable() */
+        if self.prog_flags & prog_flags::SLEEPABLE != 0
+            && !matches!(
+                self.prog_type,
+                ProgramType::Tracing
+                    | ProgramType::Lsm
+                    | ProgramType::KProbe
+                    | ProgramType::StructOps
+                    | ProgramType::Syscall
+            )
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let xdp_only = prog_flags::XDP_HAS_FRAGS | prog_flags::XDP_DEV_BOUND_ONLY;
+        if self.prog_flags & xdp_only != 0 && self.prog_type != ProgramType::Xdp {
+            return Err(Error::InvalidArgument);
+        }
+
+        /* extensions need a target function, and only tracing programs can attach to one too */
+        match self.prog_type {
+            ProgramType::Ext if self.attach_prog_fd.is_none() || self.attach_btf_id.is_none() => {
+                return Err(Error::InvalidArgument)
+            }
+            ProgramType::Ext | ProgramType::Tracing => {}
+            _ if self.attach_prog_fd.is_some() => return Err(Error::InvalidArgument),
+            _ => {}
+        }
+
+        /* only XDP and tc programs can be offloaded or bound to a device */
+        match self.prog_ifindex {
+            Some(_) if !matches!(self.prog_type, ProgramType::Xdp | ProgramType::SchedCls) => {
+                Err(Error::InvalidArgument)
+            }
+            None if self.prog_flags & prog_flags::XDP_DEV_BOUND_ONLY != 0 => {
+                Err(Error::InvalidArgument)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Information about a program as reported by the kernel.
+#[derive(Clone, Debug)]
+pub struct ProgramInfo {
+    /// The type of program.
+    pub prog_type: ProgramType,
+
+    /// The kernel id of the program.
+    pub id: u32,
+
+    /// The name of the program; empty if it was loaded without one.
+    pub name: String,
+
+    /// A hash of the program's instructions, as displayed by bpftool.
+    pub tag: [u8; 8],
+
+    /// The time the program was loaded, in nanoseconds since boot.
+    pub load_time_ns: u64,
+
+    /// The uid of the user that loaded the program.
+    pub created_by_uid: u32,
+
+    /// The ids of the maps used by the program, including those bound with
+    /// `Program::bind_map`.
+    pub map_ids: Vec<u32>,
+
+    /// The number of instructions processed by the verifier.
+    pub verified_insns: u32,
+
+    /// The number of times the program has run; only counted while stats are enabled.
+    pub run_cnt: u64,
+
+    /// The total time spent running the program, in nanoseconds; only counted while
+    /// stats are enabled.
+    pub run_time_ns: u64,
+
+    /// The size of the program after verifier rewrites, in bytes.
+    pub xlated_prog_len: u32,
+
+    /// The size of the JIT compiled program, in bytes; 0 if the program isn't JITed.
+    pub jited_prog_len: u32,
+
+    /// The id of the BTF object the program was loaded with; 0 if it has none.
+    pub btf_id: u32,
+}
+
+/// The native machine code the kernel JIT compiled a program into.
+#[derive(Clone, Debug, Default)]
+pub struct JitedImage {
+    /// The raw machine code of the program and all of its subprograms.
+    pub image: Vec<u8>,
+
+    /// The length, in bytes, of each function within `image`, in order; the main
+    /// program comes first.
+    pub func_lens: Vec<u32>,
+}
+
+/// Run time statistics for a program. These are only counted while statistics are
+/// enabled, see `StatsGuard`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProgramStats {
+    /// The number of times the program has run.
+    pub run_cnt: u64,
+
+    /// The total time spent running the program, in nanoseconds.
+    pub run_time_ns: u64,
+
+    /// The number of times the program was skipped because it would have recursed.
+    pub recursion_misses: u64,
 }
 
 pub struct Program {
@@ -90,11 +419,16 @@ pub struct Program {
 }
 
 impl Program {
-    const LICENSE: &'static str = "GPL\0";
+    const LICENSE: &'static str = "GPL";
+    const DEFAULT_LOG_SIZE: u32 = 64 << 10;
+
+    /* the kernel refuses log buffers of 1 GiB and more */
+    const MAX_LOG_SIZE: u32 = (1 << 30) - 1;
 
     /// Creates a program with the given attributes and instructions. Optionally,
     /// an object implementing the Write trait can be passed in that receives the
-    /// kernel eBPF logging output.
+    /// kernel eBPF logging output. If the program fails to load, the returned
+    /// `Error::VerifierError` holds the log regardless.
     ///
     /// # Arguments
     ///
@@ -106,7 +440,76 @@ impl Program {
         instructions: &[u64],
         log_out: Option<&mut dyn Write>,
     ) -> Result<Self, Error> {
-        let mut buf = vec![0; 1 << 20];
+        attr.validate()?;
+        Self::create_unchecked(attr, instructions, log_out)
+    }
+
+    /// Same as `create`, without validating the attributes first; feature probes load
+    /// programs the kernel is expected to reject.
+    pub(crate) fn create_unchecked(
+        attr: &ProgramAttr,
+        instructions: &[u64],
+        log_out: Option<&mut dyn Write>,
+    ) -> Result<Self, Error> {
+        let mut log_level = match (attr.log_level, &log_out) {
+            (LogLevel::Off, Some(_)) => LogLevel::Basic,
+            (log_level, _) => log_level,
+        };
+        let mut log_size = attr.log_size.unwrap_or(Self::DEFAULT_LOG_SIZE);
+
+        let (r, log) = loop {
+            let (r, log, log_true_size) =
+                Self::load(attr, instructions, log_level as u32, log_size);
+            match r {
+                /* the log didn't fit; the kernel may say how much room it needs */
+                Err(e)
+                    if e.is_no_space()
+                        && log_level != LogLevel::Off
+                        && log_size < Self::MAX_LOG_SIZE =>
+                {
+                    log_size = log_true_size
+                        .max(log_size.saturating_mul(2))
+                        .min(Self::MAX_LOG_SIZE);
+                }
+                /* only retry for a log when the verifier could be what refused it */
+                Err(e) if log_level == LogLevel::Off && Self::from_verifier(&e) => {
+                    log_level = LogLevel::Basic
+                }
+                r => break (r, log),
+            }
+        };
+
+        if let Some(log_out) = log_out {
+            let _ = write!(log_out, "{}", log);
+        }
+
+        match r {
+            Err(Error::SystemError { errno, .. }) if !log.is_empty() => {
+                Err(Error::VerifierError(errno, log))
+            }
+            Err(e) => Err(e),
+            Ok(r) => Ok(Self {
+                fd: r,
+                attr: attr.clone(),
+            }),
+        }
+    }
+
+    /// Whether a failed load may have been refused by the verifier, and so has a log
+    /// worth getting; e.g.: EPERM or E2BIG come before the verifier runs.
+    fn from_verifier(e: &Error) -> bool {
+        matches!(e.errno(), Some(EINVAL) | Some(EACCES) | Some(ENOSPC))
+    }
+
+    /// Makes a single attempt at loading a program, returning the result, the log and
+    /// the size the kernel needed for the whole log.
+    fn load(
+        attr: &ProgramAttr,
+        instructions: &[u64],
+        log_level: u32,
+        log_size: u32,
+    ) -> (Result<u32, Error>, String, u32) {
+        let mut buf = vec![0u8; if log_level == 0 { 0 } else { log_size as usize }];
 
         let expected_attach_type = if let Some(t) = attr.expected_attach_type {
             t as u32
@@ -114,58 +517,256 @@ impl Program {
             0
         };
 
-        let attach_btf_id = if let Some(id) = attr.attach_btf_id {
-            id
-        } else {
-            0
-        };
+        let attach_btf_id = attr.attach_btf_id.unwrap_or_default();
 
+        /* the name has to be nul-terminated within the 16 bytes */
         let mut prog_name: [u8; 16] = [0; 16];
         if let Some(name) = &attr.prog_name {
             for (i, c) in name.chars().enumerate() {
-                if i == 16 {
+                if i == 15 {
                     break;
                 }
                 prog_name[i] = c as u8;
             }
         }
 
-        let bpf_attr = BpfProgramAttr {
+        let btf = attr.btf.clone().unwrap_or_default();
+
+        let mut license = attr.license.as_deref().unwrap_or(Self::LICENSE).to_owned();
+        license.push('\0');
+
+        let mut bpf_attr = BpfProgramAttr {
             prog_type: attr.prog_type as u32,
             insns: instructions.as_ptr() as u64,
             insn_cnt: instructions.len() as u32,
-            license: Self::LICENSE.as_ptr() as u64,
-            log_level: 1,
-            log_size: 1 << 20,
-            log_buf: buf.as_mut_ptr() as u64,
-            kern_version: 0,
-            prog_flags: 0,
+            license: license.as_ptr() as u64,
+            log_level,
+            log_size: buf.len() as u32,
+            log_buf: if buf.is_empty() {
+                0
+            } else {
+                buf.as_mut_ptr() as u64
+            },
+            kern_version: attr.kern_version.unwrap_or_default(),
+            prog_flags: attr.prog_flags,
             prog_name,
-            prog_ifindex: 0,
+            prog_ifindex: attr.prog_ifindex.unwrap_or_default(),
             expected_attach_type,
-            prog_btf_fd: 0,
-            func_info_rec_size: 0,
-            func_info: 0,
-            func_info_count: 0,
-            line_info_rec_size: 0,
-            line_info: 0,
-            line_info_count: 0,
+            prog_btf_fd: btf.btf_fd,
+            func_info_rec_size: size_of::<FuncInfo>() as u32,
+            func_info: btf.func_info.as_ptr() as u64,
+            func_info_count: btf.func_info.len() as u32,
+            line_info_rec_size: size_of::<LineInfo>() as u32,
+            line_info: btf.line_info.as_ptr() as u64,
+            line_info_count: btf.line_info.len() as u32,
             attach_btf_id,
+            attach_prog_fd: attr.attach_prog_fd.unwrap_or_default(),
+            core_relo_cnt: 0,
+            fd_array: if btf.fd_array.is_empty() {
+                0
+            } else {
+                btf.fd_array.as_ptr() as u64
+            },
+            core_relos: 0,
+            core_relo_rec_size: 0,
+            log_true_size: 0,
+        };
+
+        let r = bpf_attr.call_bpf_mut(Command::ProgLoad);
+        (r, name_from_bytes(&buf), bpf_attr.log_true_size)
+    }
+
+    /// Opens an existing program, possibly loaded by another process, by its kernel id.
+    /// The returned program's attributes are reconstructed from what the kernel reports.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The kernel id of the program, e.g.: as returned by `program_ids()`.
+    pub fn from_id(id: u32) -> Result<Self, Error> {
+        let fd = get_fd_by_id(Command::ProgGetFdById, id)?;
+
+        let info = match Self::get_raw_info(fd) {
+            Ok(info) => info,
+            Err(e) => {
+                close(fd);
+                return Err(e);
+            }
         };
 
-        let r = bpf_attr.call_bpf(Command::ProgLoad);
+        let prog_type = match ProgramType::try_from(info.prog_type) {
+            Ok(prog_type) => prog_type,
+            Err(e) => {
+                close(fd);
+                return Err(e);
+            }
+        };
+
+        let name = name_from_bytes(&info.name);
+        let prog_name = if name.is_empty() { None } else { Some(name) };
+
+        let attach_btf_id = if info.attach_btf_id > 0 {
+            Some(info.attach_btf_id)
+        } else {
+            None
+        };
 
-        if let (Ok(s), Some(log_out)) = (std::str::from_utf8(&buf), log_out) {
-            let _ = write!(log_out, "{}", s);
+        Ok(Self {
+            fd,
+            attr: ProgramAttr {
+                prog_name,
+                prog_type,
+                expected_attach_type: None,
+                attach_btf_id,
+                prog_ifindex: (info.ifindex > 0).then_some(info.ifindex),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Queries the kernel for information about the program.
+    pub fn info(&self) -> Result<ProgramInfo, Error> {
+        let info = Self::get_raw_info(self.fd)?;
+
+        /*
+         * the map ids are only copied out if a buffer is given, so a second
+         * query is needed now that the count is known.
+         */
+        let mut map_ids = vec![0u32; info.nr_map_ids as usize];
+        if !map_ids.is_empty() {
+            let mut ids_info = BpfProgInfo::default();
+            cbzero(&mut ids_info);
+            ids_info.nr_map_ids = map_ids.len() as u32;
+            ids_info.map_ids = map_ids.as_mut_ptr() as u64;
+            get_info_by_fd(self.fd, &mut ids_info)?;
+            map_ids.truncate(ids_info.nr_map_ids as usize);
         }
 
-        match r {
-            Err(e) => Err(e),
-            Ok(r) => Ok(Self {
-                fd: r,
-                attr: attr.clone(),
-            }),
+        Ok(ProgramInfo {
+            prog_type: ProgramType::try_from(info.prog_type)?,
+            id: info.id,
+            name: name_from_bytes(&info.name),
+            tag: info.tag,
+            load_time_ns: info.load_time,
+            created_by_uid: info.created_by_uid,
+            map_ids,
+            verified_insns: info.verified_insns,
+            run_cnt: info.run_cnt,
+            run_time_ns: info.run_time_ns,
+            xlated_prog_len: info.xlated_prog_len,
+            jited_prog_len: info.jited_prog_len,
+            btf_id: info.btf_id,
+        })
+    }
+
+    /// Queries the kernel for the program's run time statistics.
+    pub fn stats(&self) -> Result<ProgramStats, Error> {
+        let info = Self::get_raw_info(self.fd)?;
+
+        Ok(ProgramStats {
+            run_cnt: info.run_cnt,
+            run_time_ns: info.run_time_ns,
+            recursion_misses: info.recursion_misses,
+        })
+    }
+
+    /// Prepares a test run of the program, executing it in the kernel against
+    /// caller-provided data and context rather than real events.
+    ///
+    /// # Example
+    /// ```
+    /// use bpf_api::prog::{Program, ProgramAttr, ProgramType};
+    ///
+    /// let attr = ProgramAttr {
+    ///     prog_name: None,
+    ///     prog_type: ProgramType::Xdp,
+    ///     expected_attach_type: None,
+    ///     attach_btf_id: None,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// /* r0 = XDP_PASS; exit */
+    /// let program = Program::create(&attr, &[0x2000000b7, 0x95], None).unwrap();
+    /// let packet = [0u8; 64];
+    /// let result = program.test_run().data_in(&packet).data_out(64).run().unwrap();
+    /// assert_eq!(result.retval, 2);
+    /// assert_eq!(result.data_out, packet);
+    /// ```
+    pub fn test_run(&self) -> TestRun<'_> {
+        TestRun::new(self)
+    }
+
+    /// Retrieves the program's instructions as rewritten by the verifier, i.e.: what the
+    /// kernel actually runs or JITs. Map references are replaced by the map's id.
+    pub fn xlated_instructions(&self) -> Result<Vec<u64>, Error> {
+        let info = Self::get_raw_info(self.fd)?;
+
+        let mut instructions = vec![0u64; info.xlated_prog_len as usize / 8];
+        if !instructions.is_empty() {
+            let mut insns_info = BpfProgInfo::default();
+            cbzero(&mut insns_info);
+            insns_info.xlated_prog_len = (instructions.len() * 8) as u32;
+            insns_info.xlated_prog_insns = instructions.as_mut_ptr() as u64;
+            get_info_by_fd(self.fd, &mut insns_info)?;
+            instructions.truncate(insns_info.xlated_prog_len as usize / 8);
         }
+
+        Ok(instructions)
+    }
+
+    /// Retrieves the machine code the kernel JIT compiled the program into. The image
+    /// is empty if the JIT is disabled.
+    pub fn jited_image(&self) -> Result<JitedImage, Error> {
+        let info = Self::get_raw_info(self.fd)?;
+
+        let mut image = vec![0u8; info.jited_prog_len as usize];
+        let mut func_lens = vec![0u32; info.nr_jited_func_lens as usize];
+        if !image.is_empty() {
+            let mut image_info = BpfProgInfo::default();
+            cbzero(&mut image_info);
+            image_info.jited_prog_len = image.len() as u32;
+            image_info.jited_prog_insns = image.as_mut_ptr() as u64;
+            image_info.nr_jited_func_lens = func_lens.len() as u32;
+            image_info.jited_func_lens = func_lens.as_mut_ptr() as u64;
+            get_info_by_fd(self.fd, &mut image_info)?;
+            image.truncate(image_info.jited_prog_len as usize);
+            func_lens.truncate(image_info.nr_jited_func_lens as usize);
+        }
+
+        Ok(JitedImage { image, func_lens })
+    }
+
+    /// Looks up the BTF id of one of the program's functions, to replace it with an
+    /// `Ext` program. The program must have been loaded with BTF.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the function.
+    pub fn func_btf_id(&self, name: &str) -> Result<u32, Error> {
+        let info = Self::get_raw_info(self.fd)?;
+        if info.btf_id == 0 {
+            return Err(Error::SymbolNotFound(name.into()));
+        }
+
+        let btf = BtfTypes::parse(&Btf::from_id(info.btf_id)?.data()?)?;
+        btf.find_by_name_kind(name, BTF_KIND_FUNC)
+            .ok_or_else(|| Error::SymbolNotFound(name.into()))
+    }
+
+    /// Binds a map to the program, so the map lives as long as the program does even
+    /// if no instruction references it, e.g.: a map holding metadata about the program.
+    /// Bound maps show up in `ProgramInfo::map_ids`.
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - The map, e.g.: a `Map` or one of the collections.
+    pub fn bind_map(&self, map: &impl MapFd) -> Result<(), Error> {
+        let mut attr = BpfProgBindMapAttr::default();
+        cbzero(&mut attr);
+        attr.prog_fd = self.fd;
+        attr.map_fd = map.map_fd();
+
+        attr.call_bpf(Command::ProgBindMap)?;
+        Ok(())
     }
 
     /// Retrieves the attributes for the program.
@@ -177,6 +778,22 @@ impl Program {
     pub fn get_fd(&self) -> u32 {
         self.fd
     }
+
+    fn get_raw_info(fd: u32) -> Result<BpfProgInfo, Error> {
+        /*
+         * the kernel requires any trailing bytes it doesn't know about,
+         * including the structure's padding, to be zero.
+         */
+        let mut info = BpfProgInfo::default();
+        cbzero(&mut info);
+        get_info_by_fd(fd, &mut info)?;
+        Ok(info)
+    }
+}
+
+/// Returns an iterator over the ids of all programs loaded in the kernel.
+pub fn program_ids() -> IdIter {
+    IdIter::new(Command::ProgGetNextId)
 }
 
 impl Drop for Program {
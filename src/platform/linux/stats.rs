@@ -0,0 +1,120 @@
+use crate::error::Error;
+use crate::platform::linux::bpf::{CallBpf, Command};
+use crate::platform::linux::prog::{Program, ProgramStats};
+use crate::platform::linux::syscalls::{cbzero, close};
+
+use std::time::Instant;
+
+#[allow(dead_code)]
+enum StatsType {
+    RunTime = 0,
+}
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfEnableStatsAttr {
+    pub stats_type: u32,
+}
+
+impl CallBpf for BpfEnableStatsAttr {}
+
+/// Keeps run time statistics (`BPF_STATS_RUN_TIME`) enabled for all programs for as
+/// long as it's alive. Collecting statistics adds a small overhead to every program
+/// invocation, so the kernel only does so while at least one guard exists.
+pub struct StatsGuard {
+    fd: u32,
+}
+
+impl StatsGuard {
+    /// Enables run time statistics until the returned guard is dropped.
+    pub fn enable() -> Result<Self, Error> {
+        let mut attr = BpfEnableStatsAttr::default();
+        cbzero(&mut attr);
+        attr.stats_type = StatsType::RunTime as u32;
+
+        Ok(Self {
+            fd: attr.call_bpf(Command::EnableStats)?,
+        })
+    }
+}
+
+impl Drop for StatsGuard {
+    fn drop(&mut self) {
+        close(self.fd);
+    }
+}
+
+/// Per-second rates computed for a program between two samples.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProgramRates {
+    /// The kernel id of the program.
+    pub id: u32,
+
+    /// The number of invocations per second.
+    pub runs_per_sec: f64,
+
+    /// The time spent running the program per second, in nanoseconds; divide by 1e9
+    /// to get the fraction of a CPU used.
+    pub run_time_ns_per_sec: f64,
+
+    /// The average time per invocation, in nanoseconds; 0 if the program didn't run.
+    pub avg_run_time_ns: f64,
+
+    /// The number of invocations skipped due to recursion, per second.
+    pub recursion_misses_per_sec: f64,
+}
+
+/// Periodically samples the statistics of a set of programs and computes rates between
+/// samples. Statistics must be enabled, e.g.: with a `StatsGuard`, for the kernel to
+/// count anything.
+pub struct StatsSampler<'a> {
+    programs: Vec<(&'a Program, u32, ProgramStats)>,
+    last_sample: Instant,
+}
+
+impl<'a> StatsSampler<'a> {
+    /// Creates a sampler for the given programs, taking the initial sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `programs` - The programs to sample.
+    pub fn new(programs: &[&'a Program]) -> Result<Self, Error> {
+        let mut sampled = vec![];
+        for program in programs {
+            sampled.push((*program, program.info()?.id, program.stats()?));
+        }
+
+        Ok(Self {
+            programs: sampled,
+            last_sample: Instant::now(),
+        })
+    }
+
+    /// Takes a new sample and returns the rates since the previous one, in the same
+    /// order the programs were given.
+    pub fn sample(&mut self) -> Result<Vec<ProgramRates>, Error> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_sample).as_secs_f64();
+        self.last_sample = now;
+
+        let mut rates = vec![];
+        for (program, id, last) in self.programs.iter_mut() {
+            let stats = program.stats()?;
+            let runs = stats.run_cnt.saturating_sub(last.run_cnt) as f64;
+            let run_time = stats.run_time_ns.saturating_sub(last.run_time_ns) as f64;
+            let misses = stats.recursion_misses.saturating_sub(last.recursion_misses) as f64;
+            *last = stats;
+
+            let per_sec = |n: f64| if elapsed > 0.0 { n / elapsed } else { 0.0 };
+            rates.push(ProgramRates {
+                id: *id,
+                runs_per_sec: per_sec(runs),
+                run_time_ns_per_sec: per_sec(run_time),
+                avg_run_time_ns: if runs > 0.0 { run_time / runs } else { 0.0 },
+                recursion_misses_per_sec: per_sec(misses),
+            });
+        }
+
+        Ok(rates)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::error::Error;
+use crate::platform::linux::bpf::{CallBpf, Command};
+use crate::platform::linux::prog::Program;
+use crate::platform::linux::syscalls::cbzero;
+
+use std::time::Duration;
+
+#[derive(Default)]
+#[repr(C, align(8))]
+struct BpfProgTestRunAttr {
+    pub prog_fd: u32,
+    pub retval: u32,
+    pub data_size_in: u32,
+    pub data_size_out: u32,
+    pub data_in: u64,
+    pub data_out: u64,
+    pub repeat: u32,
+    pub duration: u32,
+    pub ctx_size_in: u32,
+    pub ctx_size_out: u32,
+    pub ctx_in: u64,
+    pub ctx_out: u64,
+    pub flags: u32,
+    pub cpu: u32,
+    pub batch_size: u32,
+}
+
+impl CallBpf for BpfProgTestRunAttr {
+    fn target_fd(&self) -> Option<u32> {
+        Some(self.prog_fd)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TestRunFlags {
+    RunOnCpu = 1,      /* run the program on the cpu given by `cpu` */
+    XdpLiveFrames = 2, /* inject the packet into the network stack (XDP only) */
+}
+
+/// The outcome of a test run.
+#[derive(Clone, Debug, Default)]
+pub struct TestRunResult {
+    /// The program's return value, e.g.: an XDP action code.
+    pub retval: u32,
+
+    /// The packet data after the program ran; empty unless `data_out` was requested.
+    pub data_out: Vec<u8>,
+
+    /// The context after the program ran; empty unless `ctx_out` was requested.
+    pub ctx_out: Vec<u8>,
+
+    /// The average duration of a single run.
+    pub duration: Duration,
+}
+
+/// Describes a single test run of a program, built up from `Program::test_run()`.
+pub struct TestRun<'a> {
+    program: &'a Program,
+    data_in: &'a [u8],
+    data_out_size: usize,
+    ctx_in: &'a [u8],
+    ctx_out_size: usize,
+    repeat: u32,
+    cpu: u32,
+    flags: u32,
+    batch_size: u32,
+}
+
+impl<'a> TestRun<'a> {
+    pub(crate) fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            data_in: &[],
+            data_out_size: 0,
+            ctx_in: &[],
+            ctx_out_size: 0,
+            repeat: 0,
+            cpu: 0,
+            flags: 0,
+            batch_size: 0,
+        }
+    }
+
+    /// Sets the packet data the program runs against.
+    pub fn data_in(mut self, data: &'a [u8]) -> Self {
+        self.data_in = data;
+        self
+    }
+
+    /// Requests the packet data after the program ran, up to `size` bytes. The run
+    /// fails if the output packet is larger than `size`.
+    pub fn data_out(mut self, size: usize) -> Self {
+        self.data_out_size = size;
+        self
+    }
+
+    /// Sets the context passed to the program, e.g.: the bytes of an `xdp_md` or
+    /// `__sk_buff` structure.
+    pub fn ctx_in(mut self, ctx: &'a [u8]) -> Self {
+        self.ctx_in = ctx;
+        self
+    }
+
+    /// Requests the context after the program ran, up to `size` bytes.
+    pub fn ctx_out(mut self, size: usize) -> Self {
+        self.ctx_out_size = size;
+        self
+    }
+
+    /// Runs the program `repeat` times; the reported duration is the average.
+    pub fn repeat(mut self, repeat: u32) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Runs the program on the given cpu. Only supported by some program types, e.g.:
+    /// raw tracepoints.
+    pub fn cpu(mut self, cpu: u32) -> Self {
+        self.cpu = cpu;
+        self.flags |= TestRunFlags::RunOnCpu as u32;
+        self
+    }
+
+    /// Adds raw test run flags, see `TestRunFlags`. Flags set by `cpu` are kept.
+    pub fn flags(mut self, flags: u32) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    /// Sets the number of packets injected at once with `TestRunFlags::XdpLiveFrames`.
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Executes the program in the kernel.
+    pub fn run(self) -> Result<TestRunResult, Error> {
+        let mut data_out = vec![0u8; self.data_out_size];
+        let mut ctx_out = vec![0u8; self.ctx_out_size];
+
+        let mut attr = BpfProgTestRunAttr::default();
+        cbzero(&mut attr);
+        attr.prog_fd = self.program.get_fd();
+        attr.data_size_in = self.data_in.len().try_into()?;
+        attr.data_in = Self::ptr_or_null(self.data_in);
+        attr.data_size_out = data_out.len().try_into()?;
+        attr.data_out = Self::mut_ptr_or_null(&mut data_out);
+        attr.ctx_size_in = self.ctx_in.len().try_into()?;
+        attr.ctx_in = Self::ptr_or_null(self.ctx_in);
+        attr.ctx_size_out = ctx_out.len().try_into()?;
+        attr.ctx_out = Self::mut_ptr_or_null(&mut ctx_out);
+        attr.repeat = self.repeat;
+        attr.cpu = self.cpu;
+        attr.flags = self.flags;
+        attr.batch_size = self.batch_size;
+
+        attr.call_bpf_mut(Command::ProgTestRun)?;
+
+        data_out.truncate(attr.data_size_out as usize);
+        ctx_out.truncate(attr.ctx_size_out as usize);
+
+        Ok(TestRunResult {
+            retval: attr.retval,
+            data_out,
+            ctx_out,
+            duration: Duration::from_nanos(attr.duration.into()),
+        })
+    }
+
+    /// The kernel treats a null pointer as "not given", an empty slice's dangling
+    /// pointer would be rejected.
+    fn ptr_or_null(buf: &[u8]) -> u64 {
+        if buf.is_empty() {
+            0
+        } else {
+            buf.as_ptr() as u64
+        }
+    }
+
+    /// As `ptr_or_null`, for the buffers the kernel writes to.
+    fn mut_ptr_or_null(buf: &mut [u8]) -> u64 {
+        if buf.is_empty() {
+            0
+        } else {
+            buf.as_mut_ptr() as u64
+        }
+    }
+}
@@ -7,6 +7,7 @@ enum SyscallNumber {
     Munmap = 11,
     Ioctl = 16,
     PerfEventOpen = 298,
+    Prlimit64 = 302,
     Bpf = 321,
 }
 
@@ -31,8 +32,6 @@ pub enum MmapFlags {
     Anonymous = 0x20,
 }
 
-pub const MAP_FAILED: isize = isize::min_value();
-
 pub fn cbzero<T>(s: &mut T) {
     unsafe { std::ptr::write_bytes(s as *mut T, 0, 1) };
 }
@@ -88,6 +87,25 @@ unsafe fn syscall3(n: usize, arg1: usize, arg2: usize, arg3: usize) -> isize {
     ret
 }
 
+#[inline]
+unsafe fn syscall4(n: usize, arg1: usize, arg2: usize, arg3: usize, arg4: usize) -> isize {
+    let mut ret: isize;
+    let mut _ret_addr: usize;
+    let mut _rflags: usize;
+    asm!(
+        "syscall",
+        inlateout("rax") n as isize => ret,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        out("rcx") _ret_addr,
+        out("r11") _rflags,
+        options(nostack, preserves_flags)
+    );
+    ret
+}
+
 #[inline]
 unsafe fn syscall5(
     n: usize,
@@ -148,29 +166,47 @@ unsafe fn syscall6(
  * bpf()
  */
 pub fn bpf(cmd: u32, attr: *const u8, size: usize) -> isize {
-    unsafe {
-        /*
-         * regardless of the size you pass in to bpf(), the kernel assumes the memory
-         * pointed to by attr spans to at least sizeof(bpf_attr). Furthermore, it makes
-         * sure the structure data outside of the union arm being used is all zero.
-         * The structure is currently only 120 bytes, 1024 bytes is safe for the foreseeable
-         * future.
-         */
-        const BPF_ATTR_SIZE: usize = 120;
-        if size > BPF_ATTR_SIZE {
-            panic!("Structure passed to bpf() has size > BPF_ATTR_SIZE");
-        }
+    let mut buf = bpf_attr_buf(attr, size);
+    unsafe { bpf_raw(cmd, &mut buf) }
+}
 
-        let mut buf: [u8; BPF_ATTR_SIZE] = [0; BPF_ATTR_SIZE];
-        ptr::copy(attr, buf.as_mut_ptr() as *mut _, size);
+/*
+ * bpf(), copying any fields the kernel wrote back into attr, e.g.: next_id
+ * for BPF_*_GET_NEXT_ID.
+ */
+pub fn bpf_mut(cmd: u32, attr: *mut u8, size: usize) -> isize {
+    let mut buf = bpf_attr_buf(attr, size);
+    let r = unsafe { bpf_raw(cmd, &mut buf) };
+    unsafe { ptr::copy(buf.as_ptr(), attr, size) };
+    r
+}
 
-        syscall3(
-            SyscallNumber::Bpf as usize,
-            cmd as usize,
-            &buf as *const u8 as usize,
-            BPF_ATTR_SIZE,
-        )
+/*
+ * regardless of the size you pass in to bpf(), the kernel assumes the memory
+ * pointed to by attr spans to at least sizeof(bpf_attr). Furthermore, it makes
+ * sure the structure data outside of the union arm being used is all zero.
+ * It has grown past 120 bytes (e.g.: BPF_PROG_LOAD's fd_array), 256 bytes leaves
+ * room for what newer kernels add; the kernel only requires the excess to be zero.
+ */
+const BPF_ATTR_SIZE: usize = 256;
+
+fn bpf_attr_buf(attr: *const u8, size: usize) -> [u8; BPF_ATTR_SIZE] {
+    if size > BPF_ATTR_SIZE {
+        panic!("Structure passed to bpf() has size > BPF_ATTR_SIZE");
     }
+
+    let mut buf: [u8; BPF_ATTR_SIZE] = [0; BPF_ATTR_SIZE];
+    unsafe { ptr::copy(attr, buf.as_mut_ptr() as *mut _, size) };
+    buf
+}
+
+unsafe fn bpf_raw(cmd: u32, buf: &mut [u8; BPF_ATTR_SIZE]) -> isize {
+    syscall3(
+        SyscallNumber::Bpf as usize,
+        cmd as usize,
+        buf as *mut u8 as usize,
+        BPF_ATTR_SIZE,
+    )
 }
 
 /*
@@ -210,6 +246,22 @@ pub fn perf_event_enable(probe_fd: u32) -> isize {
     unsafe { syscall3(SyscallNumber::Ioctl as usize, probe_fd as usize, 0x2400, 0) }
 }
 
+/*
+ * prlimit64(0, resource, new_limit, old_limit) on the calling process; either
+ * limit may be null.
+ */
+pub fn prlimit(resource: u32, new_limit: *const u64, old_limit: *mut u64) -> isize {
+    unsafe {
+        syscall4(
+            SyscallNumber::Prlimit64 as usize,
+            0,
+            resource as usize,
+            new_limit as usize,
+            old_limit as usize,
+        )
+    }
+}
+
 /*
  * close()
  */
@@ -1,6 +1,13 @@
 #[cfg(target_os = "linux")]
 mod linux;
 pub use linux::bpf::*;
+pub use linux::btf::*;
+pub use linux::features::*;
+pub use linux::libs::*;
 pub use linux::map::*;
+pub(crate) use linux::perf::read_tracefs_event;
+pub use linux::preflight::*;
 pub use linux::probes::*;
 pub use linux::prog::*;
+pub use linux::stats::*;
+pub use linux::test_run::*;
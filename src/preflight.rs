@@ -0,0 +1,22 @@
+//! Checks of the privileges and resources loading programs depends on, to explain
+//! an `EPERM` before it happens: effective capabilities, the
+//! `kernel.unprivileged_bpf_disabled` sysctl, the lockdown mode, `RLIMIT_MEMLOCK`
+//! and where the BPF, trace and debug filesystems are mounted.
+//!
+//! Kernels before 5.11 charge maps and programs to `RLIMIT_MEMLOCK`, whose default is
+//! often too low to load anything sizeable; `raise_memlock_rlimit` lifts it on those
+//! kernels, or when it can't tell, and leaves newer ones alone.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::preflight::{raise_memlock_rlimit, Preflight};
+//!
+//! raise_memlock_rlimit().unwrap();
+//!
+//! let preflight = Preflight::check().unwrap();
+//! for problem in preflight.problems() {
+//!     eprintln!("warning: {}", problem);
+//! }
+//! ```
+
+pub use crate::platform::{raise_memlock_rlimit, Capabilities, Lockdown, Preflight, Rlimit};
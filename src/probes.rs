@@ -1 +1 @@
-pub use crate::platform::{AttachInfo, AttachType, Probe};
+pub use crate::platform::{resolve_library, AttachInfo, AttachType, Probe, UProbe};
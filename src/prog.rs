@@ -1 +1,5 @@
-pub use crate::platform::{Program, ProgramAttr, ProgramType};
+pub use crate::platform::{
+    prog_flags, program_ids, IdIter, JitedImage, LogLevel, Program, ProgramAttr, ProgramBtf,
+    ProgramInfo, ProgramRates, ProgramStats, ProgramType, StatsGuard, StatsSampler, TestRun,
+    TestRunFlags, TestRunResult,
+};
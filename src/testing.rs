@@ -0,0 +1,784 @@
+//! Helpers for test-running network programs: a builder for well-formed Ethernet
+//! frames, typed program contexts, and decoders for the packets and action codes a
+//! program produces.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::prog::{Program, ProgramAttr, ProgramType};
+//! use bpf_api::testing::{Packet, PacketBuilder, XdpAction};
+//! use std::net::Ipv4Addr;
+//!
+//! let attr = ProgramAttr {
+//!     prog_name: None,
+//!     prog_type: ProgramType::Xdp,
+//!     expected_attach_type: None,
+//!     attach_btf_id: None,
+//!     ..Default::default()
+//! };
+//!
+//! /* r0 = XDP_PASS; exit */
+//! let program = Program::create(&attr, &[0x2000000b7, 0x95], None).unwrap();
+//!
+//! let frame = PacketBuilder::new()
+//!     .ipv4(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2))
+//!     .udp(1234, 53)
+//!     .payload(b"hello")
+//!     .build()
+//!     .unwrap();
+//!
+//! let result = program.test_run().data_in(&frame).data_out(1500).run().unwrap();
+//! assert_eq!(XdpAction::try_from(result.retval).unwrap(), XdpAction::Pass);
+//!
+//! let packet = Packet::parse(&result.data_out).unwrap();
+//! assert_eq!(packet.payload, b"hello");
+//! ```
+
+use crate::error::Error;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use std::mem::size_of;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88a8;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+
+const ETH_HLEN: usize = 14;
+const VLAN_HLEN: usize = 4;
+const IPV4_HLEN: usize = 20;
+const IPV6_HLEN: usize = 40;
+const TCP_HLEN: usize = 20;
+const UDP_HLEN: usize = 8;
+const ICMP_HLEN: usize = 8;
+
+/// The return codes of XDP programs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum XdpAction {
+    Aborted = 0,
+    Drop,
+    Pass,
+    Tx,
+    Redirect,
+}
+
+impl TryFrom<u32> for XdpAction {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(XdpAction::Aborted),
+            1 => Ok(XdpAction::Drop),
+            2 => Ok(XdpAction::Pass),
+            3 => Ok(XdpAction::Tx),
+            4 => Ok(XdpAction::Redirect),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+}
+
+/// The return codes of traffic control (`SchedCls`/`SchedAct`) programs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TcAction {
+    Unspec = -1,
+    Ok = 0,
+    Reclassify,
+    Shot,
+    Pipe,
+    Stolen,
+    Queued,
+    Repeat,
+    Redirect,
+    Trap,
+}
+
+impl TryFrom<u32> for TcAction {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value as i32 {
+            -1 => Ok(TcAction::Unspec),
+            0 => Ok(TcAction::Ok),
+            1 => Ok(TcAction::Reclassify),
+            2 => Ok(TcAction::Shot),
+            3 => Ok(TcAction::Pipe),
+            4 => Ok(TcAction::Stolen),
+            5 => Ok(TcAction::Queued),
+            6 => Ok(TcAction::Repeat),
+            7 => Ok(TcAction::Redirect),
+            8 => Ok(TcAction::Trap),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+}
+
+/// A program context that can be passed to, and read back from, a test run.
+///
+/// # Safety
+///
+/// Implementors must be `repr(C)` structures made only of integers, with a layout
+/// identical to the kernel's context structure.
+pub unsafe trait Context: Copy + Default {
+    /// The raw bytes of the context, e.g.: for `TestRun::ctx_in()`.
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+
+    /// Reads a context from raw bytes, e.g.: as returned in `TestRunResult::ctx_out`.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < size_of::<Self>() {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const Self) })
+    }
+}
+
+/// The context of XDP programs, `struct xdp_md`. When test-running, `data`, `data_end`
+/// and `data_meta` must be zero; the kernel fills them in.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct XdpMd {
+    pub data: u32,
+    pub data_end: u32,
+    pub data_meta: u32,
+    pub ingress_ifindex: u32,
+    pub rx_queue_index: u32,
+    pub egress_ifindex: u32,
+}
+
+unsafe impl Context for XdpMd {}
+
+/// The context of socket filter and traffic control programs, `struct __sk_buff`.
+/// When test-running, the kernel only accepts non-zero values in `mark`, `priority`,
+/// `ingress_ifindex`, `ifindex`, `cb`, `tstamp`, `wire_len`, `gso_segs`, `gso_size`
+/// and `hwtstamp`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct SkBuff {
+    pub len: u32,
+    pub pkt_type: u32,
+    pub mark: u32,
+    pub queue_mapping: u32,
+    pub protocol: u32,
+    pub vlan_present: u32,
+    pub vlan_tci: u32,
+    pub vlan_proto: u32,
+    pub priority: u32,
+    pub ingress_ifindex: u32,
+    pub ifindex: u32,
+    pub tc_index: u32,
+    pub cb: [u32; 5],
+    pub hash: u32,
+    pub tc_classid: u32,
+    pub data: u32,
+    pub data_end: u32,
+    pub napi_id: u32,
+    pub family: u32,
+    pub remote_ip4: u32,
+    pub local_ip4: u32,
+    pub remote_ip6: [u32; 4],
+    pub local_ip6: [u32; 4],
+    pub remote_port: u32,
+    pub local_port: u32,
+    pub data_meta: u32,
+    pub flow_keys: u64,
+    pub tstamp: u64,
+    pub wire_len: u32,
+    pub gso_segs: u32,
+    pub sk: u64,
+    pub gso_size: u32,
+    pub tstamp_type: u8,
+    pub padding: [u8; 3],
+    pub hwtstamp: u64,
+}
+
+unsafe impl Context for SkBuff {}
+
+/// The network layer header of a packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NetworkHeader {
+    Ipv4 {
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        ttl: u8,
+        protocol: u8,
+    },
+    Ipv6 {
+        src: Ipv6Addr,
+        dst: Ipv6Addr,
+        hop_limit: u8,
+        next_header: u8,
+    },
+}
+
+/// The transport layer header of a packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TransportHeader {
+    Tcp {
+        src_port: u16,
+        dst_port: u16,
+        seq: u32,
+        ack: u32,
+        flags: u8,
+        window: u16,
+    },
+    Udp {
+        src_port: u16,
+        dst_port: u16,
+    },
+    Icmp {
+        icmp_type: u8,
+        code: u8,
+        rest: u32,
+    },
+}
+
+/// TCP header flags, for use with `PacketBuilder::tcp_flags()`.
+pub mod tcp_flags {
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const PSH: u8 = 0x08;
+    pub const ACK: u8 = 0x10;
+    pub const URG: u8 = 0x20;
+}
+
+/// Builds Ethernet frames with valid lengths and checksums for test runs. Without a
+/// network layer the frame carries the payload directly after the Ethernet header(s).
+#[derive(Clone, Debug)]
+pub struct PacketBuilder {
+    src_mac: [u8; 6],
+    dst_mac: [u8; 6],
+    vlans: Vec<u16>,
+    ether_type: u16,
+    network: Option<NetworkHeader>,
+    transport: Option<TransportHeader>,
+    payload: Vec<u8>,
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PacketBuilder {
+    /// Creates a builder for a frame between two locally administered MAC addresses.
+    pub fn new() -> Self {
+        Self {
+            src_mac: [0x02, 0, 0, 0, 0, 0x01],
+            dst_mac: [0x02, 0, 0, 0, 0, 0x02],
+            vlans: vec![],
+            ether_type: 0,
+            network: None,
+            transport: None,
+            payload: vec![],
+        }
+    }
+
+    /// Sets the source MAC address.
+    pub fn src_mac(mut self, mac: [u8; 6]) -> Self {
+        self.src_mac = mac;
+        self
+    }
+
+    /// Sets the destination MAC address.
+    pub fn dst_mac(mut self, mac: [u8; 6]) -> Self {
+        self.dst_mac = mac;
+        self
+    }
+
+    /// Adds an 802.1Q VLAN tag. Tags are written in the order they're added, the first
+    /// being the outermost; all but the innermost tag use the 802.1ad ether type.
+    ///
+    /// # Arguments
+    ///
+    /// * `tci` - The tag control information: priority, DEI and VLAN id.
+    pub fn vlan(mut self, tci: u16) -> Self {
+        self.vlans.push(tci);
+        self
+    }
+
+    /// Sets the ether type for frames without an IP header.
+    pub fn ether_type(mut self, ether_type: u16) -> Self {
+        self.ether_type = ether_type;
+        self
+    }
+
+    /// Adds an IPv4 header.
+    pub fn ipv4(mut self, src: Ipv4Addr, dst: Ipv4Addr) -> Self {
+        self.network = Some(NetworkHeader::Ipv4 {
+            src,
+            dst,
+            ttl: 64,
+            protocol: 0,
+        });
+        self
+    }
+
+    /// Adds an IPv6 header.
+    pub fn ipv6(mut self, src: Ipv6Addr, dst: Ipv6Addr) -> Self {
+        self.network = Some(NetworkHeader::Ipv6 {
+            src,
+            dst,
+            hop_limit: 64,
+            next_header: 0,
+        });
+        self
+    }
+
+    /// Sets the IPv4 TTL or the IPv6 hop limit.
+    pub fn ttl(mut self, value: u8) -> Self {
+        match &mut self.network {
+            Some(NetworkHeader::Ipv4 { ttl, .. }) => *ttl = value,
+            Some(NetworkHeader::Ipv6 { hop_limit, .. }) => *hop_limit = value,
+            None => {}
+        }
+        self
+    }
+
+    /// Adds a TCP header, with only the ACK flag set.
+    pub fn tcp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(TransportHeader::Tcp {
+            src_port,
+            dst_port,
+            seq: 0,
+            ack: 0,
+            flags: tcp_flags::ACK,
+            window: u16::MAX,
+        });
+        self
+    }
+
+    /// Sets the TCP flags, see `tcp_flags`.
+    pub fn tcp_flags(mut self, value: u8) -> Self {
+        if let Some(TransportHeader::Tcp { flags, .. }) = &mut self.transport {
+            *flags = value;
+        }
+        self
+    }
+
+    /// Sets the TCP sequence and acknowledgement numbers.
+    pub fn tcp_seq(mut self, seq_num: u32, ack_num: u32) -> Self {
+        if let Some(TransportHeader::Tcp { seq, ack, .. }) = &mut self.transport {
+            *seq = seq_num;
+            *ack = ack_num;
+        }
+        self
+    }
+
+    /// Adds a UDP header.
+    pub fn udp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(TransportHeader::Udp { src_port, dst_port });
+        self
+    }
+
+    /// Adds an ICMP header, or an ICMPv6 header on top of IPv6.
+    ///
+    /// # Arguments
+    ///
+    /// * `icmp_type` - The message type, e.g.: 8 for an ICMP echo request.
+    /// * `code` - The message code.
+    /// * `rest` - The type-specific remainder of the header, e.g.: id and sequence.
+    pub fn icmp(mut self, icmp_type: u8, code: u8, rest: u32) -> Self {
+        self.transport = Some(TransportHeader::Icmp {
+            icmp_type,
+            code,
+            rest,
+        });
+        self
+    }
+
+    /// Sets the payload carried by the innermost header.
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        self.payload = payload.to_vec();
+        self
+    }
+
+    /// Assembles the frame. Fails with `OutOfRange` if the payload doesn't fit in the
+    /// length fields of its headers.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        let segment = self.build_transport()?;
+        let packet = self.build_network(segment)?;
+
+        let ether_type = match &self.network {
+            Some(NetworkHeader::Ipv4 { .. }) => ETH_P_IP,
+            Some(NetworkHeader::Ipv6 { .. }) => ETH_P_IPV6,
+            None => self.ether_type,
+        };
+
+        let mut frame = Vec::with_capacity(ETH_HLEN + VLAN_HLEN * self.vlans.len() + packet.len());
+        frame.extend_from_slice(&self.dst_mac);
+        frame.extend_from_slice(&self.src_mac);
+        for (i, tci) in self.vlans.iter().enumerate() {
+            let tpid = if i + 1 < self.vlans.len() {
+                ETH_P_8021AD
+            } else {
+                ETH_P_8021Q
+            };
+            push_u16(&mut frame, tpid);
+            push_u16(&mut frame, *tci);
+        }
+        push_u16(&mut frame, ether_type);
+        frame.extend_from_slice(&packet);
+
+        Ok(frame)
+    }
+
+    fn protocol(&self) -> u8 {
+        let is_ipv6 = matches!(self.network, Some(NetworkHeader::Ipv6 { .. }));
+        match self.transport {
+            Some(TransportHeader::Tcp { .. }) => IPPROTO_TCP,
+            Some(TransportHeader::Udp { .. }) => IPPROTO_UDP,
+            Some(TransportHeader::Icmp { .. }) if is_ipv6 => IPPROTO_ICMPV6,
+            Some(TransportHeader::Icmp { .. }) => IPPROTO_ICMP,
+            None => 0xff,
+        }
+    }
+
+    /// Builds the transport header and payload, including the checksum.
+    fn build_transport(&self) -> Result<Vec<u8>, Error> {
+        let mut segment = vec![];
+        let checksum_offset = match self.transport {
+            Some(TransportHeader::Tcp {
+                src_port,
+                dst_port,
+                seq,
+                ack,
+                flags,
+                window,
+            }) => {
+                push_u16(&mut segment, src_port);
+                push_u16(&mut segment, dst_port);
+                push_u32(&mut segment, seq);
+                push_u32(&mut segment, ack);
+                segment.push(((TCP_HLEN / 4) as u8) << 4);
+                segment.push(flags);
+                push_u16(&mut segment, window);
+                push_u16(&mut segment, 0);
+                push_u16(&mut segment, 0);
+                16
+            }
+            Some(TransportHeader::Udp { src_port, dst_port }) => {
+                push_u16(&mut segment, src_port);
+                push_u16(&mut segment, dst_port);
+                push_u16(&mut segment, length(UDP_HLEN + self.payload.len())?);
+                push_u16(&mut segment, 0);
+                6
+            }
+            Some(TransportHeader::Icmp {
+                icmp_type,
+                code,
+                rest,
+            }) => {
+                segment.push(icmp_type);
+                segment.push(code);
+                push_u16(&mut segment, 0);
+                push_u32(&mut segment, rest);
+                2
+            }
+            None => {
+                return Ok(self.payload.clone());
+            }
+        };
+        segment.extend_from_slice(&self.payload);
+
+        /* ICMPv4 is the only one of these without a pseudo-header */
+        let protocol = self.protocol();
+        let mut sum = match &self.network {
+            Some(NetworkHeader::Ipv4 { src, dst, .. }) if protocol != IPPROTO_ICMP => {
+                let mut pseudo = vec![];
+                pseudo.extend_from_slice(&src.octets());
+                pseudo.extend_from_slice(&dst.octets());
+                pseudo.push(0);
+                pseudo.push(protocol);
+                push_u16(&mut pseudo, length(segment.len())?);
+                checksum(&pseudo, 0)
+            }
+            Some(NetworkHeader::Ipv6 { src, dst, .. }) => {
+                let mut pseudo = vec![];
+                pseudo.extend_from_slice(&src.octets());
+                pseudo.extend_from_slice(&dst.octets());
+                push_u32(
+                    &mut pseudo,
+                    segment.len().try_into().or(Err(Error::OutOfRange))?,
+                );
+                push_u32(&mut pseudo, protocol as u32);
+                checksum(&pseudo, 0)
+            }
+            _ => 0,
+        };
+        sum = checksum(&segment, sum);
+
+        /* a computed UDP checksum of zero is transmitted as all ones */
+        let mut sum = finish_checksum(sum);
+        if sum == 0 && protocol == IPPROTO_UDP {
+            sum = 0xffff;
+        }
+        BigEndian::write_u16(&mut segment[checksum_offset..], sum);
+
+        Ok(segment)
+    }
+
+    /// Prepends the network header to the transport segment.
+    fn build_network(&self, segment: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let mut packet = vec![];
+        match &self.network {
+            Some(NetworkHeader::Ipv4 { src, dst, ttl, .. }) => {
+                packet.push(0x40 | (IPV4_HLEN / 4) as u8);
+                packet.push(0);
+                push_u16(&mut packet, length(IPV4_HLEN + segment.len())?);
+                push_u16(&mut packet, 0);
+                push_u16(&mut packet, 0x4000); /* don't fragment */
+                packet.push(*ttl);
+                packet.push(self.protocol());
+                push_u16(&mut packet, 0);
+                packet.extend_from_slice(&src.octets());
+                packet.extend_from_slice(&dst.octets());
+
+                let sum = finish_checksum(checksum(&packet, 0));
+                BigEndian::write_u16(&mut packet[10..], sum);
+            }
+            Some(NetworkHeader::Ipv6 {
+                src,
+                dst,
+                hop_limit,
+                ..
+            }) => {
+                push_u32(&mut packet, 0x60000000);
+                push_u16(&mut packet, length(segment.len())?);
+                packet.push(self.protocol());
+                packet.push(*hop_limit);
+                packet.extend_from_slice(&src.octets());
+                packet.extend_from_slice(&dst.octets());
+            }
+            None => {}
+        }
+        packet.extend_from_slice(&segment);
+
+        Ok(packet)
+    }
+}
+
+/// A decoded Ethernet frame, e.g.: the output packet of a test run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Packet {
+    pub src_mac: [u8; 6],
+    pub dst_mac: [u8; 6],
+
+    /// The tag control information of each VLAN tag, outermost first.
+    pub vlans: Vec<u16>,
+
+    /// The ether type following the VLAN tags.
+    pub ether_type: u16,
+
+    pub network: Option<NetworkHeader>,
+    pub transport: Option<TransportHeader>,
+
+    /// Everything after the innermost header that could be decoded.
+    pub payload: Vec<u8>,
+}
+
+impl Packet {
+    /// Decodes an Ethernet frame. Headers of unknown protocols are left in the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The raw frame, starting with the destination MAC address.
+    pub fn parse(frame: &[u8]) -> Result<Self, Error> {
+        let mut buf = Reader { buf: frame, pos: 0 };
+
+        let dst_mac = buf.array()?;
+        let src_mac = buf.array()?;
+
+        let mut vlans = vec![];
+        let mut ether_type = buf.u16()?;
+        while ether_type == ETH_P_8021Q || ether_type == ETH_P_8021AD {
+            vlans.push(buf.u16()?);
+            ether_type = buf.u16()?;
+        }
+
+        let (network, protocol) = match ether_type {
+            ETH_P_IP => {
+                let ihl = (buf.peek_u8()? & 0x0f) as usize * 4;
+                if ihl < IPV4_HLEN {
+                    return Err(Error::InvalidArgument);
+                }
+                let header = buf.take(ihl)?;
+                let total_len = BigEndian::read_u16(&header[2..]) as usize;
+                buf.truncate(total_len.checked_sub(ihl).ok_or(Error::InvalidArgument)?)?;
+                let ttl = header[8];
+                let protocol = header[9];
+                let network = NetworkHeader::Ipv4 {
+                    src: Ipv4Addr::new(header[12], header[13], header[14], header[15]),
+                    dst: Ipv4Addr::new(header[16], header[17], header[18], header[19]),
+                    ttl,
+                    protocol,
+                };
+                (Some(network), Some(protocol))
+            }
+            ETH_P_IPV6 => {
+                let header = buf.take(IPV6_HLEN)?;
+                buf.truncate(BigEndian::read_u16(&header[4..]) as usize)?;
+                let next_header = header[6];
+                let src: [u8; 16] = header[8..24].try_into().or(Err(Error::InvalidArgument))?;
+                let dst: [u8; 16] = header[24..40].try_into().or(Err(Error::InvalidArgument))?;
+                let network = NetworkHeader::Ipv6 {
+                    src: Ipv6Addr::from(src),
+                    dst: Ipv6Addr::from(dst),
+                    hop_limit: header[7],
+                    next_header,
+                };
+                (Some(network), Some(next_header))
+            }
+            _ => (None, None),
+        };
+
+        let transport = match protocol {
+            Some(IPPROTO_TCP) => {
+                let data_offset = (buf.peek_at(12)? >> 4) as usize * 4;
+                if data_offset < TCP_HLEN {
+                    return Err(Error::InvalidArgument);
+                }
+                let header = buf.take(data_offset)?;
+                Some(TransportHeader::Tcp {
+                    src_port: BigEndian::read_u16(&header[0..]),
+                    dst_port: BigEndian::read_u16(&header[2..]),
+                    seq: BigEndian::read_u32(&header[4..]),
+                    ack: BigEndian::read_u32(&header[8..]),
+                    flags: header[13],
+                    window: BigEndian::read_u16(&header[14..]),
+                })
+            }
+            Some(IPPROTO_UDP) => {
+                let header = buf.take(UDP_HLEN)?;
+                Some(TransportHeader::Udp {
+                    src_port: BigEndian::read_u16(&header[0..]),
+                    dst_port: BigEndian::read_u16(&header[2..]),
+                })
+            }
+            Some(IPPROTO_ICMP) | Some(IPPROTO_ICMPV6) => {
+                let header = buf.take(ICMP_HLEN)?;
+                Some(TransportHeader::Icmp {
+                    icmp_type: header[0],
+                    code: header[1],
+                    rest: BigEndian::read_u32(&header[4..]),
+                })
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            src_mac,
+            dst_mac,
+            vlans,
+            ether_type,
+            network,
+            transport,
+            payload: buf.rest().to_vec(),
+        })
+    }
+}
+
+/// A bounds-checked cursor over a frame being decoded.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos + len;
+        if end > self.buf.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let slice = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        self.take(N)?.try_into().or(Err(Error::InvalidArgument))
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(BigEndian::read_u16(self.take(2)?))
+    }
+
+    fn peek_u8(&self) -> Result<u8, Error> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Result<u8, Error> {
+        self.buf
+            .get(self.pos + offset)
+            .copied()
+            .ok_or(Error::InvalidArgument)
+    }
+
+    /// Drops everything past the next `len` bytes, e.g.: the padding after a packet.
+    fn truncate(&mut self, len: usize) -> Result<(), Error> {
+        let end = self.pos + len;
+        if end > self.buf.len() {
+            return Err(Error::InvalidArgument);
+        }
+        self.buf = &self.buf[..end];
+        Ok(())
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+/// Converts the length of a header and what follows it into a 16-bit length field.
+fn length(len: usize) -> Result<u16, Error> {
+    len.try_into().or(Err(Error::OutOfRange))
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Adds `data` to a running ones' complement sum, as used by the internet checksum.
+fn checksum(data: &[u8], mut sum: u64) -> u64 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += BigEndian::read_u16(chunk) as u64;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u64) << 8;
+    }
+
+    sum
+}
+
+/// Folds a running sum into the final 16-bit checksum.
+fn finish_checksum(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// Computes the internet checksum of `data`; a header with a valid checksum field
+/// sums to zero.
+///
+/// # Arguments
+///
+/// * `data` - The bytes to checksum.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    finish_checksum(checksum(data, 0))
+}
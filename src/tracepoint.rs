@@ -0,0 +1,324 @@
+//! A parser for the `format` file of a classic tracepoint, describing the layout of
+//! the records its programs get as context, and a decoder for records copied out of
+//! the kernel, e.g.: through a map or ring buffer.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::tracepoint::{FieldValue, TracepointFormat};
+//!
+//! let format = "\
+//! name: sched_process_exec
+//! ID: 365
+//! format:
+//! \tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+//! \tfield:int common_pid;\toffset:4;\tsize:4;\tsigned:1;
+//!
+//! \tfield:__data_loc char[] filename;\toffset:8;\tsize:4;\tsigned:0;
+//! \tfield:pid_t pid;\toffset:12;\tsize:4;\tsigned:1;
+//!
+//! print fmt: \"filename=%s pid=%d\", __get_str(filename), REC->pid
+//! ";
+//! let format = TracepointFormat::parse(format).unwrap();
+//! assert_eq!(format.id, 365);
+//! assert_eq!(format.record_size(), 16);
+//!
+//! let mut record = vec![0u8; 16];
+//! record[8..12].copy_from_slice(&(16u32 | 5 << 16).to_ne_bytes());
+//! record[12..16].copy_from_slice(&42i32.to_ne_bytes());
+//! record.extend_from_slice(b"/bin\0");
+//!
+//! let values = format.decode(&record).unwrap();
+//! assert_eq!(values[2], ("filename".to_string(), FieldValue::String("/bin".into())));
+//! assert_eq!(values[3], ("pid".to_string(), FieldValue::Signed(42)));
+//! ```
+
+use crate::error::Error;
+use crate::platform::read_tracefs_event;
+
+use byteorder::{ByteOrder, NativeEndian};
+
+use std::fmt;
+
+/// Where a field's data lives.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FieldLocation {
+    /// In the record at the field's offset.
+    Fixed,
+
+    /// `__data_loc`: the field holds a u32 of the data's length (upper 16 bits) and
+    /// offset from the start of the record (lower 16 bits).
+    DataLoc,
+
+    /// `__rel_loc`: as `DataLoc`, with the offset relative to the end of the field.
+    RelLoc,
+}
+
+/// A field of a tracepoint record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TracepointField {
+    /// The name of the field, e.g.: `filename`.
+    pub name: String,
+
+    /// The C type of the field without any array suffix, e.g.: `const char *`.
+    pub type_name: String,
+
+    /// The offset of the field in the record.
+    pub offset: usize,
+
+    /// The size of the field in the record; 4 for dynamic arrays.
+    pub size: usize,
+
+    /// Whether the field (or its elements) is signed.
+    pub signed: bool,
+
+    /// The number of elements of a fixed-size array, e.g.: 16 for `char comm[16]`.
+    pub array_len: Option<usize>,
+
+    /// Whether the data is in the field or elsewhere in the record.
+    pub location: FieldLocation,
+}
+
+impl TracepointField {
+    /// Whether the field is one of the `common_*` fields every record starts with.
+    pub fn is_common(&self) -> bool {
+        self.name.starts_with("common_")
+    }
+
+    fn is_string(&self) -> bool {
+        let dynamic = self.location != FieldLocation::Fixed;
+        (dynamic || self.array_len.is_some())
+            && matches!(self.type_name.as_str(), "char" | "const char")
+    }
+
+    fn decode(&self, record: &[u8]) -> Result<FieldValue, Error> {
+        let data = record
+            .get(self.offset..self.offset + self.size)
+            .ok_or(Error::OutOfRange)?;
+
+        let data = match self.location {
+            FieldLocation::Fixed => data,
+            FieldLocation::DataLoc | FieldLocation::RelLoc => {
+                let loc = NativeEndian::read_u32(data) as usize;
+                let mut start = loc & 0xffff;
+                if self.location == FieldLocation::RelLoc {
+                    start += self.offset + self.size;
+                }
+                record
+                    .get(start..start + (loc >> 16))
+                    .ok_or(Error::OutOfRange)?
+            }
+        };
+
+        if self.is_string() {
+            let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+            return Ok(FieldValue::String(
+                String::from_utf8_lossy(&data[..end]).into_owned(),
+            ));
+        }
+
+        match self.array_len {
+            Some(len) if self.location == FieldLocation::Fixed && len > 0 => {
+                let elem_size = self.size / len;
+                if elem_size * len != self.size || !matches!(elem_size, 1 | 2 | 4 | 8) {
+                    return Ok(FieldValue::Bytes(data.to_vec()));
+                }
+                Ok(FieldValue::Array(
+                    data.chunks(elem_size)
+                        .map(|elem| self.decode_scalar(elem))
+                        .collect(),
+                ))
+            }
+            None if self.location == FieldLocation::Fixed => Ok(self.decode_scalar(data)),
+            _ => Ok(FieldValue::Bytes(data.to_vec())),
+        }
+    }
+
+    fn decode_scalar(&self, data: &[u8]) -> FieldValue {
+        let (signed, unsigned) = match data.len() {
+            1 => (data[0] as i8 as i64, data[0] as u64),
+            2 => {
+                let v = NativeEndian::read_u16(data);
+                (v as i16 as i64, v as u64)
+            }
+            4 => {
+                let v = NativeEndian::read_u32(data);
+                (v as i32 as i64, v as u64)
+            }
+            8 => {
+                let v = NativeEndian::read_u64(data);
+                (v as i64, v)
+            }
+            _ => return FieldValue::Bytes(data.to_vec()),
+        };
+
+        if self.signed {
+            FieldValue::Signed(signed)
+        } else {
+            FieldValue::Unsigned(unsigned)
+        }
+    }
+}
+
+/// A field decoded from a record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FieldValue {
+    Signed(i64),
+    Unsigned(u64),
+
+    /// A `char` array or dynamic string, up to the first NUL.
+    String(String),
+
+    /// A fixed-size array of integers.
+    Array(Vec<FieldValue>),
+
+    /// Anything else, e.g.: a struct or a dynamic array of non-chars.
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Signed(v) => write!(f, "{}", v),
+            Self::Unsigned(v) => write!(f, "{}", v),
+            Self::String(s) => write!(f, "{:?}", s),
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Self::Bytes(bytes) => {
+                for b in bytes {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The layout of the records of a tracepoint.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TracepointFormat {
+    /// The name of the tracepoint, e.g.: `sched_process_exec`.
+    pub name: String,
+
+    /// The id perf uses for the tracepoint.
+    pub id: u32,
+
+    /// The fields in the order of the format file, common fields first.
+    pub fields: Vec<TracepointField>,
+
+    /// The format string and arguments the kernel prints records with.
+    pub print_fmt: String,
+}
+
+impl TracepointFormat {
+    /// Reads and parses `events/<category>/<name>/format` from tracefs, or from
+    /// debugfs if tracefs isn't mounted.
+    pub fn from_tracefs(category: &str, name: &str) -> Result<Self, Error> {
+        Self::parse(&read_tracefs_event(category, name, "format")?)
+    }
+
+    /// Parses the contents of a format file.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut format = Self::default();
+        let mut id = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix("name:") {
+                format.name = name.trim().to_string();
+            } else if let Some(value) = line.strip_prefix("ID:") {
+                id = Some(value.trim().parse()?);
+            } else if let Some(fmt) = line.strip_prefix("print fmt:") {
+                format.print_fmt = fmt.trim().to_string();
+            } else if line.starts_with("field:") {
+                format.fields.push(Self::parse_field(line)?);
+            }
+        }
+
+        format.id = id.ok_or_else(|| Error::MalformedFormat("no ID".into()))?;
+        Ok(format)
+    }
+
+    /// Looks a field up by name.
+    pub fn field(&self, name: &str) -> Option<&TracepointField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// The size of the fixed part of a record; dynamic arrays follow it.
+    pub fn record_size(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|f| f.offset + f.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Decodes a raw record into the name and value of each field, in order.
+    pub fn decode(&self, record: &[u8]) -> Result<Vec<(String, FieldValue)>, Error> {
+        self.fields
+            .iter()
+            .map(|field| Ok((field.name.clone(), field.decode(record)?)))
+            .collect()
+    }
+
+    /// Parses "field:char comm[16]; offset:8; size:16; signed:1;", tab separated.
+    fn parse_field(line: &str) -> Result<TracepointField, Error> {
+        let malformed = || Error::MalformedFormat(line.to_string());
+
+        let mut decl = None;
+        let (mut offset, mut size, mut signed) = (None, None, false);
+        for part in line.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part.split_once(':').ok_or_else(malformed)?;
+            match key {
+                "field" => decl = Some(value.trim()),
+                "offset" => offset = Some(value.parse()?),
+                "size" => size = Some(value.parse()?),
+                "signed" => signed = value == "1",
+                _ => {}
+            }
+        }
+
+        let mut decl = decl.ok_or_else(malformed)?;
+        let mut location = FieldLocation::Fixed;
+        if let Some(rest) = decl.strip_prefix("__data_loc ") {
+            location = FieldLocation::DataLoc;
+            decl = rest;
+        } else if let Some(rest) = decl.strip_prefix("__rel_loc ") {
+            location = FieldLocation::RelLoc;
+            decl = rest;
+        }
+
+        /* the array suffix is on the type for dynamic arrays, "char[] filename" */
+        let mut array_len = None;
+        let mut decl = decl.replace("[]", "");
+        if let Some(start) = decl.rfind('[') {
+            array_len = decl[start + 1..].trim_end_matches(']').parse().ok();
+            decl.truncate(start);
+        }
+
+        let name_start = decl
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let name = &decl[name_start..];
+        if name.is_empty() {
+            return Err(malformed());
+        }
+
+        Ok(TracepointField {
+            name: name.to_string(),
+            type_name: decl[..name_start].trim().to_string(),
+            offset: offset.ok_or_else(malformed)?,
+            size: size.ok_or_else(malformed)?,
+            signed,
+            array_len,
+            location,
+        })
+    }
+}
@@ -0,0 +1,262 @@
+//! A parser for the verifier log of a program load, e.g.: as written to the logger
+//! passed to `Program::create` or as returned by `Error::verifier_log()`.
+//!
+//! ## Usage
+//! ```
+//! use bpf_api::verifier::VerifierReport;
+//!
+//! let log = "\
+//! 0: R1=ctx() R10=fp0
+//! 0: (b7) r2 = 1                        ; R2_w=1
+//! 1: (95) exit
+//! R0 !read_ok
+//! processed 2 insns (limit 1000000) max_states_per_insn 0 total_states 0 peak_states 0 mark_read 0
+//! ";
+//!
+//! let report = VerifierReport::parse(log);
+//! assert_eq!(report.error.as_deref(), Some("R0 !read_ok"));
+//! assert_eq!(report.error_insn, Some(1));
+//! assert_eq!(report.instructions[0].register_after("R2"), Some("1"));
+//! assert_eq!(report.stats.unwrap().processed_insns, 2);
+//! ```
+
+/// A register or stack slot and the verifier's knowledge of it, e.g.: `("R1", "ctx()")`
+/// or `("fp-8", "mmmmmmmm")`.
+pub type RegisterState = (String, String);
+
+/// An instruction as the verifier walked it. Instructions reached through several
+/// paths show up once per visit.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InsnState {
+    /// The index of the instruction.
+    pub index: usize,
+
+    /// The disassembled instruction, e.g.: `(b7) r0 = 0`.
+    pub text: String,
+
+    /// The source line the instruction came from, if the program has line info.
+    pub source: Option<String>,
+
+    /// The state before the instruction; only logged at verbose level, on function
+    /// entry and after branches.
+    pub registers_before: Vec<RegisterState>,
+
+    /// The registers and stack slots the instruction changed.
+    pub registers_after: Vec<RegisterState>,
+}
+
+impl InsnState {
+    /// Looks up a register's state before the instruction.
+    pub fn register_before(&self, name: &str) -> Option<&str> {
+        find_register(&self.registers_before, name)
+    }
+
+    /// Looks up a register's state after the instruction.
+    pub fn register_after(&self, name: &str) -> Option<&str> {
+        find_register(&self.registers_after, name)
+    }
+}
+
+fn find_register<'a>(registers: &'a [RegisterState], name: &str) -> Option<&'a str> {
+    registers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// The statistics line the verifier ends every log with.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct VerifierStats {
+    /// The number of instructions processed, counting each visit.
+    pub processed_insns: u32,
+
+    /// The most instructions the verifier is willing to process.
+    pub insn_limit: u32,
+
+    pub max_states_per_insn: u32,
+    pub total_states: u32,
+    pub peak_states: u32,
+    pub mark_read: u32,
+}
+
+/// A structured view of a verifier log.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VerifierReport {
+    /// The instructions in the order the verifier walked them.
+    pub instructions: Vec<InsnState>,
+
+    /// Why the program was rejected; `None` if the log shows no error.
+    pub error: Option<String>,
+
+    /// The index of the instruction the verifier stopped at, if it got that far.
+    pub error_insn: Option<usize>,
+
+    /// The final statistics, if the log wasn't truncated before them.
+    pub stats: Option<VerifierStats>,
+}
+
+/// Lines the verifier logs in passing that are neither instructions nor errors.
+const NOISE_PREFIXES: &[&str] = &[
+    "func#",
+    "Func#",
+    "mark_precise:",
+    "last_idx",
+    "regs=",
+    "parent ",
+    "propagating",
+    "Validating",
+    "verification time",
+    "stack depth",
+    "safe",
+    "in ",
+    "caller:",
+    "callee:",
+    "returning from callee:",
+    "to caller at",
+];
+
+impl VerifierReport {
+    /// Parses a verifier log. Lines that aren't recognized are taken as part of the
+    /// error if they follow the last instruction, or come before any instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `log` - The log text.
+    pub fn parse(log: &str) -> Self {
+        let mut report = Self::default();
+        let mut source = None;
+        let mut pending_state: Option<(usize, Vec<RegisterState>)> = None;
+        let mut error_lines = vec![];
+
+        for line in log.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(stats) = Self::parse_stats(line) {
+                report.stats = Some(stats);
+                continue;
+            }
+
+            /* source lines from line info: "; return 0; @ prog.c:12" */
+            if let Some(text) = line.strip_prefix("; ") {
+                source = Some(text.to_string());
+                continue;
+            }
+
+            /* the state after a branch: "from 4 to 6: R0=..." */
+            if let Some(rest) = line.strip_prefix("from ") {
+                if let Some((index, state)) = rest
+                    .split_once(" to ")
+                    .and_then(|(_, rest)| rest.split_once(": "))
+                    .and_then(|(to, state)| Some((to.parse().ok()?, state)))
+                {
+                    pending_state = Some((index, parse_registers(state)));
+                    continue;
+                }
+            }
+
+            if let Some((index, rest)) = line
+                .split_once(": ")
+                .and_then(|(index, rest)| Some((index.parse::<usize>().ok()?, rest)))
+            {
+                if rest.starts_with('(') {
+                    let (text, after) = match rest.split_once(" ; ") {
+                        Some((text, after)) => (text.trim_end(), parse_registers(after)),
+                        None => (rest, vec![]),
+                    };
+
+                    let registers_before = match pending_state.take() {
+                        Some((i, state)) if i == index => state,
+                        _ => vec![],
+                    };
+
+                    report.instructions.push(InsnState {
+                        index,
+                        text: text.to_string(),
+                        source: source.take(),
+                        registers_before,
+                        registers_after: after,
+                    });
+
+                    /* anything logged so far was about an instruction that verified */
+                    error_lines.clear();
+                } else {
+                    pending_state = Some((index, parse_registers(rest)));
+                }
+                continue;
+            }
+
+            if NOISE_PREFIXES.iter().any(|p| line.starts_with(p)) {
+                continue;
+            }
+
+            error_lines.push(line);
+        }
+
+        if !error_lines.is_empty() {
+            report.error = Some(error_lines.join("\n"));
+            report.error_insn = report.instructions.last().map(|i| i.index);
+        }
+
+        report
+    }
+
+    /// Parses "processed N insns (limit N) max_states_per_insn N total_states N
+    /// peak_states N mark_read N".
+    fn parse_stats(line: &str) -> Option<VerifierStats> {
+        let rest = line.strip_prefix("processed ")?;
+        let mut stats = VerifierStats::default();
+
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        stats.processed_insns = words.first()?.parse().ok()?;
+        for pair in words.windows(2) {
+            let value = pair[1].trim_end_matches(')');
+            let field = match pair[0] {
+                "(limit" => &mut stats.insn_limit,
+                "max_states_per_insn" => &mut stats.max_states_per_insn,
+                "total_states" => &mut stats.total_states,
+                "peak_states" => &mut stats.peak_states,
+                "mark_read" => &mut stats.mark_read,
+                _ => continue,
+            };
+            *field = value.parse().ok()?;
+        }
+
+        Some(stats)
+    }
+}
+
+/// Splits "R1=ctx() R2_w=scalar(smin=0, smax=7) fp-8=mmmmmmmm" into registers, taking
+/// care of spaces within parentheses. Liveness marks like `_w` are dropped from names.
+fn parse_registers(text: &str) -> Vec<RegisterState> {
+    let mut registers = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    let mut push = |item: &str| {
+        if let Some((name, value)) = item.trim().split_once('=') {
+            let name = name
+                .trim_end_matches("_rw")
+                .trim_end_matches("_w")
+                .trim_end_matches("_r");
+            registers.push((name.to_string(), value.to_string()));
+        }
+    };
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ' ' if depth == 0 => {
+                push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    push(&text[start..]);
+
+    registers
+}
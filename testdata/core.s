@@ -0,0 +1,129 @@
+# A BPF object with CO-RE relocations against kernel types, storing the relocated
+# values in .bss. Regenerate with:
+#
+#   llvm-mc -triple bpfel -filetype=obj testdata/core.s -o testdata/core.o
+
+	.section	xdp,"ax",@progbits
+	.globl	xdp_core
+	.type	xdp_core,@function
+xdp_core:
+	r1 = results ll
+	# bpf_core_field_offset(struct task_struct___local, pid)
+	r2 = 0
+	*(u64 *)(r1 + 0) = r2
+	# bpf_core_field_exists(struct task_struct___local, no_such_field)
+	r2 = 1
+	*(u64 *)(r1 + 8) = r2
+	# bpf_core_type_size(struct task_struct___local)
+	r2 = 0
+	*(u64 *)(r1 + 16) = r2
+	# bpf_core_enum_value(enum bpf_map_type___local, BPF_MAP_TYPE_RINGBUF)
+	r2 = 0
+	*(u64 *)(r1 + 24) = r2
+	r0 = 2
+	exit
+.Lxdp_core_end:
+	.size	xdp_core, .Lxdp_core_end-xdp_core
+
+# u64 results[4];
+	.section	.bss,"aw",@nobits
+	.globl	results
+	.type	results,@object
+	.p2align	3
+results:
+	.zero	32
+	.size	results, 32
+
+	.section	.BTF,"",@progbits
+	.short	0xeb9f
+	.byte	1
+	.byte	0
+	.long	24
+	.long	0
+	.long	.Ltypes_end-.Ltypes
+	.long	.Ltypes_end-.Ltypes
+	.long	.Lstrings_end-.Lstrings
+.Ltypes:
+	# [1] INT 'int' size=4 bits=32 signed
+	.long	.Ls_int-.Lstrings
+	.long	0x01000000
+	.long	4
+	.long	0x01000020
+	# [2] STRUCT 'task_struct___local' size=8 vlen=2
+	.long	.Ls_task_struct-.Lstrings
+	.long	0x04000002
+	.long	8
+	.long	.Ls_pid-.Lstrings
+	.long	1
+	.long	0
+	.long	.Ls_no_such_field-.Lstrings
+	.long	1
+	.long	32
+	# [3] ENUM 'bpf_map_type___local' size=4 vlen=1
+	.long	.Ls_bpf_map_type-.Lstrings
+	.long	0x06000001
+	.long	4
+	.long	.Ls_ringbuf-.Lstrings
+	.long	0
+.Ltypes_end:
+.Lstrings:
+	.byte	0
+.Ls_int:
+	.asciz	"int"
+.Ls_task_struct:
+	.asciz	"task_struct___local"
+.Ls_pid:
+	.asciz	"pid"
+.Ls_no_such_field:
+	.asciz	"no_such_field"
+.Ls_bpf_map_type:
+	.asciz	"bpf_map_type___local"
+.Ls_ringbuf:
+	.asciz	"BPF_MAP_TYPE_RINGBUF"
+.Ls_xdp:
+	.asciz	"xdp"
+.Ls_access_0:
+	.asciz	"0"
+.Ls_access_0_0:
+	.asciz	"0:0"
+.Ls_access_0_1:
+	.asciz	"0:1"
+.Lstrings_end:
+
+	.section	.BTF.ext,"",@progbits
+	.short	0xeb9f
+	.byte	1
+	.byte	0
+	.long	32
+	.long	0
+	.long	0
+	.long	0
+	.long	0
+	.long	.Lcore_relo-.Lext
+	.long	.Lcore_relo_end-.Lcore_relo
+.Lext:
+.Lcore_relo:
+	.long	16
+	.long	.Ls_xdp-.Lstrings
+	.long	4
+	# insn 2: FIELD_BYTE_OFFSET
+	.long	16
+	.long	2
+	.long	.Ls_access_0_0-.Lstrings
+	.long	0
+	# insn 4: FIELD_EXISTS
+	.long	32
+	.long	2
+	.long	.Ls_access_0_1-.Lstrings
+	.long	2
+	# insn 6: TYPE_SIZE
+	.long	48
+	.long	2
+	.long	.Ls_access_0-.Lstrings
+	.long	9
+	# insn 8: ENUMVAL_VALUE
+	.long	64
+	.long	3
+	.long	.Ls_access_0-.Lstrings
+	.long	11
+.Lcore_relo_end:
@@ -0,0 +1,221 @@
+# A BPF object with extern kconfig values, a typeless ksym and kfuncs, storing what it reads
+# into .bss. Regenerate with:
+#
+#   llvm-mc -triple bpfel -filetype=obj testdata/externs.s -o testdata/externs.o
+
+	.section	xdp,"ax",@progbits
+	.globl	xdp_externs
+	.type	xdp_externs,@function
+xdp_externs:
+	r6 = results ll
+	r1 = LINUX_KERNEL_VERSION ll
+	r1 = *(u32 *)(r1 + 0)
+	*(u64 *)(r6 + 0) = r1
+	r1 = CONFIG_BPF_SYSCALL ll
+	r1 = *(u8 *)(r1 + 0)
+	*(u64 *)(r6 + 8) = r1
+	r1 = CONFIG_HZ ll
+	r1 = *(u32 *)(r1 + 0)
+	*(u64 *)(r6 + 16) = r1
+	r1 = CONFIG_NO_SUCH_OPTION ll
+	r1 = *(u32 *)(r1 + 0)
+	*(u64 *)(r6 + 24) = r1
+	r1 = CONFIG_DEFAULT_HOSTNAME ll
+	r1 = *(u8 *)(r1 + 0)
+	r1 = bpf_prog_put ll
+	*(u64 *)(r6 + 32) = r1
+	call bpf_rcu_read_lock
+	call bpf_rcu_read_unlock
+	r0 = 2
+	exit
+.Lxdp_externs_end:
+	.size	xdp_externs, .Lxdp_externs_end-xdp_externs
+
+	.weak	CONFIG_NO_SUCH_OPTION
+
+# u64 results[5];
+	.section	.bss,"aw",@nobits
+	.globl	results
+	.type	results,@object
+	.p2align	3
+results:
+	.zero	40
+	.size	results, 40
+
+	.section	.BTF,"",@progbits
+	.short	0xeb9f
+	.byte	1
+	.byte	0
+	.long	24
+	.long	0
+	.long	.Ltypes_end-.Ltypes
+	.long	.Ltypes_end-.Ltypes
+	.long	.Lstrings_end-.Lstrings
+.Ltypes:
+	# [1] INT 'int' size=4 bits=32 signed
+	.long	.Ls_int-.Lstrings
+	.long	0x01000000
+	.long	4
+	.long	0x01000020
+	# [2] INT 'unsigned int' size=4 bits=32
+	.long	.Ls_uint-.Lstrings
+	.long	0x01000000
+	.long	4
+	.long	0x00000020
+	# [3] INT '_Bool' size=1 bits=8 bool
+	.long	.Ls_bool-.Lstrings
+	.long	0x01000000
+	.long	1
+	.long	0x04000008
+	# [4] INT 'char' size=1 bits=8 signed
+	.long	.Ls_char-.Lstrings
+	.long	0x01000000
+	.long	1
+	.long	0x01000008
+	# [5] ARRAY elem=[4] index=[1] nelems=16
+	.long	0
+	.long	0x03000000
+	.long	0
+	.long	4
+	.long	1
+	.long	16
+	# [6] VAR 'LINUX_KERNEL_VERSION' type=[2] extern
+	.long	.Ls_kernel_version-.Lstrings
+	.long	0x0e000000
+	.long	2
+	.long	2
+	# [7] VAR 'CONFIG_BPF_SYSCALL' type=[3] extern
+	.long	.Ls_bpf_syscall-.Lstrings
+	.long	0x0e000000
+	.long	3
+	.long	2
+	# [8] VAR 'CONFIG_HZ' type=[1] extern
+	.long	.Ls_hz-.Lstrings
+	.long	0x0e000000
+	.long	1
+	.long	2
+	# [9] VAR 'CONFIG_NO_SUCH_OPTION' type=[1] extern
+	.long	.Ls_no_such_option-.Lstrings
+	.long	0x0e000000
+	.long	1
+	.long	2
+	# [10] VAR 'CONFIG_DEFAULT_HOSTNAME' type=[5] extern
+	.long	.Ls_hostname-.Lstrings
+	.long	0x0e000000
+	.long	5
+	.long	2
+	# [11] CONST -> void
+	.long	0
+	.long	0x0a000000
+	.long	0
+	# [12] VAR 'bpf_prog_put' type=[11] extern
+	.long	.Ls_prog_put-.Lstrings
+	.long	0x0e000000
+	.long	11
+	.long	2
+	# [13] FUNC_PROTO return=void vlen=0
+	.long	0
+	.long	0x0d000000
+	.long	0
+	# [14] FUNC 'bpf_rcu_read_lock' type=[13] extern
+	.long	.Ls_rcu_read_lock-.Lstrings
+	.long	0x0c000002
+	.long	13
+	# [15] FUNC 'bpf_rcu_read_unlock' type=[13] extern
+	.long	.Ls_rcu_read_unlock-.Lstrings
+	.long	0x0c000002
+	.long	13
+	# [16] INT 'u64' size=8 bits=64
+	.long	.Ls_u64-.Lstrings
+	.long	0x01000000
+	.long	8
+	.long	0x00000040
+	# [17] ARRAY elem=[16] index=[1] nelems=5
+	.long	0
+	.long	0x03000000
+	.long	0
+	.long	16
+	.long	1
+	.long	5
+	# [18] VAR 'results' type=[17] global
+	.long	.Ls_results-.Lstrings
+	.long	0x0e000000
+	.long	17
+	.long	1
+	# [19] DATASEC '.bss' size=0 vlen=1
+	.long	.Ls_bss-.Lstrings
+	.long	0x0f000001
+	.long	0
+	.long	18
+	.long	0
+	.long	40
+	# [20] DATASEC '.kconfig' size=0 vlen=5
+	.long	.Ls_kconfig-.Lstrings
+	.long	0x0f000005
+	.long	0
+	.long	6
+	.long	0
+	.long	4
+	.long	7
+	.long	0
+	.long	1
+	.long	8
+	.long	0
+	.long	4
+	.long	9
+	.long	0
+	.long	4
+	.long	10
+	.long	0
+	.long	16
+	# [21] DATASEC '.ksyms' size=0 vlen=3
+	.long	.Ls_ksyms-.Lstrings
+	.long	0x0f000003
+	.long	0
+	.long	12
+	.long	0
+	.long	1
+	.long	14
+	.long	0
+	.long	0
+	.long	15
+	.long	0
+	.long	0
+.Ltypes_end:
+.Lstrings:
+	.byte	0
+.Ls_int:
+	.asciz	"int"
+.Ls_uint:
+	.asciz	"unsigned int"
+.Ls_bool:
+	.asciz	"_Bool"
+.Ls_char:
+	.asciz	"char"
+.Ls_kernel_version:
+	.asciz	"LINUX_KERNEL_VERSION"
+.Ls_bpf_syscall:
+	.asciz	"CONFIG_BPF_SYSCALL"
+.Ls_hz:
+	.asciz	"CONFIG_HZ"
+.Ls_no_such_option:
+	.asciz	"CONFIG_NO_SUCH_OPTION"
+.Ls_hostname:
+	.asciz	"CONFIG_DEFAULT_HOSTNAME"
+.Ls_prog_put:
+	.asciz	"bpf_prog_put"
+.Ls_rcu_read_lock:
+	.asciz	"bpf_rcu_read_lock"
+.Ls_rcu_read_unlock:
+	.asciz	"bpf_rcu_read_unlock"
+.Ls_u64:
+	.asciz	"u64"
+.Ls_results:
+	.asciz	"results"
+.Ls_bss:
+	.asciz	".bss"
+.Ls_kconfig:
+	.asciz	".kconfig"
+.Ls_ksyms:
+	.asciz	".ksyms"
+.Lstrings_end:
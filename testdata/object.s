@@ -0,0 +1,241 @@
+# A BPF object with a legacy map, a BTF-defined map, global variables and an XDP
+# program that counts packets in all of them. Regenerate with:
+#
+#   llvm-mc -triple bpfel -filetype=obj testdata/object.s -o testdata/object.o
+
+	.section	xdp,"ax",@progbits
+	.globl	xdp_count
+	.type	xdp_count,@function
+xdp_count:
+	r1 = 0
+	*(u32 *)(r10 - 4) = r1
+	r2 = r10
+	r2 += -4
+	r1 = counts ll
+	call 1
+	if r0 == 0 goto .Lskip_counts
+	r1 = 1
+	lock *(u64 *)(r0 + 0) += r1
+.Lskip_counts:
+	r2 = r10
+	r2 += -4
+	r1 = hits ll
+	call 1
+	if r0 == 0 goto .Lexit
+	r1 = 1
+	lock *(u64 *)(r0 + 0) += r1
+.Lexit:
+	r1 = increment ll
+	r6 = *(u64 *)(r1 + 0)
+	r1 = packets ll
+	lock *(u64 *)(r1 + 0) += r6
+	r1 = total ll
+	lock *(u64 *)(r1 + 0) += r6
+	r0 = 2
+	exit
+.Lxdp_count_end:
+	.size	xdp_count, .Lxdp_count_end-xdp_count
+
+# const volatile u64 increment = 1;
+	.section	.rodata,"a",@progbits
+	.globl	increment
+	.type	increment,@object
+	.p2align	3
+increment:
+	.quad	1
+	.size	increment, 8
+
+# u64 packets;
+	.section	.bss,"aw",@nobits
+	.globl	packets
+	.type	packets,@object
+	.p2align	3
+packets:
+	.zero	8
+	.size	packets, 8
+
+# u64 version = 7; static u64 total = 100;
+	.section	.data,"aw",@progbits
+	.globl	version
+	.type	version,@object
+	.p2align	3
+version:
+	.quad	7
+	.size	version, 8
+	.type	total,@object
+total:
+	.quad	100
+	.size	total, 8
+
+# struct bpf_map_def counts = { BPF_MAP_TYPE_ARRAY, sizeof(u32), sizeof(u64), 1, 0 };
+	.section	maps,"aw",@progbits
+	.globl	counts
+	.type	counts,@object
+counts:
+	.long	2
+	.long	4
+	.long	8
+	.long	1
+	.long	0
+	.size	counts, 20
+
+# struct { __uint(type, BPF_MAP_TYPE_ARRAY); __uint(max_entries, 1);
+#          __type(key, int); __type(value, u64); } hits SEC(".maps");
+	.section	.maps,"aw",@progbits
+	.globl	hits
+	.type	hits,@object
+hits:
+	.zero	32
+	.size	hits, 32
+
+	.section	.BTF,"",@progbits
+	.short	0xeb9f
+	.byte	1
+	.byte	0
+	.long	24
+	.long	0
+	.long	.Ltypes_end-.Ltypes
+	.long	.Ltypes_end-.Ltypes
+	.long	.Lstrings_end-.Lstrings
+.Ltypes:
+	# [1] INT 'int' size=4 bits=32 signed
+	.long	.Ls_int-.Lstrings
+	.long	0x01000000
+	.long	4
+	.long	0x01000020
+	# [2] ARRAY elem=[1] index=[1] nelems=2
+	.long	0
+	.long	0x03000000
+	.long	0
+	.long	1
+	.long	1
+	.long	2
+	# [3] PTR -> [2]
+	.long	0
+	.long	0x02000000
+	.long	2
+	# [4] ARRAY elem=[1] index=[1] nelems=1
+	.long	0
+	.long	0x03000000
+	.long	0
+	.long	1
+	.long	1
+	.long	1
+	# [5] PTR -> [4]
+	.long	0
+	.long	0x02000000
+	.long	4
+	# [6] INT 'u64' size=8 bits=64
+	.long	.Ls_u64-.Lstrings
+	.long	0x01000000
+	.long	8
+	.long	0x00000040
+	# [7] PTR -> [1]
+	.long	0
+	.long	0x02000000
+	.long	1
+	# [8] PTR -> [6]
+	.long	0
+	.long	0x02000000
+	.long	6
+	# [9] STRUCT size=32 vlen=4
+	.long	0
+	.long	0x04000004
+	.long	32
+	.long	.Ls_type-.Lstrings
+	.long	3
+	.long	0
+	.long	.Ls_max_entries-.Lstrings
+	.long	5
+	.long	64
+	.long	.Ls_key-.Lstrings
+	.long	7
+	.long	128
+	.long	.Ls_value-.Lstrings
+	.long	8
+	.long	192
+	# [10] VAR 'hits' type=[9] global
+	.long	.Ls_hits-.Lstrings
+	.long	0x0e000000
+	.long	9
+	.long	1
+	# [11] DATASEC '.maps' size=0 vlen=1
+	.long	.Ls_maps-.Lstrings
+	.long	0x0f000001
+	.long	0
+	.long	10
+	.long	0
+	.long	32
+	# [12] FUNC_PROTO return=[1] vlen=0
+	.long	0
+	.long	0x0d000000
+	.long	1
+	# [13] FUNC 'xdp_count' type=[12] global
+	.long	.Ls_xdp_count-.Lstrings
+	.long	0x0c000001
+	.long	12
+.Ltypes_end:
+.Lstrings:
+	.byte	0
+.Ls_int:
+	.asciz	"int"
+.Ls_u64:
+	.asciz	"u64"
+.Ls_type:
+	.asciz	"type"
+.Ls_max_entries:
+	.asciz	"max_entries"
+.Ls_key:
+	.asciz	"key"
+.Ls_value:
+	.asciz	"value"
+.Ls_hits:
+	.asciz	"hits"
+.Ls_maps:
+	.asciz	".maps"
+.Ls_xdp:
+	.asciz	"xdp"
+.Ls_xdp_count:
+	.asciz	"xdp_count"
+.Ls_file:
+	.asciz	"object.c"
+.Ls_line_entry:
+	.asciz	"int xdp_count(struct xdp_md *ctx)"
+.Ls_line_return:
+	.asciz	"return XDP_PASS;"
+.Lstrings_end:
+
+	.section	.BTF.ext,"",@progbits
+	.short	0xeb9f
+	.byte	1
+	.byte	0
+	.long	32
+	.long	.Lfunc_info-.Lext
+	.long	.Lfunc_info_end-.Lfunc_info
+	.long	.Lline_info-.Lext
+	.long	.Lline_info_end-.Lline_info
+	.long	.Lline_info_end-.Lext
+	.long	0
+.Lext:
+.Lfunc_info:
+	.long	8
+	.long	.Ls_xdp-.Lstrings
+	.long	1
+	.long	0
+	.long	13
+.Lfunc_info_end:
+.Lline_info:
+	.long	16
+	.long	.Ls_xdp-.Lstrings
+	.long	2
+	# insn 0, line 3
+	.long	0
+	.long	.Ls_file-.Lstrings
+	.long	.Ls_line_entry-.Lstrings
+	.long	3 << 10
+	# insn 27, line 12
+	.long	216
+	.long	.Ls_file-.Lstrings
+	.long	.Ls_line_return-.Lstrings
+	.long	(12 << 10) | 5
+.Lline_info_end:
@@ -0,0 +1,63 @@
+# A BPF object with two entry programs calling into functions in .text: a static
+# one shared by both and a global one that calls the static one in turn. Each call
+# bumps a counter in .bss. Regenerate with:
+#
+#   llvm-mc -triple bpfel -filetype=obj testdata/subprog.s -o testdata/subprog.o
+
+	.text
+# static u64 add_one(u64 x)
+	.type	add_one,@function
+add_one:
+	r2 = calls ll
+	r3 = *(u64 *)(r2 + 0)
+	r3 += 1
+	*(u64 *)(r2 + 0) = r3
+	r0 = r1
+	r0 += 1
+	exit
+.Ladd_one_end:
+	.size	add_one, .Ladd_one_end-add_one
+
+# u64 double_plus_one(u64 x)
+	.globl	double_plus_one
+	.type	double_plus_one,@function
+double_plus_one:
+	r1 <<= 1
+	call add_one
+	exit
+.Ldouble_plus_one_end:
+	.size	double_plus_one, .Ldouble_plus_one_end-double_plus_one
+
+# returns add_one(1) == XDP_PASS
+	.section	xdp,"ax",@progbits
+	.globl	xdp_first
+	.type	xdp_first,@function
+xdp_first:
+	r1 = 1
+	call add_one
+	exit
+.Lxdp_first_end:
+	.size	xdp_first, .Lxdp_first_end-xdp_first
+
+# returns double_plus_one(1) == XDP_TX
+	.section	xdp/second,"ax",@progbits
+	.globl	xdp_second
+	.type	xdp_second,@function
+xdp_second:
+	r1 = 1
+	call double_plus_one
+	exit
+.Lxdp_second_end:
+	.size	xdp_second, .Lxdp_second_end-xdp_second
+
+# u64 calls;
+	.section	.bss,"aw",@nobits
+	.globl	calls
+	.type	calls,@object
+	.p2align	3
+calls:
+	.quad	0
+	.size	calls, 8
+
+	.section	license,"aw",@progbits
+	.asciz	"GPL"